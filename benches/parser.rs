@@ -0,0 +1,86 @@
+//! # Parser benchmark
+//!
+//! Compares hand-written [`Parser`] with the regex based parsing, that was used before it.
+//! Regex path is copied here as it was (with escaping support), so it could be compared
+//! on the same inputs.
+//!
+//! Run with `cargo bench --bench parser`.
+use std::hint::black_box;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion};
+use once_cell::sync::Lazy;
+use pinguino::protocol::parser::Parser;
+use pinguino::protocol::request::{RawRequest, Request};
+use pinguino::protocol::response::Response;
+use regex::Regex;
+
+const SEND: &str = "<CHAT \\ 1.0>\n<Method@Send>\n<Message@'Hello world! How your day is going?'>";
+const SEND_ESCAPED: &str = "<CHAT \\ 1.0>\n<Method@Send>\n<Message@'Don\\'t forget:\\n1 \\> 0'>";
+const RESPONSE: &str = "<CHAT \\ 1.0>\n<Code@10>\n<User@'Jeff'>\n<Time@'2025-01-01 10:00:00'>\n<Timezone@'UTC+3'>\n<Message@'Hello world! How your day is going?'>";
+
+mod regex_path {
+    use super::*;
+
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^<(?P<key>[a-zA-Z]+)@(?P<value>'(?:[^'\\\r\n]|\\.)*'|\b\w+\b)>$").unwrap());
+    static VERSION_HEADER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^<CHAT \\ (?P<version>[1-9]\.[0-9])>$").unwrap());
+
+    fn parse_key_value(line: &str) -> Option<(String, String)> {
+        let caps = RE.captures(line)?;
+        let key = caps.name("key")?.as_str().to_string();
+        let value = caps.name("value")?.as_str();
+
+        let value = match value.strip_prefix('\'').and_then(|val| val.strip_suffix('\'')) {
+            Some(quoted) => pinguino::protocol::escape::unescape(quoted).ok()?.into_owned(),
+            None => value.to_string(),
+        };
+
+        Some((key, value))
+    }
+
+    // Version + every header as owned key value, same work as `from_raw_request` / `from_bytes` did
+    pub fn parse(input: &[u8]) -> Option<(String, Vec<(String, String)>)> {
+        let input = std::str::from_utf8(input).ok()?;
+        let mut lines = input.lines();
+
+        let version = VERSION_HEADER_RE.captures(lines.next()?)?.name("version")?.as_str().to_string();
+        let headers = lines.map(parse_key_value).collect::<Option<Vec<_>>>()?;
+
+        Some((version, headers))
+    }
+}
+
+fn bench_parsers(c: &mut Criterion) {
+    let addr = Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap());
+
+    for (name, input) in [("send", SEND), ("send_escaped", SEND_ESCAPED), ("response", RESPONSE)] {
+        let mut group = c.benchmark_group(name);
+        let bytes = Bytes::from_static(input.as_bytes());
+
+        group.bench_function("regex", |b| b.iter(|| regex_path::parse(black_box(&bytes))));
+
+        group.bench_function("parser", |b| b.iter(|| {
+            let mut parser = Parser::from_bytes(black_box(&bytes)).unwrap();
+            let version = parser.version().unwrap();
+            let headers = parser.map(Result::unwrap).count();
+
+            (version, headers)
+        }));
+
+        if name == "response" {
+            group.bench_function("Response::from_bytes", |b| b.iter(|| Response::from_bytes(black_box(&bytes)).unwrap()));
+        } else {
+            group.bench_function("Request::from_raw_request", |b| b.iter(|| {
+                Request::from_raw_request(RawRequest { bytes: black_box(bytes.clone()), addr: addr.clone() }).unwrap()
+            }));
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_parsers);
+criterion_main!(benches);
@@ -1,464 +0,0 @@
-//! ## `Client`
-//! 
-//! `Client` is the key part of `Pinguino` client (lol, how suprising!). It is dependant on the `tokio` and
-//! `socket2` for providing connection to the server. 
-//! 
-//! [`ClientTrait`] - it is not the best trait. I would totaly move it to something like [`wares`], because
-//! it would be much cleaner approach, and user wont be forced to implement parts that he wants to be default.
-//! 
-//! ## Example
-//! ```
-//! let target = match std::net::SocketAddr::from_str("127.0.0.1:8080").unwrap();
-//! 
-//! let mut client = DefaultClient::new(target);
-//! 
-//! println!("Running!");
-//! let token = client.bind("Jeff".to_string()).await.unwrap();
-//! client.handshake(token).await.unwrap();
-//! 
-//! client.send("Hello world!".to_string()).await.unwrap();
-//! 
-//! let sub = client.subscribe().await;
-//! 
-//! tokio::spawn(async move {
-//! let mut reciever = sub.lock().await;
-//! 
-//! // Reading loop
-//! while let Some(message) = reciever.recv().await {
-//!     println!("Got message:\n{0}", message.pretty_string());
-//! }});
-//! 
-//! // Writing loop
-//! loop {
-//!     let mut stdin = BufReader::new(stdin());
-//!     let mut line = String::new();
-//! 
-//!     match stdin.read_line(&mut line).await {
-//!         Ok(0) => {
-//!             println!("EOF reached or no input provided");
-//!         }
-//!         Ok(_) => {}
-//!         Err(e) => {
-//!             eprintln!("Failed to read line: {}", e);
-//!         }
-//!     }
-//!     if line.strip_suffix('\n').unwrap() == "quit" {
-//!         client.terminate().await.unwrap();
-//!         println!("Terminated");
-//!         break;
-//!     }
-//! 
-//!     client.send(line).await.unwrap();
-//! }
-//! ```
-//! 
-//! [`wares`]: crate::protocol::wares
-use std::net::SocketAddr;
-use std::str::FromStr;
-use std::sync::Arc;
-use tokio::net::TcpStream;
-use tokio::io::{AsyncWriteExt, AsyncReadExt};
-use tokio::select;
-use tokio::sync::mpsc::{self, Receiver, Sender, UnboundedReceiver, UnboundedSender};
-use tokio::sync::Mutex;
-use async_trait::async_trait;
-
-use crate::protocol::request::{ParseError, Request};
-use crate::protocol::response::{Response, ResponseCode};
-use crate::protocol::set_keepalive;
-
-/// ## `DefaultClient`
-/// 
-/// This is the default implementator of the [`ClientTrait`]. 
-/// 
-/// ## Example
-/// ```
-/// let target = match std::net::SocketAddr::from_str("127.0.0.1:8080").unwrap();
-/// 
-/// let mut client = DefaultClient::new(target);
-/// 
-/// println!("Running!");
-/// let token = client.bind("Jeff".to_string()).await.unwrap();
-/// client.handshake(token).await.unwrap();
-/// 
-/// client.send("Hello world!".to_string()).await.unwrap();
-/// 
-/// let sub = client.subscribe().await;
-/// 
-/// tokio::spawn(async move {
-/// let mut reciever = sub.lock().await;
-/// 
-/// // Reading loop
-/// while let Some(message) = reciever.recv().await {
-///     println!("Got message:\n{0}", message.pretty_string());
-/// }});
-/// 
-/// // Writing loop
-/// loop {
-///     let mut stdin = BufReader::new(stdin());
-///     let mut line = String::new();
-/// 
-///     match stdin.read_line(&mut line).await {
-///         Ok(0) => {
-///             println!("EOF reached or no input provided");
-///         }
-///         Ok(_) => {}
-///         Err(e) => {
-///             eprintln!("Failed to read line: {}", e);
-///         }
-///     }
-///     if line.strip_suffix('\n').unwrap() == "quit" {
-///         client.terminate().await.unwrap();
-///         println!("Terminated");
-///         break;
-///     }
-/// 
-///     client.send(line).await.unwrap();
-/// }
-/// ```
-/// 
-/// ## Changes to be made
-/// 1. make it in the way [`wares`]
-/// 2. add `varmap`. Why it doesnt have `varmap`, but [`Router`] do have in the `state` and `app` fields? 
-/// 
-/// [`wares`]: crate::protocol::wares
-/// [`Router`]: crate::router::Router
-#[derive(Debug)]
-pub struct DefaultClient {
-    pub target: SocketAddr,
-    pub out_reciever: Arc<Mutex<Receiver<Request>>>,
-    pub out_sender: Arc<Sender<Request>>,
-    pub in_reciever: Arc<Mutex<UnboundedReceiver<Response>>>,
-    pub in_sender: Arc<UnboundedSender<Response>>,
-    handle: Option<tokio::task::JoinHandle<Result<(), ()>>>
-}
-
-/// ## `ClientTrait`
-/// 
-/// This trait holds main functions, that any client should have to be fullfilable.
-///
-/// ## Changes to be made
-/// 1. make it in the way [`wares`]
-/// 2. add `varmap`. Why it doesnt have `varmap`, but [`Router`] do have in the `state` and `app` fields?
-///  
-/// ## how it looks in the human way
-/// ```
-/// #[async_trait]
-/// pub trait ClientTrait {
-///     fn new(target: SocketAddr) -> Self;
-/// 
-///     async fn bind(&self, name: String) -> Result<String, ClientError>;
-/// 
-///     async fn handshake(&mut self, token: String) -> Result<(), ClientError>;
-/// 
-///     async fn send(&mut self, message: String) -> Result<(), ClientError>;
-/// 
-///     async fn subscribe(&self) -> Arc<Mutex<UnboundedReceiver<Response>>>;
-/// 
-///     async fn terminate(&mut self) -> Result<(), ClientError>;
-/// }
-/// ```
-/// 
-/// [`wares`]: crate::protocol::wares
-/// [`Router`]: crate::router::Router
-
-pub fn new_client() -> Box<> {
-
-}
-
-#[async_trait]
-pub trait ClientTrait {
-    async fn bind(&self, name: String) -> Result<String, ClientError>;
-
-    async fn handshake(&mut self, token: String) -> Result<(), ClientError>;
-
-    async fn send(&mut self, message: String) -> Result<(), ClientError>;
-
-    async fn subscribe(&self) -> Arc<Mutex<UnboundedReceiver<Response>>>;
-
-    async fn terminate(&mut self) -> Result<(), ClientError>;
-}
-
-async fn cool(mut stream: TcpStream, out_recieverr: Arc<Mutex<Receiver<Request>>>, in_sender: Arc<UnboundedSender<Response>>) -> Result<(), ()>{
-    let mut out_reciever = out_recieverr.lock().await;
-    let mut read_buf = [0u8; 512];
-    loop {
-        select! {
-            _ = stream.read(&mut read_buf) => {
-                let response = match Response::from_bytes(&read_buf) {
-                    Ok(val) => val,
-                    Err(_e) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [SUBH] Failed to parse Response from bytes with error {:?}", _e);
-
-                        return Err(());
-                    }
-                };
-                match in_sender.send(response) {
-                    Ok(_) => {
-                        #[cfg(feature = "debug_full")]
-                        println!("--> [SUBH] Sent response to in_sender")
-                    }
-                    Err(_e) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [SUBH] Failed to send via in_sender with error {_e}");
-                        continue;
-                    }
-                }
-            },
-            val = out_reciever.recv() => {
-                if let Some(val) = val {
-                    let bytes = match val.as_bytes() {
-                        Ok(val) => {
-                            #[cfg(feature = "debug_full")]
-                            println!("--> [SUBH] Parsed recieved request from out_reciever to bytes");
-                            
-                            val
-                        },
-                        Err(_) => {
-                            #[cfg(feature = "debug_light")]
-                            println!("<<< [SUBH] Failed to parse request to bytes recieved from out_reciever");
-                            return Err(());
-                        }
-                    };
-
-                    match stream.write(&bytes).await {
-                        Ok(0) => {
-                            #[cfg(feature = "debug_light")]
-                            println!("<<< [SUBH] Connection closed");
-                            return Err(());
-                        },
-                        Ok(_val) => {
-                            #[cfg(feature = "debug_full")]
-                            println!("--> [SUBH] Wrote {_val} bytes to the server");
-                            continue;
-                        },
-                        Err(_e) => {
-                            #[cfg(feature = "debug_light")]
-                            println!("<<< [SUBH] Error occured while writing request to the server with: {_e}");
-                            return Err(());
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
-
-#[allow(unused)]
-#[derive(Debug)]
-pub enum ClientError {
-    CouldntConnect(std::io::Error),
-    ClosedConnection,
-    SendingFailed(std::io::Error),
-    ReadingFailed(std::io::Error),
-    ParseError(ParseError),
-    MissingToken,
-    WrongResponseCoce(ResponseCode),
-    InternalError,
-    NoActiveHandle,
-    AlreadyFinished,
-}
-
-#[async_trait]
-impl ClientTrait for DefaultClient {
-    fn new(target: SocketAddr) -> Box<dyn ClientTrait> {
-        let (out_sender, out_reciever) = mpsc::channel::<Request>(32);
-        let (in_sender, in_reciever) = mpsc::unbounded_channel::<Response>();
-
-        Box::new(DefaultClient {
-            target,
-            out_reciever: Arc::new(Mutex::new(out_reciever)),
-            out_sender: Arc::new(out_sender),
-            in_reciever: Arc::new(Mutex::new(in_reciever)),
-            in_sender: Arc::new(in_sender),
-            handle: None,
-        })
-    }
-
-    async fn bind(&self, name: String) -> Result<String, ClientError> {
-        let mut stream = match TcpStream::connect(self.target).await {
-            Ok(val) => val,
-            Err(e) => { return Err(ClientError::CouldntConnect(e)); }
-        };
-
-        match stream.write(format!("<CHAT \\ 1.0>\n<Method@Bind>\n<Name@{name}>").as_bytes()).await {
-            Ok(0) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [BIND] Closed connection, before it needed");
-
-                return Err(ClientError::ClosedConnection);
-            },
-            Ok(_val) => {
-                #[cfg(feature = "debug_full")]
-                println!("--> [BIND] Sent {_val} bytes to the server");
-
-                let mut read_buf = [0u8; 512];
-                match stream.read(&mut read_buf).await {
-                    Ok(0) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [BIND] Closed connection, before it needed");
-
-                        return Err(ClientError::ClosedConnection);                        
-                    },
-                    Ok(_val) => {
-                        #[cfg(feature = "debug_full")]
-                        println!("--> [BIND] Read {_val} bytes to the server");
-
-                        let response = match Response::from_bytes(&read_buf) {
-                            Ok(val) => val,
-                            Err(e) => { 
-                                #[cfg(feature = "debug_light")]
-                                println!("<<< [BIND] Failed to parse response with error {:?}", e);
-
-                                return Err(ClientError::ParseError(e)); },
-                        };
-                        if let Some(token) = response.token {
-                            return Ok(token)
-                        }
-                        // No real need for else, right?
-                        return Err(ClientError::MissingToken);
-                    },
-                    Err(e) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [BIND] Failed to read from the server with error {e}");
-
-                        return Err(ClientError::ReadingFailed(e));
-                    }
-                }
-            },
-            Err(e) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [BIND] Failed to send request to the server with error: {e}");
-
-                return Err(ClientError::SendingFailed(e));
-            }
-        }
-    }
-
-    async fn handshake(&mut self, token: String) -> Result<(), ClientError> {
-        let stream = match TcpStream::connect(self.target).await {
-            Ok(val) => val,
-            Err(e) => { 
-                #[cfg(feature = "debug_light")]
-                println!("<<< [HAND] Failed to connect to the server with error {e}");
-
-                return Err(ClientError::CouldntConnect(e)); 
-            }
-        };
-        
-        let mut stream = match set_keepalive(stream).await {
-            Ok(val) => val,
-            Err(e) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [HAND] Failed to failed to start keepalive {e}");
-
-                return Err(ClientError::CouldntConnect(e));
-            }
-        };
-
-        match stream.write(format!("<CHAT \\ 1.0>\n<Method@Handshake>\n<Authorization@'{token}'>").as_bytes()).await {
-            Ok(0) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [HAND] Connection closed be it should've");
-
-                return Err(ClientError::ClosedConnection);
-            },
-            Ok(_val) => {
-                #[cfg(feature = "debug_full")]
-                println!("--> [HAND] Sent {_val} bytes to the server");
-
-                let mut read_buf = [0u8; 512];
-                match stream.read(&mut read_buf).await {
-                    Ok(0) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [HAND] Connection closed before it should've");
-
-                        return Err(ClientError::ClosedConnection);
-                    },
-                    Ok(_val) => {
-                        #[cfg(feature = "debug_full")]
-                        println!("--> [HAND] Read {_val} bytes from the server");
-
-                        let response = match Response::from_bytes(&read_buf) {
-                            Ok(val) => val,
-                            Err(e) => {
-                                #[cfg(feature = "debug_light")]
-                                println!("<<< [HAND] Failed to parse response from bytes with error {:?}", e);
-
-                                return Err(ClientError::ParseError(e));
-                            }
-                        };
-
-                        if response.code == ResponseCode::AuthOK {
-                            let handle = tokio::spawn(cool(stream, self.out_reciever.clone(), self.in_sender.clone()));
-                            self.handle = Some(handle);
-                            return Ok(());
-                        }
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [HAND] Wrong response code occured {:?}", response.code);
-                        return Err(ClientError::WrongResponseCoce(response.code));
-                    },
-                    Err(e) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [HAND] Failed to read bytes from the server with error {e}");
-
-                        return Err(ClientError::ReadingFailed(e));
-                    }
-                }
-            }
-            Err(e) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [HAND] Failed to send a request to the server with error {e}");
-
-                return Err(ClientError::SendingFailed(e))
-            }
-        }
-    }
-
-    async fn send(&mut self, message: String) -> Result<(), ClientError> {
-        let message = message.trim_end_matches('\n');
-        let addr = SocketAddr::from_str("127.0.0.1:9999").unwrap(); // Just a place holder.
-        let request = match Request::parse(&format!("<CHAT \\ 1.0>\n<Method@Send>\n<Message@'{0}'>", message), Arc::new(addr)) {
-            Ok(val) => val,
-            Err(e) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [SEND] Failed to parse the request with error {:?}", e);
-                
-                return Err(ClientError::ParseError(e));
-            }
-        };
-        match self.out_sender.send(request).await {
-            Ok(_) => Ok(()),
-            Err(_e) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [SEND] Failed to send the request with error {_e}");
-
-                Err(ClientError::InternalError)
-            }
-        }
-    }
-
-    async fn subscribe(&self) -> Arc<Mutex<UnboundedReceiver<Response>>> {
-        self.in_reciever.clone()
-    }
-
-    async fn terminate(&mut self) -> Result<(), ClientError> {
-        if let Some(handle) = &self.handle {
-            if handle.is_finished() {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [TERM] Failed to terminate the handle, because its already finished");
-                
-                self.handle = None;
-                return Err(ClientError::AlreadyFinished);
-            }
-
-            self.handle = None;
-            return Ok(());
-        }
-        #[cfg(feature = "debug_light")]
-        println!("<<< [TERM] No handle is currently running");
-
-        Err(ClientError::NoActiveHandle)
-    } 
-}
\ No newline at end of file
@@ -29,9 +29,11 @@
 //! 
 //! client.send("Hello world!".to_string()).await.unwrap();
 //! ```
-use crate::protocol::request::{ParseError, Request};
+use crate::protocol::frame::{FrameCodec, FrameError};
+use crate::protocol::request::{ParseError, Request, Version};
 use crate::protocol::response::{Response, ResponseCode};
-use crate::protocol::Varmap;
+use crate::protocol::{set_keepalive, Varmap};
+use crate::router::Connection;
 use std::any::Any;
 use std::fmt::Debug;
 use std::net::SocketAddr;
@@ -39,9 +41,13 @@ use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::mpsc::{self, Receiver, Sender, UnboundedReceiver, UnboundedSender};
 use tokio::sync::Mutex;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::TlsConnector;
 use wares::subscribe::{DefaultSubscribe, SubscribeTrait};
 use wares::terminate::{TerminateTrait, DefaultTerminate};
-use wares::{BindTrait, DefaultBind, DefaultHandshake, DefaultSend, HandshakeTrait, SendTrait};
+use wares::{BindTrait, DefaultBind, DefaultHandshake, DefaultHistory, DefaultSend, HandshakeTrait, HistoryTrait, SendTrait};
 
 pub mod wares;
 
@@ -78,6 +84,11 @@ pub struct ClientState {
     pub in_reciever: Arc<Mutex<UnboundedReceiver<Response>>>,
     pub in_sender: Arc<UnboundedSender<Response>>,
     pub handle: Option<tokio::task::JoinHandle<Result<(), ()>>>,
+    pub codec: FrameCodec,
+    pub version: Version,       // Version client speaks. `CHAT11` by default, so `<Reason@...>` is recieved
+    pub tls: Option<Arc<ClientConfig>>,             // If set, every connection goes over TLS
+    pub server_name: Option<ServerName<'static>>,   // Name certificate is checked against. If not set, `target` ip is used
+    pub password: Option<String>,                   // Sent on `Bind` as `<Password@...>`, if server has the accounts
     pub varmap: Varmap,
 }
 
@@ -111,8 +122,12 @@ pub enum ClientError {
     SendingFailed(std::io::Error),
     ReadingFailed(std::io::Error),
     ParseError(ParseError),
+    FrameError(FrameError),
     MissingToken,
     WrongResponseCoce(ResponseCode),
+    Rejected(String),           // Server couldnt parse the request or didnt like the name, holds `<Reason@...>` it sent
+    UnsupportedVersion(String), // Server doesnt accept client version, holds `<Supported@...>` it sent (empty for `1.0` client)
+    TlsFailed(std::io::Error),  // TLS handshake failed (certificate is not trusted, or server doesnt speak TLS)
     InternalError,
     NoActiveHandle,
     AlreadyFinished,
@@ -153,6 +168,7 @@ pub struct Client {
     ssend: Box<dyn SendTrait>, 
     ssubscribe: Box<dyn SubscribeTrait>,
     sterminate: Box<dyn TerminateTrait>,
+    shistory: Box<dyn HistoryTrait>,
     pub state: Arc<Mutex<ClientState>>,
 }
 
@@ -297,6 +313,26 @@ impl Client {
         self.sterminate.terminate(self.state.clone()).await
     }
 
+    /// `history()` function is needed to execute pre-defined [`HistoryTrait`] function either default or custom one
+    /// provided via [`ClientBuilder`].history(). Messages are oldest first, each has its `<Id@...>`.
+    /// 
+    /// ## Example
+    /// ```
+    /// let client = Client::default();
+    /// 
+    /// client.bind("Jeff".to_string()).await.unwrap();
+    /// 
+    /// // Newest 50 messages
+    /// let page = client.history(None, 50).await.unwrap();
+    /// 
+    /// // And 50 before them
+    /// let oldest = page.first().and_then(|val| val.custom.as_ref()?.get("Id")?.parse().ok());
+    /// let older = client.history(oldest, 50).await.unwrap();
+    /// ```
+    pub async fn history(&self, before: Option<u64>, limit: usize) -> Result<Vec<Response>, ClientError> {
+        self.shistory.history(self.state.clone(), before, limit).await
+    }
+
     /// `insert()` This function is needed to easily insert value into `client.state.varmap`
     /// without 
     pub async fn insert<T: Any + Send + Sync>(&self, val: T) {
@@ -323,6 +359,7 @@ pub struct ClientBuilder {
     ssend: Box<dyn SendTrait>, 
     ssubscribe: Box<dyn SubscribeTrait>,
     sterminate: Box<dyn TerminateTrait>,
+    shistory: Box<dyn HistoryTrait>,
     pub state: Arc<Mutex<ClientState>>,
 }
 
@@ -342,6 +379,7 @@ impl ClientBuilder {
             ssend: Box::new(DefaultSend),
             ssubscribe: Box::new(DefaultSubscribe),
             sterminate: Box::new(DefaultTerminate),
+            shistory: Box::new(DefaultHistory),
             state: Arc::new(Mutex::new(ClientState::new(target, capacity)))
         }
     }
@@ -421,6 +459,97 @@ impl ClientBuilder {
         self
     }
 
+    /// `ClientBuilder::history()` sets custom `history` function instead of the current one
+    /// 
+    /// This function takes `struct` that implements [`HistoryTrait`] as a parametr.
+    /// 
+    /// ## Example
+    /// ```
+    /// let client = ClientBuilder::new(SocketAddr::from_str("127.0.0.1:8080").unwrap(), None)
+    ///     .history(MyCustomHistoryImplementator) // it implements `HistoryTrait` tho.
+    ///     .build();    
+    /// ```
+    pub fn history(mut self, history: Box<dyn HistoryTrait>) -> Self {
+        self.shistory = history;
+        self
+    }
+
+    /// `ClientBuilder::version()` sets protocol version client speaks. By default it is [`Version::LATEST`].
+    /// 
+    /// Old servers, that know only `1.0`, wont understand `1.1` requests, so this is the way to talk with them.
+    /// 
+    /// ## Example
+    /// ```
+    /// let client = ClientBuilder::new(SocketAddr::from_str("127.0.0.1:8080").unwrap(), None)
+    ///     .version(Version::CHAT10)
+    ///     .build();
+    /// ```
+    /// 
+    /// [`Version::LATEST`]: crate::protocol::request::Version::LATEST
+    pub fn version(self, version: Version) -> Self {
+        // Nobody else has the state before client is built, so it is never locked here
+        if let Ok(mut locked) = self.state.try_lock() {
+            locked.version = version;
+        }
+        self
+    }
+
+    /// `ClientBuilder::tls()` makes client talk to the server over TLS. Server certificate has to be signed by one of the `root_store` ones.
+    /// 
+    /// Certificate is checked against the `target` ip, use [`ClientBuilder::server_name`] if it was issued for the domain.
+    /// 
+    /// ## Example
+    /// ```
+    /// let mut root_store = RootCertStore::empty();
+    /// root_store.add(ca_cert)?;
+    /// 
+    /// let client = ClientBuilder::new(SocketAddr::from_str("127.0.0.1:8080").unwrap(), None)
+    ///     .tls(root_store)
+    ///     .build();
+    /// ```
+    pub fn tls(self, root_store: RootCertStore) -> Self {
+        let config = ClientConfig::builder()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+
+        if let Ok(mut locked) = self.state.try_lock() {
+            locked.tls = Some(Arc::new(config));
+        }
+        self
+    }
+
+    /// `ClientBuilder::server_name()` sets the name server certificate is checked against. Makes sense only with [`ClientBuilder::tls`].
+    /// 
+    /// ## Example
+    /// ```
+    /// let client = ClientBuilder::new(SocketAddr::from_str("10.0.0.5:8080").unwrap(), None)
+    ///     .tls(root_store)
+    ///     .server_name(ServerName::try_from("chat.example.com").unwrap())
+    ///     .build();
+    /// ```
+    pub fn server_name(self, name: ServerName<'static>) -> Self {
+        if let Ok(mut locked) = self.state.try_lock() {
+            locked.server_name = Some(name);
+        }
+        self
+    }
+
+    /// `ClientBuilder::password()` sets the password, that is sent with every `Bind`.
+    /// Needed only if server checks them, see `RouterBuilder::authenticator`.
+    /// 
+    /// ## Example
+    /// ```
+    /// let client = ClientBuilder::new(SocketAddr::from_str("127.0.0.1:8080").unwrap(), None)
+    ///     .password("hunter2")
+    ///     .build();
+    /// ```
+    pub fn password(self, password: &str) -> Self {
+        if let Ok(mut locked) = self.state.try_lock() {
+            locked.password = Some(password.to_string());
+        }
+        self
+    }
+
     /// `ClientBuilder::insert()` inserts value into `state.varmap` before [`Client`] is built.
     /// 
     /// ## Example
@@ -454,6 +583,7 @@ impl ClientBuilder {
             ssend: self.ssend,
             ssubscribe: self.ssubscribe,
             sterminate: self.sterminate,
+            shistory: self.shistory,
             state: self.state
         }
     }
@@ -487,7 +617,79 @@ impl ClientState {
             in_sender: Arc::new(in_sender),
             in_reciever: Arc::new(Mutex::new(in_reciever)),
             handle: None,
+            codec: FrameCodec::default(),
+            version: Version::LATEST,
+            tls: None,
+            server_name: None,
+            password: None,
             varmap: Varmap::new()
         }
     }
+
+    /// Connects to the `target`, over TLS if it is set. `keepalive` is for long-lived connections (`Handshake`).
+    /// 
+    /// ## Example
+    /// ```
+    /// // Somewhere inside of the custom BindTrait
+    /// let locked = state.lock().await;
+    /// let stream = FrameReader::new(locked.connect(false).await?, locked.codec);
+    /// ```
+    pub async fn connect(&self, keepalive: bool) -> Result<Box<dyn Connection>, ClientError> {
+        self.connector().connect(keepalive).await
+    }
+
+    /// Everything that is needed to connect, so it could be done without holding the state lock.
+    /// 
+    /// ## Example
+    /// ```
+    /// let connector = state.lock().await.connector();
+    /// let stream = connector.connect(false).await?;
+    /// ```
+    pub fn connector(&self) -> Connector {
+        Connector {
+            target: self.target,
+            tls: self.tls.clone(),
+            server_name: self.server_name.clone(),
+        }
+    }
+}
+
+/// ## `Connector`
+/// 
+/// Copy of the [`ClientState`] connection settings, see [`ClientState::connector`].
+#[derive(Debug, Clone)]
+pub struct Connector {
+    pub target: SocketAddr,
+    pub tls: Option<Arc<ClientConfig>>,
+    pub server_name: Option<ServerName<'static>>,
+}
+
+impl Connector {
+    /// Connects to the `target`, over TLS if it is set. Same as [`ClientState::connect`].
+    pub async fn connect(&self, keepalive: bool) -> Result<Box<dyn Connection>, ClientError> {
+        let stream = match TcpStream::connect(self.target).await {
+            Ok(val) => val,
+            Err(e) => return Err(ClientError::CouldntConnect(e)),
+        };
+
+        if keepalive {
+            if let Err(e) = set_keepalive(&stream) {
+                return Err(ClientError::CouldntConnect(e));
+            }
+        }
+
+        let Some(config) = &self.tls else {
+            return Ok(Box::new(stream));
+        };
+
+        let name = match &self.server_name {
+            Some(name) => name.clone(),
+            None => ServerName::IpAddress(self.target.ip().into()),
+        };
+
+        match TlsConnector::from(config.clone()).connect(name, stream).await {
+            Ok(val) => Ok(Box::new(val)),
+            Err(e) => Err(ClientError::TlsFailed(e)),
+        }
+    }
 }
\ No newline at end of file
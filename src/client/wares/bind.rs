@@ -1,8 +1,9 @@
 use std::sync::Arc;
-use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream, sync::Mutex};
+use tokio::sync::Mutex;
 use std::fmt::Debug;
+use tracing::{trace, warn};
 
-use crate::{client::{ClientError, ClientState}, protocol::response::Response};
+use crate::{client::{ClientError, ClientState}, protocol::{escape::escape, frame::{write_frame, FrameError, FrameReader}, response::{Response, ResponseCode}}};
 
 /// ## `Bind`
 /// 
@@ -49,39 +50,31 @@ pub trait BindTrait: Debug + Send + Sync {
 /// impl BindTrait for DefaultBind {
 ///     async fn bind(state: Arc<Mutex<ClientState>>, name: String) -> Result<(), ClientError> {
 ///         let locked = state.lock().await;
-///         let mut stream = match TcpStream::connect(locked.target).await {
-///             Ok(val) => val,
-///             Err(e) => { return Err(ClientError::CouldntConnect(e)); }
-///         };
+///         // Plain TCP or TLS, depending on the state
+///         let mut stream = FrameReader::new(locked.connect(false).await?, locked.codec);
 /// 
-///         match stream.write(format!("<CHAT \\ 1.0>\n<Method@Bind>\n<Name@{name}>").as_bytes()).await {
+///         match write_frame(stream.get_mut(), &locked.codec, format!("<CHAT \\ {0}>\n<Method@Bind>\n<Name@'{1}'>", locked.version.to_str(), escape(&name)).as_bytes()).await {
 ///             Ok(0) => {
-///                 #[cfg(feature = "debug_light")]
-///                 println!("<<< [BIND] Closed connection, before it needed");
+///                 warn!("Closed connection, before it needed");
 /// 
 ///                 return Err(ClientError::ClosedConnection);
 ///             },
-///             Ok(_val) => {
-///                 #[cfg(feature = "debug_full")]
-///                 println!("--> [BIND] Sent {_val} bytes to the server");
+///             Ok(val) => {
+///                 trace!(bytes = val, "Sent bytes to the server");
 /// 
-///                 let mut read_buf = [0u8; 512];
-///                 match stream.read(&mut read_buf).await {
-///                     Ok(0) => {
-///                         #[cfg(feature = "debug_light")]
-///                         println!("<<< [BIND] Closed connection, before it needed");
+///                 match stream.read_frame().await {
+///                     Ok(None) => {
+///                         warn!("Closed connection, before it needed");
 /// 
 ///                         return Err(ClientError::ClosedConnection);                        
 ///                     },
-///                     Ok(_val) => {
-///                         #[cfg(feature = "debug_full")]
-///                         println!("--> [BIND] Read {_val} bytes to the server");
+///                     Ok(Some(payload)) => {
+///                         trace!(bytes = payload.len(), "Read bytes to the server");
 /// 
-///                         let response = match Response::from_bytes(&read_buf) {
+///                         let response = match Response::from_bytes(&payload) {
 ///                             Ok(val) => val,
 ///                             Err(e) => { 
-///                                 #[cfg(feature = "debug_light")]
-///                                 println!("<<< [BIND] Failed to parse response with error {:?}", e);
+///                                 warn!(error = ?e, "Failed to parse response");
 /// 
 ///                                 return Err(ClientError::ParseError(e)); },
 ///                         };
@@ -92,16 +85,14 @@ pub trait BindTrait: Debug + Send + Sync {
 ///                         return Err(ClientError::MissingToken);
 ///                     },
 ///                     Err(e) => {
-///                         #[cfg(feature = "debug_light")]
-///                         println!("<<< [BIND] Failed to read from the server with error {e}");
+///                         warn!(error = %e, "Failed to read from the server");
 /// 
-///                         return Err(ClientError::ReadingFailed(e));
+///                         return Err(ClientError::FrameError(e));
 ///                     }
 ///                 }
 ///             },
 ///             Err(e) => {
-///                 #[cfg(feature = "debug_light")]
-///                 println!("<<< [BIND] Failed to send request to the server with error: {e}");
+///                 warn!(error = %e, "Failed to send request to the server");
 /// 
 ///                 return Err(ClientError::SendingFailed(e));
 ///             }
@@ -124,47 +115,71 @@ impl BindTrait for DefaultBind {
         let mut locked = state.lock().await;
 
         // Connecting to the server
-        let mut stream = match TcpStream::connect(locked.target).await {
-            Ok(val) => val,
-            Err(e) => { return Err(ClientError::CouldntConnect(e)); }
-        };
+        let mut stream = FrameReader::new(locked.connect(false).await?, locked.codec);
+
+        let mut request = format!("<CHAT \\ {0}>\n<Method@Bind>\n<Name@'{1}'>", locked.version.to_str(), escape(&name));
+        if let Some(password) = &locked.password {
+            request.push_str(&format!("\n<Password@'{0}'>", escape(password)));
+        }
 
         // Writing to the stream
-        match stream.write(format!("<CHAT \\ 1.0>\n<Method@Bind>\n<Name@{name}>").as_bytes()).await {
+        match write_frame(stream.get_mut(), &locked.codec, request.as_bytes()).await {
             Ok(0) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [BIND] Closed connection, before it needed");
+                warn!("Closed connection, before it needed");
 
                 return Err(ClientError::ClosedConnection);
             },
-            Ok(_val) => {
-                #[cfg(feature = "debug_full")]
-                println!("--> [BIND] Sent {_val} bytes to the server");
+            Ok(val) => {
+                trace!(bytes = val, "Sent bytes to the server");
 
                 // Server responded -> We need to extract `token`
-                let mut read_buf = [0u8; 512];
-                match stream.read(&mut read_buf).await {
-                    Ok(0) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [BIND] Closed connection, before it needed");
+                match stream.read_frame().await {
+                    Ok(None) => {
+                        warn!("Closed connection, before it needed");
 
                         // Unable to extract token, when connection is closed.
                         return Err(ClientError::ClosedConnection);                        
                     },
-                    Ok(_val) => {
-                        #[cfg(feature = "debug_full")]
-                        println!("--> [BIND] Read {_val} bytes from the server");
+                    Ok(Some(payload)) => {
+                        trace!(bytes = payload.len(), "Read bytes from the server");
 
                         // Response extraction
-                        let response = match Response::from_bytes(&read_buf) {
+                        let response = match Response::from_bytes(&payload) {
                             Ok(val) => val,
                             Err(e) => { 
-                                #[cfg(feature = "debug_light")]
-                                println!("<<< [BIND] Failed to parse response with error {:?}", e);
+                                warn!(error = ?e, "Failed to parse response");
 
                                 return Err(ClientError::ParseError(e)); },
                         };
 
+                        // Server didnt like the request, and told why
+                        if response.code == ResponseCode::ParseError {
+                            warn!(reason = ?response.reason, "Request was rejected");
+
+                            return Err(ClientError::Rejected(response.reason.unwrap_or_default()));
+                        }
+
+                        if response.code == ResponseCode::UnsupportedVersion {
+                            warn!(version = locked.version.to_str(), "Server doesnt accept the version");
+
+                            let supported = response.custom.and_then(|mut custom| custom.remove("Supported"));
+                            return Err(ClientError::UnsupportedVersion(supported.unwrap_or_default()));
+                        }
+
+                        // Name is against the server name policy
+                        if response.code == ResponseCode::InvalidName {
+                            warn!(reason = ?response.reason, "Name was rejected");
+
+                            return Err(ClientError::Rejected(response.reason.unwrap_or_default()));
+                        }
+
+                        // Wrong password, or no such user
+                        if response.code == ResponseCode::Unauthorized {
+                            warn!(reason = ?response.reason, "Server didnt accept the credentials");
+
+                            return Err(ClientError::WrongResponseCoce(response.code));
+                        }
+
                         // Trying to get the token
                         if let Some(token) = response.token {
                             locked.token = Some(token);
@@ -174,17 +189,20 @@ impl BindTrait for DefaultBind {
 
                         return Err(ClientError::MissingToken);
                     },
-                    Err(e) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [BIND] Failed to read from the server with error {e}");
+                    Err(FrameError::Io(e)) => {
+                        warn!(error = %e, "Failed to read from the server");
 
                         return Err(ClientError::ReadingFailed(e));
+                    },
+                    Err(e) => {
+                        warn!(error = %e, "Failed to read frame from the server");
+
+                        return Err(ClientError::FrameError(e));
                     }
                 }
             },
             Err(e) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [BIND] Failed to send request to the server with error: {e}");
+                warn!(error = %e, "Failed to send request to the server");
 
                 return Err(ClientError::SendingFailed(e));
             }
@@ -1,8 +1,9 @@
 use std::sync::Arc;
 use std::fmt::Debug;
-use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream, select, sync::{mpsc::{Receiver, UnboundedSender}, Mutex}};
+use tokio::{select, sync::{mpsc::{Receiver, UnboundedSender}, Mutex}};
+use tracing::{trace, warn};
 
-use crate::{client::{ClientError, ClientState}, protocol::{request::Request, response::{Response, ResponseCode}, set_keepalive}};
+use crate::{client::{ClientError, ClientState}, protocol::{escape::escape, frame::{write_frame, FrameError, FrameReader}, request::Request, response::{Response, ResponseCode}}, router::Connection};
 
 /// ## `DefaultHandshake`
 /// 
@@ -44,60 +45,45 @@ impl HandshakeTrait for DefaultHandshake {
             return Err(ClientError::MissingToken);
         };
 
-        // Connecting
-        let stream = match TcpStream::connect(locked.target).await {
+        // Connecting, with keepalive, because connection stays
+        let stream = match locked.connect(true).await {
             Ok(val) => val,
             Err(e) => { 
-                #[cfg(feature = "debug_light")]
-                println!("<<< [HAND] Failed to connect to the server with error {e}");
+                warn!(error = ?e, "Failed to connect to the server");
 
-                return Err(ClientError::CouldntConnect(e)); 
+                return Err(e); 
             }
         };
-        
-        // Setting keepalive
-        let mut stream = match set_keepalive(stream).await {
-            Ok(val) => val,
-            Err(e) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [HAND] Failed to failed to start keepalive {e}");
 
-                return Err(ClientError::CouldntConnect(e));
-            }
-        };
+        // Reader is handed to the event_loop later, so whatever server sent after the response is kept
+        let mut stream = FrameReader::new(stream, locked.codec);
 
         // Sending the request
         //
         // If there is a need in custom EndingBytesware for the Client you could create function and call it here
         // before sending.
-        match stream.write(format!("<CHAT \\ 1.0>\n<Method@Handshake>\n<Authorization@'{token}'>").as_bytes()).await {
+        match write_frame(stream.get_mut(), &locked.codec, format!("<CHAT \\ {0}>\n<Method@Handshake>\n<Authorization@'{1}'>", locked.version.to_str(), escape(token)).as_bytes()).await {
             Ok(0) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [HAND] Connection closed be it should've");
+                warn!("Connection closed before it should've");
 
                 return Err(ClientError::ClosedConnection);
             },
-            Ok(_val) => {
-                #[cfg(feature = "debug_full")]
-                println!("--> [HAND] Sent {_val} bytes to the server");
+            Ok(val) => {
+                trace!(bytes = val, "Sent bytes to the server");
 
-                let mut read_buf = [0u8; 512];
-                match stream.read(&mut read_buf).await {
-                    Ok(0) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [HAND] Connection closed before it should've");
+                match stream.read_frame().await {
+                    Ok(None) => {
+                        warn!("Connection closed before it should've");
 
                         return Err(ClientError::ClosedConnection);
                     },
-                    Ok(_val) => {
-                        #[cfg(feature = "debug_full")]
-                        println!("--> [HAND] Read {_val} bytes from the server");
+                    Ok(Some(payload)) => {
+                        trace!(bytes = payload.len(), "Read bytes from the server");
 
-                        let response = match Response::from_bytes(&read_buf) {
+                        let response = match Response::from_bytes(&payload) {
                             Ok(val) => val,
                             Err(e) => {
-                                #[cfg(feature = "debug_light")]
-                                println!("<<< [HAND] Failed to parse response from bytes with error {:?}", e);
+                                warn!(error = ?e, "Failed to parse response from bytes");
 
                                 return Err(ClientError::ParseError(e));
                             }
@@ -108,21 +94,36 @@ impl HandshakeTrait for DefaultHandshake {
                             locked.handle = Some(handle);
                             return Ok(());
                         }
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [HAND] Wrong response code occured {:?}", response.code);
+                        if response.code == ResponseCode::ParseError {
+                            warn!(reason = ?response.reason, "Request was rejected");
+
+                            return Err(ClientError::Rejected(response.reason.unwrap_or_default()));
+                        }
+
+                        if response.code == ResponseCode::UnsupportedVersion {
+                            warn!(version = locked.version.to_str(), "Server doesnt accept the version");
+
+                            let supported = response.custom.and_then(|mut custom| custom.remove("Supported"));
+                            return Err(ClientError::UnsupportedVersion(supported.unwrap_or_default()));
+                        }
+
+                        warn!(code = ?response.code, "Wrong response code occured");
                         return Err(ClientError::WrongResponseCoce(response.code));
                     },
-                    Err(e) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [HAND] Failed to read bytes from the server with error {e}");
+                    Err(FrameError::Io(e)) => {
+                        warn!(error = %e, "Failed to read bytes from the server");
 
                         return Err(ClientError::ReadingFailed(e));
+                    },
+                    Err(e) => {
+                        warn!(error = %e, "Failed to read frame from the server");
+
+                        return Err(ClientError::FrameError(e));
                     }
                 }
             }
             Err(e) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [HAND] Failed to send a request to the server with error {e}");
+                warn!(error = %e, "Failed to send a request to the server");
 
                 return Err(ClientError::SendingFailed(e))
             }
@@ -130,29 +131,41 @@ impl HandshakeTrait for DefaultHandshake {
     }
 }
 
-pub async fn event_loop(mut stream: TcpStream, out_recieverr: Arc<Mutex<Receiver<Request>>>, in_sender: Arc<UnboundedSender<Response>>) -> Result<(), ()>{
+pub async fn event_loop(mut stream: FrameReader<Box<dyn Connection>>, out_recieverr: Arc<Mutex<Receiver<Request>>>, in_sender: Arc<UnboundedSender<Response>>) -> Result<(), ()>{
     let mut out_reciever = out_recieverr.lock().await;
-    let mut read_buf = [0u8; 512];
+    let codec = *stream.codec();
     loop {
         select! {
-            _ = stream.read(&mut read_buf) => {
-                let response = match Response::from_bytes(&read_buf) {
+            // read_frame() is cancel safe, so if out_reciever wins, half-read frame is still there
+            val = stream.read_frame() => {
+                let payload = match val {
+                    Ok(Some(val)) => val,
+                    Ok(None) => {
+                        warn!("Connection closed");
+
+                        return Err(());
+                    },
+                    Err(e) => {
+                        warn!(error = %e, "Failed to read frame");
+
+                        return Err(());
+                    }
+                };
+
+                let response = match Response::from_bytes(&payload) {
                     Ok(val) => val,
-                    Err(_e) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [SUBH] Failed to parse Response from bytes with error {:?}", _e);
+                    Err(e) => {
+                        warn!(error = ?e, "Failed to parse Response from bytes");
 
                         return Err(());
                     }
                 };
                 match in_sender.send(response) {
                     Ok(_) => {
-                        #[cfg(feature = "debug_full")]
-                        println!("--> [SUBH] Sent response to in_sender")
+                        trace!("Sent response to in_sender")
                     }
-                    Err(_e) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [SUBH] Failed to send via in_sender with error {_e}");
+                    Err(e) => {
+                        warn!(error = %e, "Failed to send via in_sender");
                         continue;
                     }
                 }
@@ -161,32 +174,27 @@ pub async fn event_loop(mut stream: TcpStream, out_recieverr: Arc<Mutex<Receiver
                 if let Some(val) = val {
                     let bytes = match val.as_bytes() {
                         Ok(val) => {
-                            #[cfg(feature = "debug_full")]
-                            println!("--> [SUBH] Parsed recieved request from out_reciever to bytes");
+                            trace!("Parsed recieved request from out_reciever to bytes");
                             
                             val
                         },
                         Err(_) => {
-                            #[cfg(feature = "debug_light")]
-                            println!("<<< [SUBH] Failed to parse request to bytes recieved from out_reciever");
+                            warn!("Failed to parse request to bytes recieved from out_reciever");
                             return Err(());
                         }
                     };
 
-                    match stream.write(&bytes).await {
+                    match write_frame(stream.get_mut(), &codec, &bytes).await {
                         Ok(0) => {
-                            #[cfg(feature = "debug_light")]
-                            println!("<<< [SUBH] Connection closed");
+                            warn!("Connection closed");
                             return Err(());
                         },
-                        Ok(_val) => {
-                            #[cfg(feature = "debug_full")]
-                            println!("--> [SUBH] Wrote {_val} bytes to the server");
+                        Ok(val) => {
+                            trace!(bytes = val, "Wrote bytes to the server");
                             continue;
                         },
-                        Err(_e) => {
-                            #[cfg(feature = "debug_light")]
-                            println!("<<< [SUBH] Error occured while writing request to the server with: {_e}");
+                        Err(e) => {
+                            warn!(error = %e, "Error occured while writing request to the server");
                             return Err(());
                         }
                     }
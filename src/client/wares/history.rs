@@ -0,0 +1,158 @@
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use std::fmt::Debug;
+use tracing::{trace, warn};
+
+use crate::{client::{ClientError, ClientState}, protocol::{escape::escape, frame::{write_frame, FrameError, FrameReader}, response::{Response, ResponseCode}}};
+
+/// ## `History`
+///
+/// This trait is responsible for implementing `History` request from client to the server.
+/// It asks for up to `limit` messages older than `before` id (or the newest ones, if it is None), oldest first.
+///
+/// ## Ideal usage
+/// 1. Use `bind()` for getting the token
+/// 2. Use `history(None, 50)` for the newest messages
+/// 3. Use `history(Some(oldest_id), 50)` for the ones before them, where `oldest_id` is `<Id@...>` of the first one
+///
+/// ## How it looks like in the human way
+///
+/// ```
+/// #[async_trait::async_trait]
+/// pub trait HistoryTrait: Debug + Send + Sync {
+///     async fn history(&self, state: Arc<Mutex<ClientState>>, before: Option<u64>, limit: usize) -> Result<Vec<Response>, ClientError>;
+/// }
+/// ```
+///
+/// ## How to implement
+/// See [`DefaultHistory`] for default implementation code.
+#[async_trait::async_trait]
+pub trait HistoryTrait: Debug + Send + Sync {
+    async fn history(&self, state: Arc<Mutex<ClientState>>, before: Option<u64>, limit: usize) -> Result<Vec<Response>, ClientError>;
+}
+
+/// ## `DefaultHistory`
+///
+/// This is the default implementator of the `History`. It opens its own connection (same as `Bind`), so it works
+/// with or without the `Handshake`, sends the token and reads messages until the answer with `<End@'History'>`.
+/// State is not locked while it waits, so `send` and others go on meanwhile.
+///
+/// ## How does it look in the human way:
+///
+/// ```
+/// #[derive(Debug)]
+/// pub struct DefaultHistory;
+///
+/// impl HistoryTrait for DefaultHistory {
+///     async fn history(&self, state: Arc<Mutex<ClientState>>, before: Option<u64>, limit: usize) -> Result<Vec<Response>, ClientError> {
+///         let locked = state.lock().await;
+///         let token = locked.token.clone().ok_or(ClientError::MissingToken)?;
+///         let (connector, codec, version) = (locked.connector(), locked.codec, locked.version);
+///         drop(locked);
+///
+///         let mut stream = FrameReader::new(connector.connect(false).await?, codec);
+///         write_frame(stream.get_mut(), &codec, request.as_bytes()).await?;
+///
+///         let mut messages = Vec::new();
+///         loop {
+///             let response = Response::from_bytes(&stream.read_frame().await??)?;
+///             if is_end(&response) {
+///                 return Ok(messages);
+///             }
+///             messages.push(response);
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct DefaultHistory;
+
+#[async_trait::async_trait]
+impl HistoryTrait for DefaultHistory {
+    async fn history(&self, state: Arc<Mutex<ClientState>>, before: Option<u64>, limit: usize) -> Result<Vec<Response>, ClientError> {
+        let locked = state.lock().await;
+
+        // Without the token server wouldnt know who is asking
+        let Some(token) = locked.token.clone() else {
+            warn!("There is no token, bind first");
+
+            return Err(ClientError::MissingToken);
+        };
+
+        // Page could take a while, so the lock is not held for it
+        let (connector, codec, version) = (locked.connector(), locked.codec, locked.version);
+        drop(locked);
+
+        // Connecting to the server
+        let mut stream = FrameReader::new(connector.connect(false).await?, codec);
+
+        let mut request = format!("<CHAT \\ {0}>\n<Method@History>\n<Limit@{1}>\n<Authorization@'{2}'>", version.to_str(), limit, escape(&token));
+        if let Some(before) = before {
+            request.push_str(&format!("\n<Before@{before}>"));
+        }
+
+        match write_frame(stream.get_mut(), &codec, request.as_bytes()).await {
+            Ok(0) => {
+                warn!("Closed connection, before it needed");
+
+                return Err(ClientError::ClosedConnection);
+            },
+            Ok(val) => {
+                trace!(bytes = val, "Sent bytes to the server");
+            },
+            Err(e) => {
+                warn!(error = %e, "Failed to send request to the server");
+
+                return Err(ClientError::SendingFailed(e));
+            }
+        }
+
+        // Messages go first, then the answer to the request
+        let mut messages = Vec::new();
+        loop {
+            let payload = match stream.read_frame().await {
+                Ok(Some(val)) => val,
+                Ok(None) => {
+                    warn!("Closed connection, before it needed");
+
+                    return Err(ClientError::ClosedConnection);
+                },
+                Err(FrameError::Io(e)) => {
+                    warn!(error = %e, "Failed to read from the server");
+
+                    return Err(ClientError::ReadingFailed(e));
+                },
+                Err(e) => {
+                    warn!(error = %e, "Failed to read frame from the server");
+
+                    return Err(ClientError::FrameError(e));
+                }
+            };
+
+            trace!(bytes = payload.len(), "Read bytes from the server");
+
+            let response = match Response::from_bytes(&payload) {
+                Ok(val) => val,
+                Err(e) => {
+                    warn!(error = ?e, "Failed to parse response");
+
+                    return Err(ClientError::ParseError(e));
+                }
+            };
+
+            let is_end = response.custom.as_ref().and_then(|custom| custom.get("End")).is_some_and(|val| val == "History");
+            if is_end {
+                return Ok(messages);
+            }
+
+            // Server answered without the page, so something is wrong
+            if response.code != ResponseCode::OK {
+                warn!(reason = ?response.reason, "Server didnt give the history");
+
+                return Err(ClientError::WrongResponseCoce(response.code));
+            }
+
+            messages.push(response);
+        }
+    }
+}
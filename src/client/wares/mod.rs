@@ -6,10 +6,12 @@
 //! [`Router`]: crate::router::Router
 pub mod bind;
 pub mod handshake;
+pub mod history;
 pub mod send;
 pub mod subscribe;
 pub mod terminate;
 
 pub use bind::{BindTrait, DefaultBind};
 pub use handshake::{HandshakeTrait, DefaultHandshake};
+pub use history::{HistoryTrait, DefaultHistory};
 pub use send::{SendTrait, DefaultSend};
@@ -4,7 +4,9 @@ use std::sync::Arc;
 use std::net::SocketAddr;
 use tokio::sync::Mutex;
 use crate::client::{ClientError, ClientState};
+use crate::protocol::escape::escape;
 use crate::protocol::request::Request;
+use tracing::warn;
 
 #[async_trait::async_trait]
 pub trait SendTrait: Debug + Send + Sync {
@@ -20,11 +22,11 @@ impl SendTrait for DefaultSend {
         println!("Im here!");
         let message = message.trim_end_matches('\n');
         let addr = SocketAddr::from_str("127.0.0.1:9999").unwrap(); // Just a place holder.
-        let request = match Request::parse(&format!("<CHAT \\ 1.0>\n<Method@Send>\n<Message@'{0}'>", message), Arc::new(addr)) {
+        let version = state.lock().await.version;
+        let request = match Request::parse(&format!("<CHAT \\ {0}>\n<Method@Send>\n<Message@'{1}'>", version.to_str(), escape(message)), Arc::new(addr)) {
             Ok(val) => val,
             Err(e) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [SEND] Failed to parse the request with error {:?}", e);
+                warn!(error = ?e, "Failed to parse the request");
                 
                 return Err(ClientError::ParseError(e));
             }
@@ -33,9 +35,8 @@ impl SendTrait for DefaultSend {
         let locked = state.lock().await;
         match locked.out_sender.send(request).await {
             Ok(_) => Ok(()),
-            Err(_e) => {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [SEND] Failed to send the request with error {_e}");
+            Err(e) => {
+                warn!(error = %e, "Failed to send the request");
 
                 Err(ClientError::InternalError)
             }
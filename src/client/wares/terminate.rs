@@ -2,6 +2,7 @@ use std::sync::Arc;
 use std::fmt::Debug;
 use tokio::sync::Mutex;
 use crate::client::{ClientError, ClientState};
+use tracing::warn;
 
 
 #[async_trait::async_trait]
@@ -18,8 +19,7 @@ impl TerminateTrait for DefaultTerminate {
         let mut locked = state.lock().await;
         if let Some(handle) = &locked.handle {
             if handle.is_finished() {
-                #[cfg(feature = "debug_light")]
-                println!("<<< [TERM] Failed to terminate the handle, because its already finished");
+                warn!("Failed to terminate the handle, because its already finished");
                 
                 locked.handle = None;
                 return Err(ClientError::AlreadyFinished);
@@ -28,8 +28,7 @@ impl TerminateTrait for DefaultTerminate {
             locked.handle = None;
             return Ok(());
         }
-        #[cfg(feature = "debug_light")]
-        println!("<<< [TERM] No handle is currently running");
+        warn!("No handle is currently running");
 
         Err(ClientError::NoActiveHandle)
     } 
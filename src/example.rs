@@ -1,3 +1,4 @@
+use bytes::{BufMut, Bytes, BytesMut};
 use core::str;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -8,7 +9,7 @@ use crate::protocol::response::{string_to_bytes, ResponseBuilder, ResponseCode,
 #[allow(dead_code, unused_variables)]
 fn main() {
     // Lets say here we got the bytes from the TcpStream and we already know addr from before, where we first made contact with the client.
-    let mut bytes = string_to_bytes("<CHAT \\ 1.0>\n<Method@Send>\n<Message@'Hello world!'>".to_string());
+    let mut bytes = BytesMut::from(&string_to_bytes("<CHAT \\ 1.0>\n<Method@Send>\n<Message@'Hello world!'>".to_string())[..]);
     let addr = Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap());
 
     // Lets say that we want to encode colors in HEX in last bytes.
@@ -16,22 +17,20 @@ fn main() {
     // For each two of those hex number we need to allocate 1 byte, and to make our selfs lifes easier, we keep that '#' symbol at the -5 position and -1, to ensure that we are handling not just stray things, but our color bytes.
     
     let symb = '#' as u8; 
-    bytes[512 - 1] = symb; // 512 - * is not because im dumb, its because i want to make things clearer.
     // Today I want some red, so lets use \#ff0000 (\ so Prettier want be messing with my color :) )
-    bytes[512 - 4] = 255; //FF in base 10 means 255 though.
-    bytes[512 - 5] = symb;
+    bytes.put_slice(&[symb, 255, 0, 0, symb]); //FF in base 10 means 255 though.
 
     //Now our bytes would look like this:
-    // index: [... -8 -7 -6 -5  -4 -3 -2 -1]
-    // bytes: [...  0  0  0 35 255  0  0 35]
+    // index: [... -8  -7  -6 -5  -4 -3 -2 -1]
+    // bytes: [... 33  39  62 35 255  0  0 35]
     // Great!
     // As next part we would need something to process this bytes.
-    // After processing bytes, we should 0 them, so our programm will continue to shine.
+    // After processing bytes, we should cut them off, so our programm will continue to shine.
 
     let color = first_simple_bytesware(&mut bytes).unwrap(); // For now we are sure that its color.
 
     // Now that we parsed color out of our request line, it should be parsable.
-    let send_request_line = str::from_utf8(&bytes).unwrap().trim_end_matches('\0'); //Dont forget last line! Its important!
+    let send_request_line = str::from_utf8(&bytes).unwrap();
     let mut send_request = Request::parse(send_request_line, addr.clone()).unwrap();
     // When we got our Request object, we can inject Color in it!
     send_request.varmap.insert::<Color>(color);
@@ -48,37 +47,34 @@ fn main() {
     // And thats it! we made a request, that our system would handle.
 }
 
-fn first_simple_bytesware(bytes: &mut [u8; 512]) -> Result<Color, ()> {
+fn first_simple_bytesware(bytes: &mut BytesMut) -> Result<Color, ()> {
     // Step 1: Make sure, that we are dealing with our *special* request
     let symb = '#' as u8;
-    if !(bytes[512 - 1] == bytes[512 - 5] && bytes[512 - 1] == symb) {
+    let len = bytes.len();
+    if len < 5 || !(bytes[len - 1] == bytes[len - 5] && bytes[len - 1] == symb) {
         return Err(())
     }
     // Step 2: Extract hexnumbers
-    let blue = bytes[512 - 2];
-    let green = bytes[512 - 3];
-    let red = bytes[512 - 4];
+    let blue = bytes[len - 2];
+    let green = bytes[len - 3];
+    let red = bytes[len - 4];
 
     let color = Color { red, green, blue };
-    // Step 3: Dont forget to 0 retrieved bytes
-    bytes[512-5..=512-1].fill(0);
+    // Step 3: Dont forget to cut off retrieved bytes
+    bytes.truncate(len - 5);
     // Step 4: Done! Return color.
     Ok(color)
 }
 
-fn last_simple_bytesware(res: Response) -> Result<[u8; 512], ()> {
+fn last_simple_bytesware(res: Response) -> Result<Bytes, ()> {
     if let Some(ref varmap) = res.varmap {
         if let Some(color) = varmap.get::<Color>() {
-            let mut bytes = res.as_bytes()?;
+            let mut bytes = BytesMut::from(&res.as_bytes()?[..]);
 
             let symb = '#' as u8;
-            bytes[512 - 1] = symb;
-            bytes[512 - 2] = color.blue;
-            bytes[512 - 3] = color.green;
-            bytes[512 - 4] = color.red; 
-            bytes[512 - 5] = symb;
+            bytes.put_slice(&[symb, color.red, color.green, color.blue, symb]);
 
-            return Ok(bytes);
+            return Ok(bytes.freeze());
         }
     }
 
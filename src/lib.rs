@@ -19,7 +19,7 @@
 //!     let router: Router = RouterBuilder::new()
 //!         .build();
 //! 
-//!     router.run().await
+//!     router.run().await.unwrap()
 //! }
 //! ```
 //!
@@ -86,18 +86,17 @@
 //! On Ryzen 5800x cpu I managed to get up to 900 concurent clients without errors / lost clients, and up to 1240 clients without being Lagged by tokio MPSC / broadcast channels. *it is 1240 senders * 1240 recievers every 1200 ms*
 //! 
 //! ## Goals
-//! - Scalable messages
-//! - Move [u8; 512] to Bytes with capacity 512
+//! - ~~Scalable messages~~
+//! - ~~Move [u8; 512] to Bytes with capacity 512~~
 //! - Add custom rooms
 //! - Add built-in tools (such as rate limiting)
 
 //! ## Questions
-//! - Lib provided tracing for errors (in addition to Debug modes) is needed? What info is needed?
-//! - Is there need for custom request parser written with `nom` for example, instead of regex?
+//! - ~~Lib provided tracing for errors (in addition to Debug modes) is needed? What info is needed?~~ Yes, everything is `tracing` now, connections and requests are spans
+//! - ~~Is there need for custom request parser written with `nom` for example, instead of regex?~~ Yes, `protocol::parser` is hand-written now (`cargo bench --bench parser` compares it with the regex one)
 
 pub mod example;
 pub mod protocol;
-pub mod app;
 pub mod client;
 pub mod tests;
 pub mod router;
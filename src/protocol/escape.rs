@@ -0,0 +1,103 @@
+//! ## `Escape`
+//!
+//! Every header is one `<Key@'value'>` line, so before there was no way to send a message
+//! with `'` inside of it (`"don't"` was broken), and message with a newline was split
+//! into two lines, which both were not valid headers.
+//!
+//! So now quoted values are escaped with `\`:
+//!
+//! | Symbol            | On the wire |
+//! |-------------------|-------------|
+//! | `\`               | `\\`        |
+//! | `'`               | `\'`        |
+//! | `>`               | `\>`        |
+//! | newline (`\n`)    | `\n`        |
+//! | carriage (`\r`)   | `\r`        |
+//!
+//! Everything else goes as it is. Escaped value never has raw newline or lone `'` inside,
+//! so one header is always exactly one line, and it always ends at the first unescaped `'>`.
+//! `>` doesnt really break anything inside of the quotes, but escaping it lets simple clients
+//! search for the end of the header without knowing about quotes at all.
+//!
+//! Unquoted values (`<Method@Send>`) are only `\w+`, so there is nothing to escape there.
+//!
+//! ## Example
+//! ```
+//! let escaped = escape("don't\nstop");
+//! assert_eq!(escaped, "don\\'t\\nstop");
+//!
+//! let value = unescape(&escaped).unwrap();
+//! assert_eq!(value, "don't\nstop");
+//! ```
+use std::borrow::Cow;
+
+use crate::protocol::request::{ParseError, ParseErrorKind};
+
+/// Escapes `value`, so it could be put between `'` in the header.
+///
+/// If there is nothing to escape - no allocation is done.
+///
+/// ## Example
+/// ```
+/// let line = format!("<Message@'{0}'>", escape("It's <fine>"));
+/// assert_eq!(line, "<Message@'It\\'s <fine\\>'>");
+/// ```
+pub fn escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(['\\', '\'', '>', '\n', '\r']) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for ch in value.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\'' => escaped.push_str("\\'"),
+            '>' => escaped.push_str("\\>"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(ch),
+        }
+    }
+
+    Cow::Owned(escaped)
+}
+
+/// Reverse of the [`escape`]. Takes value without surrounding quotes.
+///
+/// Unknown escape sequence (`\x`) or `\` at the very end is [`ParseErrorKind::InvalidFormat`],
+/// same as any other broken header.
+///
+/// ## Example
+/// ```
+/// let value = unescape("don\\'t").unwrap();
+/// assert_eq!(value, "don't");
+///
+/// assert!(unescape("broken\\").is_err());
+/// ```
+pub fn unescape(value: &str) -> Result<Cow<'_, str>, ParseError> {
+    if !value.contains('\\') {
+        return Ok(Cow::Borrowed(value));
+    }
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unescaped.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('\'') => unescaped.push('\''),
+            Some('>') => unescaped.push('>'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            _ => {
+                return Err(ParseError::new(ParseErrorKind::InvalidFormat));
+            }
+        }
+    }
+
+    Ok(Cow::Owned(unescaped))
+}
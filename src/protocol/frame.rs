@@ -0,0 +1,251 @@
+//! ## `Frame`
+//!
+//! This module holds the framing layer of the protocol. Before, every message was a fixed `[u8; 512]`,
+//! so anything longer than that was cut (sometimes right in the middle of the UTF-8 symbol).
+//! Now every [`Request`] / [`Response`] is sent as one frame:
+//!
+//! ```txt
+//! +----------------------+---------------------------+
+//! | length (u32, BE)     | payload (`length` bytes)  |
+//! +----------------------+---------------------------+
+//! ```
+//!
+//! Payload is the same `<CHAT \ 1.0>...` text as before, just without any `'\0'` padding.
+//!
+//! TCP doesnt know anything about our frames, so one `read()` could return half of the frame,
+//! or three frames at once. That is why reading should be done via [`FrameReader`], it keeps
+//! not yet finished bytes between reads.
+//!
+//! ## Example
+//! ```
+//! let codec = FrameCodec::new(1024);
+//!
+//! let frame = codec.encode(b"<CHAT \\ 1.0>\n<Method@Bind>\n<Name@Jeff>").unwrap();
+//!
+//! let mut buf = BytesMut::from(&frame[..]);
+//! let payload = codec.decode(&mut buf).unwrap().unwrap();
+//! ```
+//!
+//! [`Request`]: crate::protocol::request::Request
+//! [`Response`]: crate::protocol::response::Response
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::fmt::{Display, Formatter};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Size of the length header in bytes.
+pub const HEADER_LEN: usize = 4;
+
+/// Default maximum size of the frame payload, if nothing else was set.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// ## `FrameError`
+///
+/// Errors that could happen while reading / decoding the frame. After any of them
+/// the stream is out of sync, so connection should be closed.
+#[derive(Debug)]
+pub enum FrameError {
+    TooLarge(usize),            // Payload is bigger than `max_frame_size` (in the header, or the one that is encoded)
+    UnexpectedEof,              // Connection was closed in the middle of the frame
+    Io(std::io::Error),         // Reading from the stream failed
+}
+
+impl Display for FrameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::TooLarge(len) => write!(f, "frame of {len} bytes is too large"),
+            FrameError::UnexpectedEof => write!(f, "connection closed in the middle of the frame"),
+            FrameError::Io(e) => write!(f, "failed to read frame: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<std::io::Error> for FrameError {
+    fn from(e: std::io::Error) -> Self {
+        FrameError::Io(e)
+    }
+}
+
+/// ## `FrameCodec`
+///
+/// This struct is encoding payloads into frames and decoding them back. It only knows about
+/// the length header, so what is inside of the payload is not its problem.
+///
+/// `max_frame_size` is the limit for the payloads both ways. If the header says that frame is
+/// bigger - [`FrameError::TooLarge`] is returned, and nothing is read into memory. Bigger payload
+/// is not encoded either, other side would reject it anyway.
+///
+/// ## Example
+/// ```
+/// let codec = FrameCodec::default();
+///
+/// let frame = codec.encode(b"Hello world!").unwrap();
+/// assert_eq!(frame.len(), HEADER_LEN + 12);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameCodec {
+    pub max_frame_size: usize,
+}
+
+impl FrameCodec {
+    /// Creates new codec with given max payload size.
+    pub fn new(max_frame_size: usize) -> Self {
+        FrameCodec { max_frame_size }
+    }
+
+    /// Puts length header in front of the payload.
+    ///
+    /// Returns [`FrameError::TooLarge`], if the payload is bigger than `max_frame_size` (or doesnt fit into the `u32` header).
+    ///
+    /// ## Example
+    /// ```
+    /// let frame = FrameCodec::default().encode(b"Hello world!")?;
+    /// ```
+    pub fn encode(&self, payload: &[u8]) -> Result<Bytes, FrameError> {
+        if payload.len() > self.max_frame_size || payload.len() > u32::MAX as usize {
+            return Err(FrameError::TooLarge(payload.len()));
+        }
+
+        let mut buf = BytesMut::with_capacity(HEADER_LEN + payload.len());
+
+        buf.put_u32(payload.len() as u32);
+        buf.put_slice(payload);
+
+        Ok(buf.freeze())
+    }
+
+    /// Tries to take one frame out of the `buf`.
+    ///
+    /// Returns `Ok(None)` if there is not enough bytes yet. If frame is taken, its bytes
+    /// are removed from the `buf`, so what is left is the beginning of the next frame.
+    ///
+    /// ## Example
+    /// ```
+    /// let codec = FrameCodec::default();
+    /// let mut buf = BytesMut::from(&codec.encode(b"Hello world!").unwrap()[..]);
+    ///
+    /// let payload = codec.decode(&mut buf).unwrap();
+    /// assert_eq!(payload, Some(Bytes::from_static(b"Hello world!")));
+    /// ```
+    pub fn decode(&self, buf: &mut BytesMut) -> Result<Option<Bytes>, FrameError> {
+        if buf.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let mut header = [0u8; HEADER_LEN];
+        header.copy_from_slice(&buf[..HEADER_LEN]);
+        let len = u32::from_be_bytes(header) as usize;
+
+        if len > self.max_frame_size {
+            return Err(FrameError::TooLarge(len));
+        }
+
+        if buf.len() < HEADER_LEN + len {
+            // Making sure that there is space for the rest of the frame
+            buf.reserve(HEADER_LEN + len - buf.len());
+            return Ok(None);
+        }
+
+        buf.advance(HEADER_LEN);
+        Ok(Some(buf.split_to(len).freeze()))
+    }
+}
+
+impl Default for FrameCodec {
+    /// Codec with [`DEFAULT_MAX_FRAME_SIZE`]
+    fn default() -> Self {
+        FrameCodec::new(DEFAULT_MAX_FRAME_SIZE)
+    }
+}
+
+/// Encodes `payload` into frame and writes whole frame to the `stream`.
+///
+/// Returns amount of written bytes (header included), so it could be matched the same
+/// way, as `stream.write()`. Payload that is too large is `ErrorKind::InvalidInput`, and nothing is written.
+pub async fn write_frame<W: AsyncWrite + Unpin>(stream: &mut W, codec: &FrameCodec, payload: &[u8]) -> std::io::Result<usize> {
+    let frame = codec.encode(payload).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+    stream.write_all(&frame).await?;
+    // Nothing for TCP, but TLS and WebSocket could keep it buffered otherwise
+    stream.flush().await?;
+    Ok(frame.len())
+}
+
+/// ## `FrameReader`
+///
+/// Buffered reader of the frames. It is used by both [`Router`] and [`Client`], so they
+/// handle split and coalesced TCP reads the same way.
+///
+/// Bytes that were read, but are not a full frame yet, stay in the inner buffer until next
+/// `read_frame()`. Because of that `read_frame()` is cancel safe and could be used inside of
+/// `select!` - if other branch wins, nothing is lost.
+///
+/// Writing is done to the inner stream via `get_mut()`.
+///
+/// ## Example
+/// ```
+/// let mut reader = FrameReader::new(stream, FrameCodec::default());
+///
+/// while let Some(payload) = reader.read_frame().await? {
+///     let response = Response::from_bytes(&payload)?;
+///
+///     write_frame(reader.get_mut(), &codec, &answer).await?;
+/// }
+/// ```
+///
+/// [`Router`]: crate::router::Router
+/// [`Client`]: crate::client::Client
+#[derive(Debug)]
+pub struct FrameReader<R> {
+    stream: R,
+    codec: FrameCodec,
+    buf: BytesMut,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    /// Wraps `stream` into the reader.
+    pub fn new(stream: R, codec: FrameCodec) -> Self {
+        FrameReader {
+            stream,
+            codec,
+            buf: BytesMut::with_capacity(HEADER_LEN + 512),
+        }
+    }
+
+    /// Reads next frame payload.
+    ///
+    /// Returns `Ok(None)` if connection was closed between frames, and [`FrameError::UnexpectedEof`]
+    /// if it was closed in the middle of one.
+    pub async fn read_frame(&mut self) -> Result<Option<Bytes>, FrameError> {
+        loop {
+            // There could be whole frame from the previous read already
+            if let Some(frame) = self.codec.decode(&mut self.buf)? {
+                return Ok(Some(frame));
+            }
+
+            if self.stream.read_buf(&mut self.buf).await? == 0 {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+
+                return Err(FrameError::UnexpectedEof);
+            }
+        }
+    }
+
+    /// Codec, that is used by this reader
+    pub fn codec(&self) -> &FrameCodec {
+        &self.codec
+    }
+
+    /// Reference to the inner stream
+    pub fn get_ref(&self) -> &R {
+        &self.stream
+    }
+
+    /// Mutable reference to the inner stream, mostly for writing.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.stream
+    }
+}
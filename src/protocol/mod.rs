@@ -4,12 +4,19 @@
 //! For example - [`Request`] and [`Response`] are objects that developer will interact with
 //! when he will be working on custom client, or [`wares`].
 //! 
-//! This module also holds [`Varmap`].
+//! This module also holds [`Varmap`], [`frame`] - the length-prefixed framing of the messages,
+//! [`escape`] - the escaping of the quoted header values, and [`parser`] - the parser of the protocol text.
 //! 
 //! [`Request`]: crate::protocol::request::Request
 //! [`Response`]: crate::protocol::response::Response
 //! [`wares`]: crate::protocol::wares
+//! [`frame`]: crate::protocol::frame
+//! [`escape`]: crate::protocol::escape
+//! [`parser`]: crate::protocol::parser
 
+pub mod escape;
+pub mod frame;
+pub mod parser;
 pub mod request;
 pub mod response;
 mod utils;
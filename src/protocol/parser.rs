@@ -0,0 +1,377 @@
+//! ## `Parser`
+//!
+//! Hand-written parser of the protocol text. It replaced regex based `parse_key_value`, which was
+//! allocating `String` for every key and value and running `Regex` captures for every line.
+//!
+//! [`Parser`] goes through the payload once, line by line, and gives away [`Header`]s that are
+//! borrowing from the input. Value is copied only if it had escaped symbols inside (see [`escape`]).
+//!
+//! Both [`Request`] and [`Response`] are parsed with it, because for the parser they are the same:
+//! ```txt
+//! <CHAT \ 1.0>        <- version line
+//! <Key@Value>         <- any amount of headers
+//! <Key@'Value'>
+//! ```
+//! What every header means is decided by the `Request` / `Response` themselves.
+//!
+//! If something is wrong, [`ParseError`] points to the line and column (both starting from 1)
+//! where it happened, and holds the text of that line.
+//!
+//! ## Example
+//! ```
+//! let mut parser = Parser::new("<CHAT \\ 1.0>\n<Method@Send>\n<Message@'Hello world!'>");
+//!
+//! let version = parser.version().unwrap();
+//! for header in parser {
+//!     let header = header.unwrap();
+//!     println!("{0} = {1}", header.key, header.value);
+//! }
+//! ```
+//!
+//! [`Request`]: crate::protocol::request::Request
+//! [`Response`]: crate::protocol::response::Response
+//! [`escape`]: crate::protocol::escape
+//! [`ParseError`]: crate::protocol::request::ParseError
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use crate::protocol::request::{ParseError, ParseErrorKind, Version};
+
+const VERSION_PREFIX: &str = "<CHAT \\ ";
+
+/// ## `Position`
+///
+/// Place in the input. Both `line` and `column` are starting from 1, and `column`
+/// is counted in chars, not bytes, so it matches what you see in the editor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// ## `Header`
+///
+/// One `<Key@Value>` line. `key` and `raw` are always borrowed from the input, `value` is
+/// borrowed too, unless it had to be unescaped.
+///
+/// `position` points to the `<` of the header, `raw` is the whole line as it was.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header<'a> {
+    pub key: &'a str,
+    pub value: Cow<'a, str>,
+    pub raw: &'a str,
+    pub position: Position,
+}
+
+impl Header<'_> {
+    /// [`ParseError`] that points to this header. Used when header itself is fine,
+    /// but it is not what was expected.
+    pub fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError::new(kind)
+            .at(self.position)
+            .with_header(self.raw)
+    }
+}
+
+/// ## `Parser`
+///
+/// Streaming parser over one frame payload. First call [`Parser::version`], then take headers
+/// one by one via [`Parser::header`] or just iterate over it.
+///
+/// After the first error parser should not be used anymore, iterator will stop by its own.
+#[derive(Debug, Clone)]
+pub struct Parser<'a> {
+    input: &'a str,
+    offset: usize,
+    line: usize,
+    failed: bool,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates parser over `input`.
+    pub fn new(input: &'a str) -> Self {
+        Parser {
+            input,
+            offset: 0,
+            line: 1,
+            failed: false,
+        }
+    }
+
+    /// Creates parser over the frame payload. Payload should be valid UTF-8, if it is not -
+    /// error points to the first broken byte.
+    pub fn from_bytes(input: &'a [u8]) -> Result<Self, ParseError> {
+        match std::str::from_utf8(input) {
+            Ok(val) => Ok(Parser::new(val)),
+            Err(e) => {
+                // Everything before `valid_up_to` is fine, so it is safe to count lines there
+                let valid = std::str::from_utf8(&input[..e.valid_up_to()]).unwrap_or_default();
+                let line = valid.matches('\n').count() + 1;
+                let line_start = valid.rfind('\n').map_or(0, |idx| idx + 1);
+
+                Err(ParseError::new(ParseErrorKind::InvalidFormat)
+                    .at(Position { line, column: valid[line_start..].chars().count() + 1 })
+                    .with_header(&valid[line_start..]))
+            }
+        }
+    }
+
+    /// Current position of the parser, start of the next line.
+    pub fn position(&self) -> Position {
+        Position { line: self.line, column: 1 }
+    }
+
+    /// Parses version line (`<CHAT \ 1.0>`). It should be the first thing called.
+    pub fn version(&mut self) -> Result<Version, ParseError> {
+        let (line, start) = match self.next_line() {
+            Some(val) => val,
+            None => {
+                return Err(self.error(self.input.len(), ParseErrorKind::MissingVersion));
+            }
+        };
+
+        let version = match line.strip_prefix(VERSION_PREFIX) {
+            Some(rest) => rest,
+            None => {
+                let column = common_prefix(line, VERSION_PREFIX);
+                return Err(self.error(start + column, ParseErrorKind::InvalidFormat));
+            }
+        };
+        let version_start = start + VERSION_PREFIX.len();
+
+        // `[1-9].[0-9]>` and nothing after
+        let bytes = version.as_bytes();
+        let checks: [fn(u8) -> bool; 4] = [
+            |b| (b'1'..=b'9').contains(&b),
+            |b| b == b'.',
+            |b| b.is_ascii_digit(),
+            |b| b == b'>',
+        ];
+        for (idx, check) in checks.iter().enumerate() {
+            match bytes.get(idx) {
+                Some(b) if check(*b) => {},
+                _ => {
+                    return Err(self.error(version_start + idx, ParseErrorKind::InvalidFormat));
+                }
+            }
+        }
+        if bytes.len() > checks.len() {
+            return Err(self.error(version_start + checks.len(), ParseErrorKind::InvalidFormat));
+        }
+
+        // Version is written correctly, we just dont know it
+        match Version::from_str(&version[..3]) {
+            Ok(val) => Ok(val),
+            Err(_) => Err(self.error(version_start, ParseErrorKind::UnsupportedVersion)),
+        }
+    }
+
+    /// Parses next header. `Ok(None)` means that input is over.
+    pub fn header(&mut self) -> Result<Option<Header<'a>>, ParseError> {
+        let (line, start) = match self.next_line() {
+            Some(val) => val,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        match parse_line(line) {
+            Ok((key, value)) => Ok(Some(Header {
+                key,
+                value,
+                raw: line,
+                position: Position { line: self.line - 1, column: 1 },
+            })),
+            Err((offset, kind)) => Err(self.error(start + offset, kind)),
+        }
+    }
+
+    /// Same as [`Parser::header`], but input being over is an error too - `missing`.
+    pub fn expect_header(&mut self, missing: ParseErrorKind) -> Result<Header<'a>, ParseError> {
+        match self.header()? {
+            Some(header) => Ok(header),
+            None => Err(self.error(self.input.len(), missing)),
+        }
+    }
+
+    // Gives away the next line without `\n` (and `\r` before it), and its offset in the input.
+    // Trailing newline at the very end is not an empty line, same as with `str::lines()`.
+    fn next_line(&mut self) -> Option<(&'a str, usize)> {
+        if self.failed || self.offset >= self.input.len() {
+            return None;
+        }
+
+        let start = self.offset;
+        let rest = &self.input[start..];
+        let line = match rest.find('\n') {
+            Some(idx) => {
+                self.offset += idx + 1;
+                &rest[..idx]
+            },
+            None => {
+                self.offset = self.input.len();
+                rest
+            }
+        };
+        self.line += 1;
+
+        Some((line.strip_suffix('\r').unwrap_or(line), start))
+    }
+
+    // Builds error for the byte `offset` of the input. Its called only on the error path,
+    // so counting chars here is fine.
+    fn error(&mut self, offset: usize, kind: ParseErrorKind) -> ParseError {
+        self.failed = true;
+
+        let offset = offset.min(self.input.len());
+        let line_start = self.input[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = self.input[offset..].find('\n').map_or(self.input.len(), |idx| offset + idx);
+        let line = self.input[..line_start].matches('\n').count() + 1;
+
+        let err = ParseError::new(kind)
+            .at(Position { line, column: self.input[line_start..offset].chars().count() + 1 });
+
+        // If input is just over, there is no line to show
+        let text = self.input[line_start..line_end].trim_end_matches('\r');
+        if text.is_empty() {
+            err
+        } else {
+            err.with_header(text)
+        }
+    }
+}
+
+impl<'a> Iterator for Parser<'a> {
+    type Item = Result<Header<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.header().transpose()
+    }
+}
+
+/// Takes only the version out of the frame payload, without looking at the rest of it.
+/// Used to answer in the same version, even if the rest of the request is broken.
+///
+/// ## Example
+/// ```
+/// assert_eq!(peek_version(b"<CHAT \\ 1.1>\n<Method@Nonsense>"), Some(Version::CHAT11));
+/// ```
+pub fn peek_version(payload: &[u8]) -> Option<Version> {
+    // Version line is short, so there is no need to check whole payload for UTF-8
+    let end = payload.iter().position(|b| *b == b'\n').unwrap_or(payload.len());
+
+    Parser::from_bytes(&payload[..end]).ok()?.version().ok()
+}
+
+/// Parses one header line, that is not a part of the bigger input.
+/// Line should not have `\n` inside.
+///
+/// ## Example
+/// ```
+/// let header = parse_header("<Message@'Hello world!'>").unwrap();
+/// assert_eq!(header.key, "Message");
+/// ```
+pub fn parse_header(line: &str) -> Result<Header<'_>, ParseError> {
+    let mut parser = Parser::new(line);
+
+    let header = parser.expect_header(ParseErrorKind::InvalidFormat)?;
+    if parser.offset < line.len() {
+        return Err(parser.error(parser.offset, ParseErrorKind::InvalidFormat));
+    }
+
+    Ok(header)
+}
+
+// Parses `<Key@Value>` / `<Key@'Value'>`. On error returns byte offset in the line, where it happened.
+fn parse_line(line: &str) -> Result<(&str, Cow<'_, str>), (usize, ParseErrorKind)> {
+    let bytes = line.as_bytes();
+
+    if bytes.first() != Some(&b'<') {
+        return Err((0, ParseErrorKind::InvalidFormat));
+    }
+
+    // Key is `[a-zA-Z]+`
+    let key_end = 1 + bytes[1..].iter().take_while(|b| b.is_ascii_alphabetic()).count();
+    if key_end == 1 || bytes.get(key_end) != Some(&b'@') {
+        return Err((key_end, ParseErrorKind::InvalidFormat));
+    }
+    let key = &line[1..key_end];
+
+    let value_start = key_end + 1;
+    let (value, value_end) = if bytes.get(value_start) == Some(&b'\'') {
+        parse_quoted(line, value_start + 1)?
+    } else {
+        // Unquoted value is `\w+`
+        let len: usize = line[value_start..]
+            .chars()
+            .take_while(|ch| ch.is_alphanumeric() || *ch == '_')
+            .map(char::len_utf8)
+            .sum();
+        if len == 0 {
+            return Err((value_start, ParseErrorKind::InvalidFormat));
+        }
+
+        (Cow::Borrowed(&line[value_start..value_start + len]), value_start + len)
+    };
+
+    if bytes.get(value_end) != Some(&b'>') {
+        return Err((value_end, ParseErrorKind::InvalidFormat));
+    }
+    if value_end + 1 != bytes.len() {
+        return Err((value_end + 1, ParseErrorKind::InvalidFormat));
+    }
+
+    Ok((key, value))
+}
+
+// Parses quoted value starting right after the opening `'`. Returns value and offset of the
+// symbol after the closing `'`.
+fn parse_quoted(line: &str, start: usize) -> Result<(Cow<'_, str>, usize), (usize, ParseErrorKind)> {
+    let bytes = line.as_bytes();
+    let mut escaped = false;
+    let mut idx = start;
+
+    // All special symbols are ASCII, so going byte by byte is fine even with UTF-8 inside
+    loop {
+        match bytes.get(idx) {
+            Some(b'\'') => break,
+            Some(b'\\') => {
+                match bytes.get(idx + 1) {
+                    Some(b'\\' | b'\'' | b'>' | b'n' | b'r') => {},
+                    _ => {
+                        return Err((idx, ParseErrorKind::InvalidFormat));
+                    }
+                }
+                escaped = true;
+                idx += 2;
+            },
+            Some(b'\r') => {
+                return Err((idx, ParseErrorKind::InvalidFormat));
+            },
+            Some(_) => idx += 1,
+            None => {
+                return Err((idx, ParseErrorKind::InvalidFormat));
+            }
+        }
+    }
+
+    let raw = &line[start..idx];
+    let value = if escaped {
+        // Every sequence was already checked above
+        match crate::protocol::escape::unescape(raw) {
+            Ok(val) => val,
+            Err(e) => {
+                return Err((start, e.kind));
+            }
+        }
+    } else {
+        Cow::Borrowed(raw)
+    };
+
+    Ok((value, idx + 1))
+}
+
+// Amount of bytes that are the same at the start of both strings.
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count()
+}
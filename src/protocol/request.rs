@@ -6,7 +6,7 @@
 //! Its recomended to go through examples and some comments on for each struct, but here is
 //! a small overview:
 //! 
-//! The `Request` is what `Router` recieves from `Client`. It should be less then 512 in bytes
+//! The `Request` is what `Router` recieves from `Client`. It should fit into router `max_frame_size`
 //! and should follow the protocol rules in order to be parsed.
 //! 
 //! ## Example
@@ -42,24 +42,20 @@
 //! ```
 //! 
 //! [`response`]: crate::protocol::response
-use regex::Regex;
-use once_cell::sync::Lazy;
-use core::str;
+use bytes::Bytes;
 use std::any::Any;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::protocol::escape::escape;
+use crate::protocol::parser::{parse_header, Parser, Position};
 use crate::protocol::varmap::Varmap;
 
 use super::response::string_to_bytes;
 
-// This regex is for general key value extractions
-static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^<(?P<key>[a-zA-Z]+)@(?P<value>'[^']+'|\b\w+\b)>$").unwrap());
-// This one is special to the Version. Because its not ket value. Obvious, but still want to point it out.
-static VERSION_HEADER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^<CHAT \\ (?P<version>[1-9]\.[0-9])>$").unwrap());
-
 /*
     Example:
     ```
@@ -91,6 +87,7 @@ static VERSION_HEADER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^<CHAT \\ (?P<
 /// with its own [`Varmap`] exactly for that purpose.
 /// 
 /// **ATTENTION** value field behaves differently based on which method is used.
+/// For [`Method::Custom`] there is no value at all, so it is empty, and every header is in `custom`.
 /// 
 /// ## Example
 /// 
@@ -103,7 +100,7 @@ static VERSION_HEADER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^<CHAT \\ (?P<
 /// 
 /// let req_bytes = request.as_bytes().unwrap();
 /// 
-/// match stream.write(&req_bytes).await {...}
+/// match write_frame(&mut stream, &codec, &req_bytes).await {...}
 /// ``` 
 /// 
 /// [`Middleware`]: crate::protocol::wares::middleware
@@ -323,184 +320,268 @@ impl RequestBuilder {
 /// This struct is for easier carrying around request bytes and address. 
 /// Why do i need to carry around address? Idk, maybe developer would
 /// need it for example for the rate limitter, or for location based `Bind`'ing
+/// 
+/// `bytes` is the payload of the frame, so there is no length header and no `'\0'` padding.
 #[derive(Debug, Clone)]
 pub struct RawRequest {
-    pub bytes: [u8; 512],
+    pub bytes: Bytes,
     pub addr: Arc<SocketAddr>,
 }
 
 /// ## `Version`
 /// 
 /// This enum is for general understanding with which version of the protocol
-/// we are dealing with. And maybe, based on the version treat request differently.
-/// I havent yet figured out how it would be handled diffrently, and in the logic that
-/// we have it wont be possible, and if something will be changed in the protocol, it 
-/// would most likely will depricieate previous chat implementations, and then why do
-/// I even add Version in the first case, but who cares, it wont be used anyway :(
-#[derive(Debug, Clone, PartialEq)]
+/// we are dealing with. Turns out it is used after all :)
+/// 
+/// - `CHAT10` - the original protocol.
+/// - `CHAT11` - same as `1.0`, but adds new headers (see [`Version::has_header`]).
+/// 
+/// Router answers in the version the client used, so `1.0` clients keep working
+/// next to the `1.1` ones. Which versions router accepts is set via `RouterBuilder::supported_versions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Version {
     CHAT10,
+    CHAT11,
 }
 
 /// ## `Method`
 /// 
 /// This enum is for general understanding with which method are we dealing with.
-/// for now, there are only 3 predefine methods, but there is already thoughts on
-/// `Command` method, and custom user-defined methods, but they are still in thoughts.
-#[derive(Debug, Clone, PartialEq)]
+/// There are 7 predefined methods (`Join` and `Leave` are for the rooms, `Unbind` frees the name, `History` pages through past messages), and everything else is [`Method::Custom`], which is
+/// routed to the route registered via `RouterBuilder::route("Typing", middleware, ending_bytesware)`.
+/// 
+/// Custom method name is letters / digits / `_` only, same as any other unquoted value.
+/// 
+/// ## Example
+/// ```
+/// let request = Request::parse("<CHAT \\ 1.1>\n<Method@Typing>\n<Room@'general'>", addr).unwrap();
+/// 
+/// assert_eq!(request.method, Method::Custom("Typing".to_string()));
+/// assert_eq!(request.custom.get("Room").unwrap(), "general");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Method {
     Bind,
     Handshake,
     Send,
+    Join,
+    Leave,
+    Unbind,
+    History,
+    Custom(String),
 }
 
 /// ## `ParseError`
 /// 
-/// This enum holds info, why parsing broke, and it should be handled. It is 
-/// printed with `debug_light` feature on, so you will know, why it did break.
+/// This struct holds info, why parsing broke, and where exactly. It is logged as the `error` field of the
+/// warning event, and [`DefaultStartingBytesware`] sends it back to the client
+/// in the `<Reason@'...'>` header, so client developers could see why their frame was rejected.
 /// 
-/// This should be converted to `anyhow` error, or something like that.
-#[derive(Debug, Clone)]
-pub enum ParseError {
+/// - `kind` - what went wrong, see [`ParseErrorKind`]
+/// - `position` - line and column (both from 1), if the error is tied to some place in the input
+/// - `header` - raw text of the line that broke (cut to [`MAX_REPORTED_HEADER`] chars)
+/// - `expected` - key that was expected on that line, if there was one
+/// 
+/// ## Example
+/// ```
+/// let err = Request::parse("<CHAT \\ 1.0>\n<Method@Send>\n<Name@Jeff>", addr).unwrap_err();
+/// 
+/// assert_eq!(err.kind, ParseErrorKind::InvalidKey);
+/// assert_eq!(err.expected.as_deref(), Some("Message"));
+/// 
+/// // invalid key at line 3, column 1 in `<Name@Jeff>`, expected `Message`
+/// println!("{err}");
+/// ```
+/// 
+/// [`DefaultStartingBytesware`]: crate::protocol::wares::starting_bytesware::DefaultStartingBytesware
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub position: Option<Position>,
+    pub header: Option<String>,
+    pub expected: Option<String>,
+}
+
+/// ## `ParseErrorKind`
+/// 
+/// This enum is the reason part of the [`ParseError`]. It used to be the `ParseError` itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
     InvalidFormat,
     InvalidKey,
     MissingVersion,
+    UnsupportedVersion,
     MissingMethod,
     MissingRequestValue,
     MissingCode,
     NotFound,
 }
 
+/// Max amount of chars of the broken header, that is kept inside of the [`ParseError`].
+/// Frames could be big, and there is no need to send whole frame back.
+pub const MAX_REPORTED_HEADER: usize = 128;
+
+impl ParseError {
+    /// Error without any context, add it via `at()`, `with_header()` and `with_expected()`.
+    pub fn new(kind: ParseErrorKind) -> Self {
+        ParseError {
+            kind,
+            position: None,
+            header: None,
+            expected: None,
+        }
+    }
+
+    /// Setter for `position` field.
+    pub fn at(mut self, position: Position) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    /// Setter for `header` field, header is cut to [`MAX_REPORTED_HEADER`] chars.
+    pub fn with_header(mut self, header: &str) -> Self {
+        self.header = Some(match header.char_indices().nth(MAX_REPORTED_HEADER) {
+            Some((idx, _)) => format!("{0}...", &header[..idx]),
+            None => header.to_string(),
+        });
+        self
+    }
+
+    /// Setter for `expected` field.
+    pub fn with_expected(mut self, key: &str) -> Self {
+        self.expected = Some(key.to_string());
+        self
+    }
+}
+
+impl From<ParseErrorKind> for ParseError {
+    fn from(kind: ParseErrorKind) -> Self {
+        ParseError::new(kind)
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            ParseErrorKind::InvalidFormat => "invalid format",
+            ParseErrorKind::InvalidKey => "invalid key",
+            ParseErrorKind::MissingVersion => "missing version",
+            ParseErrorKind::UnsupportedVersion => "unsupported version",
+            ParseErrorKind::MissingMethod => "missing or unknown method",
+            ParseErrorKind::MissingRequestValue => "missing request value",
+            ParseErrorKind::MissingCode => "missing response code",
+            ParseErrorKind::NotFound => "header not found",
+        };
+
+        write!(f, "{text}")
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{0}", self.kind)?;
+
+        if let Some(position) = &self.position {
+            write!(f, " at line {0}, column {1}", position.line, position.column)?;
+        }
+
+        if let Some(header) = &self.header {
+            write!(f, " in `{header}`")?;
+        }
+
+        if let Some(expected) = &self.expected {
+            write!(f, ", expected `{expected}`")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 /// I believe there is a way, in which this function could be used in application, so i make it public.
 /// 
-/// This function is used to extract key and value via `Regex` from one line.
+/// This function is used to extract key and value from one line. Quoted value is returned
+/// without quotes and already unescaped.
+/// 
+/// It allocates both `key` and `value`, so if you are parsing a lot - use [`parse_header`],
+/// it borrows from the line.
 /// 
 /// ## Example
 /// ```
-/// let line = "<Key@'Value'";
+/// let line = "<Key@'Don\\'t'>";
 /// 
 /// let (key, value) = parse_key_value(line).unwrap();
+/// assert_eq!(value, "Don't");
 /// ```
+/// 
+/// [`parse_header`]: crate::protocol::parser::parse_header
 pub fn parse_key_value(line: &str) -> Result<(String, String), ParseError>{
-    let caps = match RE.captures(line) {
-        Some(val) => val,
-        None => {
-            return Err(ParseError::InvalidFormat);
-        }
-    };
-
-    let key = match caps.name("key") {
-        Some(val) => val,
-        None => {
-            return Err(ParseError::InvalidFormat)
-        }
-    }.as_str().to_string();
-    let value = match caps.name("value") {
-        Some(val) => val,
-        None => {
-            return Err(ParseError::InvalidFormat)
-        }
-    }.as_str().trim_matches('\'').to_string();
-
-    Ok((key, value))
-}
-
-// This function is just version extractor, nothing too fancy here.
-pub(crate) fn extract_version(lines: &mut std::str::Lines<'_>) -> Result<Version, ParseError> {
-    // Making sure, that there is a line, if no - too sad.
-    let version_line = match lines.next() {
-        Some(val) => val,
-        None => {
-            return Err(ParseError::MissingVersion);
-        }
-    };
+    let header = parse_header(line)?;
 
-    // Using custom regular expersion to retrieve version.
-    let version = VERSION_HEADER_RE.captures(version_line)
-        .and_then(|cap| cap.name("version").map(|m| m.as_str()));
-    
-    if let Some(version) = version {
-        // If we found it, we need to match it with the `Version` struct.
-        return match Version::from_str(version) {
-            Ok(val) => Ok(val),
-            Err(_) => Err(ParseError::MissingVersion)
-        };
-    } else {
-        return Err(ParseError::InvalidFormat);
-    }
+    Ok((header.key.to_string(), header.value.into_owned()))
 }
 
 // Same as in the previous, its just the method extractor. nothing too fancy.
-pub(crate) fn extract_method(lines: &mut std::str::Lines<'_>) -> Result<Method, ParseError> {
-    // Making sure there is next line
-    let line = match lines.next() {
-        Some(val) => val,
-        None => {
-            return Err(ParseError::MissingMethod);
-        }
-    };
-     
-    let (key, value) = match parse_key_value(line) {
-        Ok(val) => val,
-        Err(e) => {
-            return Err(e);
-        }
-    };
+pub(crate) fn extract_method(parser: &mut Parser<'_>) -> Result<Method, ParseError> {
+    let header = parser
+        .expect_header(ParseErrorKind::MissingMethod)
+        .map_err(|e| e.with_expected("Method"))?;
 
     // Need to make sure that the key is `Method`.
     // Why? It will fail anywat to be extracted, if its not the Method?
     // We need to make sure, because there is dumb case, like this:
     // <IdontLikeMethod@Send>
     // And you can do nothing, but check that its method.
-    if key == "Method" {
-        return match Method::from_str(&value) {
-            Ok(val) => Ok(val),
-            Err(_) => Err(ParseError::MissingMethod)
-        };
-    } else {
-        return Err(ParseError::MissingMethod);
+    if header.key == "Method" {
+        // Unknown names are `Method::Custom`, router decides if there is a route for it
+        if let Ok(val) = Method::from_str(&header.value) {
+            return Ok(val);
+        }
     }
+
+    Err(header.error(ParseErrorKind::MissingMethod).with_expected("Method"))
 }
 
-// Just a bigger wrapper around `parse_key_value` function.
-fn extract_value(method: &Method, lines: &mut std::str::Lines<'_>) -> Result<String, ParseError> {
-    let line = match lines.next() {
-        Some(val) => val,
-        None => {
-            return Err(ParseError::MissingRequestValue)
-        }
+// Takes value of the request, key is decided by the method.
+// Custom methods have no value, their headers are all custom.
+fn extract_value(method: &Method, parser: &mut Parser<'_>) -> Result<String, ParseError> {
+    let key = match method.value_key() {
+        Some(key) => key,
+        None => return Ok(String::new()),
     };
 
-    let (key, value) = match parse_key_value(line) {
-        Ok(val) => val,
-        Err(e) => {
-            return Err(e);
-        }
-    };
-    let value = value.trim_matches('\'').to_string(); // SHOULD FIX THIS IN REGEX CAPTURING, BUT IM TOO DUMB
-    return match method {
-        Method::Bind => {
-            if key == "Name" {
-                Ok(value)
-            } else {
-                Err(ParseError::InvalidKey)
-            }
-        },
-        Method::Handshake => {
-            if key == "Authorization" {
-                Ok(value)
-            } else {
-                Err(ParseError::InvalidKey)
-            }
-        },
-        Method::Send => {
-            if key == "Message" {
-                Ok(value)
-            } else {
-                Err(ParseError::InvalidKey)
-            }
-        }
-    };
+    let header = parser
+        .expect_header(ParseErrorKind::MissingRequestValue)
+        .map_err(|e| e.with_expected(key))?;
+
+    if header.key == key {
+        Ok(header.value.into_owned())
+    } else {
+        Err(header.error(ParseErrorKind::InvalidKey).with_expected(key))
+    }
+}
+
+// Shared by `Request::parse()` and `Request::from_raw_request()`
+fn parse_request(mut parser: Parser<'_>, addr: Arc<SocketAddr>) -> Result<Request, ParseError> {
+    let version = parser.version()?;
+    let method = extract_method(&mut parser)?;
+    let value = extract_value(&method, &mut parser)?;
+    let mut custom = HashMap::new();
+
+    for header in parser {
+        let header = header?;
+        custom.insert(header.key.to_string(), header.value.into_owned());
+    }
+
+    Ok(Request {
+        addr,
+        version,
+        method,
+        value,
+        custom,
+        varmap: Varmap::new(),
+    })
 }
 
 impl FromStr for Method {
@@ -511,6 +592,11 @@ impl FromStr for Method {
             "Bind" => Ok(Method::Bind),
             "Handshake" => Ok(Method::Handshake),
             "Send" => Ok(Method::Send),
+            "Join" => Ok(Method::Join),
+            "Leave" => Ok(Method::Leave),
+            "Unbind" => Ok(Method::Unbind),
+            "History" => Ok(Method::History),
+            name if is_method_name(name) => Ok(Method::Custom(name.to_string())),
             _ => Err(()),
         }
     }
@@ -522,25 +608,86 @@ impl FromStr for Version {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "1.0" => Ok(Version::CHAT10),
+            "1.1" => Ok(Version::CHAT11),
             _ => Err(()),
         }
     }
 }
 
+// Same as unquoted value, so `<Method@'Two words'>` is not a method
+fn is_method_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
 impl Method {
     pub fn to_str(&self) -> &str {
         match self {
             Method::Bind => "Bind",
             Method::Handshake => "Handshake",
             Method::Send => "Send",
+            Method::Join => "Join",
+            Method::Leave => "Leave",
+            Method::Unbind => "Unbind",
+            Method::History => "History",
+            Method::Custom(name) => name,
+        }
+    }
+
+    /// Key of the header, that holds request value. Custom methods dont have one.
+    /// 
+    /// | Method      | Key             |
+    /// |-------------|-----------------|
+    /// | `Bind`      | `Name`          |
+    /// | `Handshake` | `Authorization` |
+    /// | `Send`      | `Message`       |
+    /// | `Join`      | `Room`          |
+    /// | `Leave`     | `Room`          |
+    /// | `Unbind`    | `Authorization` |
+    /// | `History`   | `Limit`         |
+    /// | `Custom`    | -               |
+    pub fn value_key(&self) -> Option<&'static str> {
+        match self {
+            Method::Bind => Some("Name"),
+            Method::Handshake | Method::Unbind => Some("Authorization"),
+            Method::Send => Some("Message"),
+            Method::Join | Method::Leave => Some("Room"),
+            Method::History => Some("Limit"),
+            Method::Custom(_) => None,
         }
     }
 }
 
 impl Version {
+    /// Every version this crate knows, from the oldest to the newest.
+    pub const ALL: [Version; 2] = [Version::CHAT10, Version::CHAT11];
+
+    /// Newest version this crate knows.
+    pub const LATEST: Version = Version::CHAT11;
+
     pub fn to_str(&self) -> &str {
         match self {
             Version::CHAT10 => "1.0",
+            Version::CHAT11 => "1.1",
+        }
+    }
+
+    /// Is the header known in this version. Headers that were added in `1.1` are
+    /// not sent to `1.0` clients at all:
+    /// 
+    /// | Header        | Since |
+    /// |---------------|-------|
+    /// | `Reason`      | 1.1   |
+    /// | `Supported`   | 1.1   |
+    /// | `RetryAfter`  | 1.1   |
+    /// | `Id`          | 1.1   |
+    /// | anything else | 1.0   |
+    ///
+    /// `1.0` parser rejects the whole response, if it doesnt like one line (and escaped `\'` is one of those),
+    /// so it is not enough for it to skip the unknown ones.
+    pub fn has_header(&self, key: &str) -> bool {
+        match key {
+            "Reason" | "Supported" | "RetryAfter" | "Id" => *self >= Version::CHAT11,
+            _ => true,
         }
     }
 }
@@ -549,8 +696,8 @@ impl Request {
     /// ## `Request::parse()`
     /// 
     /// This function is desgined to be easiest solution on parsing incoming requests.
-    /// It would probably be re-written in `nom` for better efficiency, but right now
-    /// its fine.
+    /// It goes through the [`Parser`], so if you need to know where exactly it broke -
+    /// use the parser directly.
     /// 
     /// ## Example
     /// ```
@@ -562,27 +709,10 @@ impl Request {
     /// 
     /// println!("Look at my cool incoming request: {:?}", request);
     /// ```
+    /// 
+    /// [`Parser`]: crate::protocol::parser::Parser
     pub fn parse(input: &str, addr: Arc<SocketAddr>) -> Result<Self, ParseError> {
-        let mut lines = input.lines();
-    
-        let version = extract_version(&mut lines)?;
-        let method = extract_method(&mut lines)?;
-        let value = extract_value(&method, &mut lines)?;
-        let mut custom = HashMap::new();
-
-        while let Some(line) = lines.next() {
-            let (key, value) = parse_key_value(line)?;
-            custom.insert(key, value);
-        }
-
-        Ok(Request {
-            addr,
-            version,
-            method,
-            value,
-            custom,
-            varmap: Varmap::new(),
-        })
+        parse_request(Parser::new(input), addr)
     }
     
     /// ## `Request::from_raw_request()`
@@ -591,16 +721,15 @@ impl Request {
     /// dragging around `req_bytes` and `addr` was too boring, and i just
     /// stacked those fields inside of `RawRequest`.
     /// 
-    /// As `Request::parse()` it should be re-writeen in something like `nom`
-    /// for better efficiency, but for now it works great.
+    /// Parsing is the same as in `Request::parse()`, bytes should be valid UTF-8.
     /// 
     /// ## Example
     /// ```
     /// let request_line = "<CHAT \\ 1.0>\n<Method@Bind>\n<Name@Jeff>";
     /// 
-    /// // Lets say that we have magic function, that turns String to [u8; 512]
+    /// // Lets say that we have magic function, that turns String to Bytes
     /// // There is actually function like that in pinguino::protocol::response
-    /// let request_bytes = string_to_bytes(request_line.to_string()).unwrap();
+    /// let request_bytes = string_to_bytes(request_line.to_string());
     /// let addr = Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap());
     ///
     /// let raw_req = RawRequest { bytes: request_bytes, addr}
@@ -609,41 +738,16 @@ impl Request {
     /// println!("Look at my cool incoming request: {:?}", request);
     /// ```
     pub fn from_raw_request(raw_req: RawRequest) -> Result<Self, ParseError> {
-        // Same story, as in the Request::parse()
-        let line = match str::from_utf8(&raw_req.bytes) {
-            Ok(val) => val,
-            Err(_) => {
-                return Err(ParseError::InvalidFormat);
-            }
-        }.trim_end_matches('\0');
-
-        let addr = raw_req.addr;
-        let mut lines = line.lines();
-    
-        let version = extract_version(&mut lines)?;
-        let method = extract_method(&mut lines)?;
-        let value = extract_value(&method, &mut lines)?;
-
-        let mut custom = HashMap::new();
+        let parser = Parser::from_bytes(&raw_req.bytes)?;
 
-        while let Some(line) = lines.next() {
-            let (key, value) = parse_key_value(line)?;
-            custom.insert(key, value);
-        }
-
-        Ok(Request {
-            addr,
-            version,
-            method,
-            value,
-            custom,
-            varmap: Varmap::new(),
-        })
+        parse_request(parser, raw_req.addr)
     }
 
     /// ## `Request::as_bytes(&self)`
     /// 
     /// This function is designed for `Client` to prepare it for sending.
+    /// Returned bytes are the frame payload, so they should be written via [`write_frame`].
+    /// All values are quoted and escaped, so `value` could hold anything.
     /// 
     /// ## Example
     /// ```
@@ -658,23 +762,19 @@ impl Request {
     /// 
     /// let req_bytes = request.as_bytes().unwrap();
     /// 
-    /// match stream.write(&req_bytes).await {...}
+    /// match write_frame(&mut stream, &codec, &req_bytes).await {...}
     /// ```
-    pub fn as_bytes(&self) -> Result<[u8; 512], ()> {
+    /// 
+    /// [`write_frame`]: crate::protocol::frame::write_frame
+    pub fn as_bytes(&self) -> Result<Bytes, ()> {
         let mut response_line = format!("<CHAT \\ {0}>\n<Method@{1}>", self.version.to_str(), self.method.to_str());
 
-        if self.method == Method::Handshake {
-            response_line += format!("\n<Authorization@'{0}'>", self.value).as_str();
-        } else if self.method == Method::Bind {
-            response_line += format!("\n<Name@'{0}'>", self.value).as_str();
-        } else if self.method == Method::Send {
-            response_line += format!("\n<Message@'{0}'>", self.value).as_str();
-        } else {
-            return Err(());
+        if let Some(key) = self.method.value_key() {
+            response_line += format!("\n<{key}@'{0}'>", escape(&self.value)).as_str();
         }
 
         for (key, value) in &self.custom {
-            response_line += format!("\n<{key}@'{value}'").as_str();
+            response_line += format!("\n<{key}@'{0}'>", escape(value)).as_str();
         }
 
         let bytes = string_to_bytes(response_line);
@@ -20,16 +20,18 @@
 //! 
 //! println!("Look how pretty is my response! {0}", response.pretty_string());
 //! 
-//! let res_bytes = response.as_bytes();
-//! match stream.write(&res_bytes).await {...}
+//! let res_bytes = response.as_bytes().unwrap();
+//! match write_frame(&mut stream, &codec, &res_bytes).await {...}
 //! ```
+use crate::protocol::escape::escape;
 use crate::protocol::request::Version;
 use crate::protocol::varmap::Varmap;
-use core::str;
+use bytes::Bytes;
 use std::any::Any;
 use std::collections::HashMap;
 use std::str::FromStr;
-use crate::protocol::request::{extract_version, ParseError, parse_key_value};
+use crate::protocol::parser::Parser;
+use crate::protocol::request::{ParseError, ParseErrorKind};
 use chrono::{DateTime, Utc, NaiveDateTime, TimeZone};
 /*
     Example:
@@ -72,7 +74,7 @@ use chrono::{DateTime, Utc, NaiveDateTime, TimeZone};
                 .unwrap();
             let _ = message_response.as_bytes().unwrap(); //This one would be for TcpStream
 
-            println!("3. Message response: {:?}", message_response.pretty_string()); // This function for display purposes, so there is no need to play around with &[u8; 512] and '\0' bytes
+            println!("3. Message response: {:?}", message_response.pretty_string()); // This function for display purposes, so there is no need to play around with raw bytes
 
             // Custom Response
             let custom_response = ResponseBuilder::new()
@@ -177,8 +179,9 @@ use chrono::{DateTime, Utc, NaiveDateTime, TimeZone};
 ///     .message("Hello world!".to_string())
 ///     .build()
 ///     .unwrap()
-///     .as_bytes() // Here it may panic, if message is > 512 u8 bytes.
+///     .as_bytes()
 ///     .unwrap();
+/// // Size is checked by the `FrameCodec` of the reciever, so dont go crazy with the message size.
 /// ```
 /// 
 /// ## Purpose of the [`Varmap`] here
@@ -195,7 +198,8 @@ pub struct Response {
     pub token: Option<String>,      // <= 32 bytes
     pub user: Option<String>,       // <= 16 bytes
     pub time: Option<DateTime<Utc>>,
-    pub message: Option<String>,    // < 512 bytes
+    pub message: Option<String>,    // < max_frame_size bytes
+    pub reason: Option<String>,     // Why request was rejected, see `ParseError`
     pub custom: Option<HashMap<String, String>>, // Temporary support for custom response building.
     pub varmap: Option<Varmap>,
 }
@@ -273,7 +277,8 @@ pub struct ResponseBuilder {
     pub token: Option<String>,      // <= 32 bytes
     pub user: Option<String>,       // <= 16 bytes
     pub time: Option<DateTime<Utc>>,
-    pub message: Option<String>,    // < 512 bytes
+    pub message: Option<String>,    // < max_frame_size bytes
+    pub reason: Option<String>,     // Why request was rejected, see `ParseError`
     pub custom: Option<HashMap<String, String>>, // Temporary support for custom response building. 
     pub varmap: Option<Varmap>,
 }
@@ -309,11 +314,14 @@ pub struct ResponseBuilder {
 pub enum ResponseCode {
     OK,             // <Code@10> (general good)
     AuthOK,         // <Code@11> (binding complete)
-    ParseError,     // <Code@20> (no use for now, but reserved for general parse erros)
+    ParseError,     // <Code@20> (request couldnt be parsed, `<Reason@...>` tells why)
     InvalidName,    // <Code@21>
     AlreadyTaken,   // <Code@22> (name already taken)
     InvalidHeader,  // <Code@23>
     Unauthorized,   // <Code@24> (Invalid token / token parse failed)
+    UnsupportedVersion, // <Code@25> (Router doesnt speak this version, `<Supported@...>` lists the ones it does)
+    Undeliverable,  // <Code@26> (Direct message `<To@...>` couldnt be delivered, user is not connected)
+    RateLimited,    // <Code@27> (Too many requests, `<RetryAfter@...>` tells how many ms to wait)
     Error,          // <Code@30> (General error)
     FatalError,     // <Code@31> (Cant recover from this)
     Custom(u8),     // <Code@{val}> 
@@ -347,6 +355,7 @@ impl ResponseBuilder {
             user: None,
             time: None,
             message: None,
+            reason: None,
             custom: None,
             varmap: None,
         }
@@ -449,6 +458,23 @@ impl ResponseBuilder {
         self
     }
 
+    /// Setter for `reason` field. It is sent as `<Reason@'...'>`, mostly together with
+    /// [`ResponseCode::ParseError`], so client knows what was wrong with its request.
+    /// 
+    /// ## Example
+    /// ```
+    /// let response = ResponseBuilder::new()
+    ///     .version(Version::CHAT10)
+    ///     .code(ResponseCode::ParseError)
+    ///     .reason(parse_error.to_string())
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn reason(mut self, reason: String) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+
     /// Insert for `varmap` field
     /// 
     /// ## Example
@@ -549,6 +575,7 @@ impl ResponseBuilder {
                     user: self.user,
                     time: self.time,
                     message: self.message,
+                    reason: self.reason,
                     custom: self.custom,
                     varmap: self.varmap,
                 });
@@ -570,6 +597,7 @@ impl Default for ResponseBuilder {
             user: None,
             time: None,
             message: None,
+            reason: None,
             custom: None,
             varmap: None,
         }
@@ -578,13 +606,13 @@ impl Default for ResponseBuilder {
 
 impl Response {
     /// ## Response::from_bytes(read_buf)
-    /// This function, is for retrieving `Response` out of `[u8; 512]`.
+    /// This function, is for retrieving `Response` out of the frame payload.
     /// This function should be used on the [`Client`] side
     /// ## Example
     /// ```
     /// // Lets say that we have some function that translates
-    /// // String to the [u8; 512].
-    /// let line = string_to_bytes("<CHAT \\ 1.0>\n<Code@10>\n<User@\"Jeff\">\n<Message@\"Hello world!\">".to_string());
+    /// // String to the Bytes.
+    /// let read_buf = string_to_bytes("<CHAT \\ 1.0>\n<Code@10>\n<User@\"Jeff\">\n<Message@\"Hello world!\">".to_string());
     /// 
     /// let response = Response::from_bytes(read_buf).unwrap();
     /// println!("Look what i got: {:?}", response);
@@ -593,84 +621,8 @@ impl Response {
     /// **IMPORTANT** THE READ_BUF SHOULD BE UTF-8 PARSABLE 
     /// 
     /// [`Client`]: crate::client
-    pub fn from_bytes(read_buf: &[u8; 512]) -> Result<Response, ParseError> {
-        // We have Regex based parsing here, so its important for us to parse into &str
-        let response = match str::from_utf8(read_buf) {
-            Ok(val) => val.trim_end_matches('\0'),
-            Err(_) => {
-                return Err(ParseError::InvalidFormat);
-            }
-        };
-
-        // Its much easier to work with it by turning into Lines
-        let mut lines = response.lines();
-
-        // Its guaranteed, that Version and Code are at the first and second lines respectivly.
-        let version = extract_version(&mut lines)?;
-        let code = extract_code(&mut lines)?;
-
-        let mut response = ResponseBuilder::new()
-            .version(version)
-            .code(code);
-
-        while let Some(line) = lines.next() {
-            match extract_user(line) {
-                Ok(val) => {
-                    response = response.user(val);
-                    continue;
-                },
-                Err(ParseError::NotFound) => {},
-                Err(e) => {
-                    return Err(e);
-                }
-            }
-
-            match extract_time(line) {
-                Ok(val) => {
-                    response = response.time(val);
-                    continue;
-                },
-                Err(ParseError::NotFound) => {},
-                Err(e) => {
-                    return Err(e);
-                }
-            }
-
-            match extract_token(line) {
-                Ok(val) => {
-                    response = response.token(val);
-                    continue;
-                },
-                Err(ParseError::NotFound) => {},
-                Err(e) => {
-                    return Err(e);
-                }
-            }
-
-            match extract_message(line) {
-                Ok(val) => {
-                    response = response.message(val);
-                    continue;
-                },
-                Err(ParseError::NotFound) => {},
-                Err(e) => {
-                    return Err(e);
-                }
-            }
-
-            // If we didnt manage to find any presetted header - just insert it into custom field.
-            let (key, value) = match parse_key_value(line) {
-                Ok(val) => val,
-                Err(e) => {
-                    return Err(e);
-                }
-            };
-
-            response = response.custom_insert(key, value);
-        }
-        
-        // Can unwrap, because if we havent found Version or Code yet, we would've exited function already
-        Ok(response.build().unwrap())
+    pub fn from_bytes(read_buf: &[u8]) -> Result<Response, ParseError> {
+        parse_response(Parser::from_bytes(read_buf)?)
     }
 
     /// ## Response::pretty_string(&self)
@@ -689,21 +641,25 @@ impl Response {
         let mut response_line = format!("<CHAT \\ {0}>\n{1}", self.version.to_str(), self.code.to_string());
 
         if let Some(token) = &self.token {
-            response_line += &format!("\n<Token@'{token}'>");
+            response_line += &format!("\n<Token@'{0}'>", escape(token));
         }
 
         if let Some(user) = &self.user {
-            response_line += &format!("\n<User@'{user}'>");
+            response_line += &format!("\n<User@'{0}'>", escape(user));
+        }
+
+        if let Some(reason) = self.reason.as_ref().filter(|_| self.version.has_header("Reason")) {
+            response_line += &format!("\n<Reason@'{0}'>", escape(reason));
         }
 
         if let Some(custom) = &self.custom {
-            for (key, value) in custom {
-                response_line += &format!("\n<{key}@'{value}'>");
+            for (key, value) in custom.iter().filter(|(key, _)| self.version.has_header(key)) {
+                response_line += &format!("\n<{key}@'{0}'>", escape(value));
             }
         }
 
         if let Some(message) = &self.message {
-            response_line += &format!("\n<Message@'{message}'>");
+            response_line += &format!("\n<Message@'{0}'>", escape(message));
         }
 
         response_line
@@ -722,31 +678,39 @@ impl Response {
     /// 
     /// let res_bytes = response.as_bytes().unwrap();
     /// 
-    /// match stream.write(&res_bytes).await {...}
+    /// match write_frame(&mut stream, &codec, &res_bytes).await {...}
     /// ```
-    pub fn as_bytes(&self) -> Result<[u8; 512], ()> {
+    /// 
+    /// Returned bytes are the frame payload, length header is added by [`FrameCodec`].
+    /// Values are escaped (see [`escape`]), so messages with `'`, `>` or newlines are fine.
+    /// Headers that `version` doesnt know (see [`Version::has_header`]) are skipped.
+    /// 
+    /// [`FrameCodec`]: crate::protocol::frame::FrameCodec
+    /// [`escape`]: crate::protocol::escape
+    /// [`Version::has_header`]: crate::protocol::request::Version::has_header
+    pub fn as_bytes(&self) -> Result<Bytes, ()> {
         let mut response_line = format!("<CHAT \\ {0}>\n{1}", self.version.to_str(), self.code.to_string());
 
         if let Some(token) = &self.token {
-            response_line += &format!("\n<Token@'{token}'>");
+            response_line += &format!("\n<Token@'{0}'>", escape(token));
         }
 
         if let Some(user) = &self.user {
-            response_line += &format!("\n<User@'{user}'>");
+            response_line += &format!("\n<User@'{0}'>", escape(user));
+        }
+
+        if let Some(reason) = self.reason.as_ref().filter(|_| self.version.has_header("Reason")) {
+            response_line += &format!("\n<Reason@'{0}'>", escape(reason));
         }
 
         if let Some(custom) = &self.custom {
-            for (key, value) in custom {
-                response_line += &format!("\n<{key}@'{value}'>");
+            for (key, value) in custom.iter().filter(|(key, _)| self.version.has_header(key)) {
+                response_line += &format!("\n<{key}@'{0}'>", escape(value));
             }
         }
 
         if let Some(message) = &self.message {
-            response_line += &format!("\n<Message@'{message}'>");
-        }
-
-        if response_line.len() > 512 {
-            return Err(());
+            response_line += &format!("\n<Message@'{0}'>", escape(message));
         }
 
         let bytes = string_to_bytes(response_line);
@@ -754,87 +718,55 @@ impl Response {
     }
 }
 
-fn extract_code(lines: &mut std::str::Lines<'_>) -> Result<ResponseCode, ParseError>{
-    let code_line = match lines.next() {
-        Some(val) => val,
-        None => {
-            return Err(ParseError::MissingCode);
-        }
-    };
-
-    match ResponseCode::from_str(code_line) {
-        Ok(val) => Ok(val),
-        Err(_) => Err(ParseError::MissingCode)
-    }
-}
-
-fn extract_user(line: &str) -> Result<String, ParseError> {
-    let (key, value) = match parse_key_value(line) {
-        Ok(val) => val,
-        Err(e) => {
-            println!("key value doesnt like it");
-            return Err(e);
-        }
-    };
-
-    if key == "User" {
-        return Ok(value);
-    }
-
-    return Err(ParseError::NotFound)
-}
-
-fn extract_token(line: &str) -> Result<String, ParseError> {
-    let (key, value) = match parse_key_value(line) {
-        Ok(val) => val,
-        Err(e) => {
-            return Err(e);
-        }
-    };
-
-    if key == "Token" {
-        return Ok(value);
-    }
-
-    return Err(ParseError::NotFound)
-}
-
-fn extract_time(line: &str) -> Result<DateTime<Utc>, ParseError> {
-    let (key, value) = match parse_key_value(line) {
-        Ok(val) => val,
-        Err(e) => {
-            return Err(e);
-        }
-    };
-
-    if key == "Time" {
-        let parsed_naive = match NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S") {
-            Ok(val) => val,
-            Err(_) => {
-                return Err(ParseError::InvalidFormat);
-            }
+// Same path as the `Request` has, just other headers are expected.
+fn parse_response(mut parser: Parser<'_>) -> Result<Response, ParseError> {
+    // Its guaranteed, that Version and Code are at the first and second lines respectivly.
+    let version = parser.version()?;
+    let code = extract_code(&mut parser)?;
+
+    let mut response = ResponseBuilder::new()
+        .version(version)
+        .code(code);
+
+    for header in parser {
+        let header = header?;
+
+        response = match header.key {
+            "User" => response.user(header.value.into_owned()),
+            "Token" => response.token(header.value.into_owned()),
+            "Message" => response.message(header.value.into_owned()),
+            "Reason" => response.reason(header.value.into_owned()),
+            "Time" => {
+                let parsed_naive = match NaiveDateTime::parse_from_str(&header.value, "%Y-%m-%d %H:%M:%S") {
+                    Ok(val) => val,
+                    Err(_) => {
+                        return Err(header.error(ParseErrorKind::InvalidFormat));
+                    }
+                };
+
+                response.time(Utc.from_utc_datetime(&parsed_naive))
+            },
+            // If its not any presetted header - just insert it into custom field.
+            key => response.custom_insert(key.to_string(), header.value.into_owned()),
         };
-        let parsed_utc: DateTime<Utc> = Utc.from_utc_datetime(&parsed_naive);
-
-        return Ok(parsed_utc);
     }
 
-    return Err(ParseError::NotFound)
+    // Can unwrap, because if we havent found Version or Code yet, we would've exited function already
+    Ok(response.build().unwrap())
 }
 
-fn extract_message(line: &str) -> Result<String, ParseError> {
-    let (key, value) = match parse_key_value(line) {
-        Ok(val) => val,
-        Err(e) => {
-            return Err(e);
-        }
-    };
+fn extract_code(parser: &mut Parser<'_>) -> Result<ResponseCode, ParseError> {
+    let header = parser
+        .expect_header(ParseErrorKind::MissingCode)
+        .map_err(|e| e.with_expected("Code"))?;
 
-    if key == "Message" {
-        return Ok(value);
+    if header.key == "Code" {
+        if let Ok(val) = header.value.parse::<u8>() {
+            return Ok(ResponseCode::from(val));
+        }
     }
 
-    return Err(ParseError::NotFound)
+    Err(header.error(ParseErrorKind::MissingCode).with_expected("Code"))
 }
 
 impl ResponseCode {
@@ -847,6 +779,9 @@ impl ResponseCode {
             ResponseCode::AlreadyTaken  => "<Code@22>".to_string(),
             ResponseCode::InvalidHeader => "<Code@23>".to_string(),
             ResponseCode::Unauthorized  => "<Code@24>".to_string(),
+            ResponseCode::UnsupportedVersion => "<Code@25>".to_string(),
+            ResponseCode::Undeliverable => "<Code@26>".to_string(),
+            ResponseCode::RateLimited   => "<Code@27>".to_string(),
             ResponseCode::Error         => "<Code@30>".to_string(),
             ResponseCode::FatalError    => "<Code@31>".to_string(),
             ResponseCode::Custom(val) => format!("<Code@{val}>"),
@@ -859,21 +794,32 @@ impl FromStr for ResponseCode {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match extract_val(s) {
-            Some(10) => Ok(ResponseCode::OK),
-            Some(11) => Ok(ResponseCode::AuthOK),
-            Some(20) => Ok(ResponseCode::ParseError),
-            Some(21) => Ok(ResponseCode::InvalidName),
-            Some(22) => Ok(ResponseCode::AlreadyTaken),
-            Some(23) => Ok(ResponseCode::InvalidHeader),
-            Some(24) => Ok(ResponseCode::Unauthorized),
-            Some(30) => Ok(ResponseCode::Error),
-            Some(31) => Ok(ResponseCode::FatalError),
-            Some(val) => Ok(ResponseCode::Custom(val)),
+            Some(val) => Ok(ResponseCode::from(val)),
             None => Err(()),
         }
     }
 }
 
+impl From<u8> for ResponseCode {
+    fn from(val: u8) -> Self {
+        match val {
+            10 => ResponseCode::OK,
+            11 => ResponseCode::AuthOK,
+            20 => ResponseCode::ParseError,
+            21 => ResponseCode::InvalidName,
+            22 => ResponseCode::AlreadyTaken,
+            23 => ResponseCode::InvalidHeader,
+            24 => ResponseCode::Unauthorized,
+            25 => ResponseCode::UnsupportedVersion,
+            26 => ResponseCode::Undeliverable,
+            27 => ResponseCode::RateLimited,
+            30 => ResponseCode::Error,
+            31 => ResponseCode::FatalError,
+            val => ResponseCode::Custom(val),
+        }
+    }
+}
+
 // Special function needed for impl FromStr ResponseCode
 fn extract_val(input: &str) -> Option<u8> {
     input.strip_prefix("<Code@")?
@@ -883,20 +829,14 @@ fn extract_val(input: &str) -> Option<u8> {
 }
 
 /// Do i need to move it to utils?
-/// This function is for feading `String` to `[u8; 512]`.
-/// Why do i need that one? Because im using `[u8; 512]` and
-/// I didnt manage to find any human way to translate `String`
-/// to the `[u8; 512]`
+/// This function is for feading `String` to `Bytes`.
+/// It used to pad / cut everything to the `[u8; 512]`, now its just the
+/// conversion, because size is handled by [`FrameCodec`].
 /// 
 /// Made it public, so people can use it in [`StartingBytesware`]
 /// 
 /// [`StartingBytesware`]: crate::protocol::wares::starting_bytesware
-pub fn string_to_bytes(input: String) -> [u8; 512] {
-    let mut buffer = [0u8; 512]; 
-    let bytes = input.as_bytes();
-    
-    let len = bytes.len().min(512);
-    buffer[..len].copy_from_slice(&bytes[..len]);
-    
-    buffer
+/// [`FrameCodec`]: crate::protocol::frame::FrameCodec
+pub fn string_to_bytes(input: String) -> Bytes {
+    Bytes::from(input)
 }
\ No newline at end of file
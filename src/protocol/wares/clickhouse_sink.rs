@@ -0,0 +1,418 @@
+//! ## `clickhouse_sink`
+//!
+//! Built-in analytics ware. Every sent message, and every connect, bind, handshake and disconnect is written
+//! into the ClickHouse tables, so chat volume per user and per hour is one query away. Rows are not sent one by one,
+//! they are batched by the `clickhouse` inserter in the background task, and flushed every `flush_interval`
+//! or when there are `max_rows` of them, whatever comes first. Router never waits for ClickHouse.
+//!
+//! [`ClickHouseSink`] is the [`Layer`] (for `Bind`, `Handshake` and `Send`), [`BeforeConnect`] and [`AfterConnect`]
+//! at the same time, [`ClickHouseSink::attach`] puts it everywhere it is needed.
+//!
+//! ## Example
+//! ```
+//! let sink = ClickHouseSink::builder(clickhouse::Client::default().with_url("http://localhost:8123"))
+//!     .flush_interval(Duration::from_secs(5))
+//!     .build();
+//!
+//! sink.create_tables().await?;
+//!
+//! let router = sink.attach(RouterBuilder::new()).build();
+//! ```
+//!
+//! Then messages per user and per hour are:
+//! ```sql
+//! SELECT user, toStartOfHour(time) AS hour, count() FROM chat_sent WHERE delivered GROUP BY user, hour ORDER BY hour
+//! ```
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clickhouse::{inserter::Inserter, Client, Row};
+use crate::protocol::request::{Method, Request};
+use crate::protocol::response::Response;
+use crate::router::{RouterBuilder, State};
+use serde::Serialize;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::{oneshot, Mutex};
+use tracing::{trace, warn};
+
+use super::{AfterConnect, BeforeConnect, Layer, Next};
+
+/// Table for the sent messages, if [`ClickHouseSinkBuilder::sent_table`] wasnt set.
+pub const DEFAULT_SENT_TABLE: &str = "chat_sent";
+
+/// Table for the events, if [`ClickHouseSinkBuilder::events_table`] wasnt set.
+pub const DEFAULT_EVENTS_TABLE: &str = "chat_events";
+
+/// How often rows are flushed, if [`ClickHouseSinkBuilder::flush_interval`] wasnt set.
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Shortest flush interval, zero one is clamped to it (timer cant tick every 0 seconds).
+pub const MIN_FLUSH_INTERVAL: Duration = Duration::from_millis(1);
+
+/// How many rows are batched at most, if [`ClickHouseSinkBuilder::max_rows`] wasnt set.
+pub const DEFAULT_MAX_ROWS: u64 = 10_000;
+
+/// ## `SentRow`
+///
+/// One sent message, the one that the `Send` middleware let through. `room` and `to` are None for the messages to everyone.
+/// `delivered` is false for `<To@...>`, when the user wasnt connected (sender got `<Code@26>` for it).
+#[derive(Debug, Clone, PartialEq, Row, Serialize)]
+pub struct SentRow {
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub time: DateTime<Utc>,
+    pub connection: u64,
+    pub user: String,
+    pub room: Option<String>,
+    pub to: Option<String>,
+    pub message: String,
+    pub delivered: bool,
+}
+
+/// ## `EventRow`
+///
+/// One connect, bind, handshake or disconnect. `user` is None, when it is not known yet (or it is `connect`).
+#[derive(Debug, Clone, PartialEq, Row, Serialize)]
+pub struct EventRow {
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub time: DateTime<Utc>,
+    pub event: String,      // `connect`, `bind`, `handshake`, `disconnect`
+    pub connection: u64,
+    pub user: Option<String>,
+}
+
+// What goes to the background task
+enum Command {
+    Sent(SentRow),
+    Event(EventRow),
+    Flush(oneshot::Sender<()>),
+}
+
+/// ## `ClickHouseSink`
+///
+/// Handle to the background task, that batches rows into ClickHouse. Cloning it gives the same task,
+/// so it could be used in many places. Task ends (after the last flush), when every clone is dropped.
+#[derive(Clone)]
+pub struct ClickHouseSink {
+    pub sent_table: String,
+    pub events_table: String,
+    client: Client,
+    tx: UnboundedSender<Command>,
+}
+
+impl Debug for ClickHouseSink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClickHouseSink")
+            .field("sent_table", &self.sent_table)
+            .field("events_table", &self.events_table)
+            .finish_non_exhaustive()
+    }
+}
+
+/// ## `ClickHouseSinkBuilder`
+///
+/// Everything that is not set gets its `DEFAULT_*` value in `build()`.
+pub struct ClickHouseSinkBuilder {
+    client: Client,
+    sent_table: Option<String>,
+    events_table: Option<String>,
+    flush_interval: Option<Duration>,
+    max_rows: Option<u64>,
+}
+
+impl ClickHouseSinkBuilder {
+    pub fn new(client: Client) -> Self {
+        ClickHouseSinkBuilder {
+            client,
+            sent_table: None,
+            events_table: None,
+            flush_interval: None,
+            max_rows: None,
+        }
+    }
+
+    pub fn sent_table(mut self, table: &str) -> Self {
+        self.sent_table = Some(table.to_string());
+        self
+    }
+
+    pub fn events_table(mut self, table: &str) -> Self {
+        self.events_table = Some(table.to_string());
+        self
+    }
+
+    /// Longest time row waits in the batch before it is sent. At least [`MIN_FLUSH_INTERVAL`].
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval.max(MIN_FLUSH_INTERVAL));
+        self
+    }
+
+    /// Batch is sent right away, when it has this many rows.
+    pub fn max_rows(mut self, max_rows: u64) -> Self {
+        self.max_rows = Some(max_rows);
+        self
+    }
+
+    /// Spawns the background task, so it must be called inside of the tokio runtime.
+    pub fn build(self) -> ClickHouseSink {
+        let sent_table = self.sent_table.unwrap_or(DEFAULT_SENT_TABLE.to_string());
+        let events_table = self.events_table.unwrap_or(DEFAULT_EVENTS_TABLE.to_string());
+        let max_rows = self.max_rows.unwrap_or(DEFAULT_MAX_ROWS);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let sent = Batch::new(&self.client, &sent_table, max_rows);
+        let events = Batch::new(&self.client, &events_table, max_rows);
+        tokio::spawn(run(rx, sent, events, self.flush_interval.unwrap_or(DEFAULT_FLUSH_INTERVAL)));
+
+        ClickHouseSink {
+            sent_table,
+            events_table,
+            client: self.client,
+            tx,
+        }
+    }
+}
+
+impl ClickHouseSink {
+    pub fn builder(client: Client) -> ClickHouseSinkBuilder {
+        ClickHouseSinkBuilder::new(client)
+    }
+
+    /// Puts the sink on `Bind`, `Handshake` and `Send` as the [`Layer`] (outermost, if it is attached before the others),
+    /// and as `before` and `after`. The `before` and `after` that were set are kept, and ran right after the sink.
+    pub fn attach(&self, mut builder: RouterBuilder) -> RouterBuilder {
+        let before = Chained { sink: self.clone(), inner: builder.before.take() };
+        let after = Chained { sink: self.clone(), inner: builder.after.take() };
+
+        builder
+            .layer(Method::Bind.to_str(), Box::new(self.clone()))
+            .layer(Method::Handshake.to_str(), Box::new(self.clone()))
+            .layer(Method::Send.to_str(), Box::new(self.clone()))
+            .before(Box::new(before))
+            .after(Box::new(after))
+    }
+
+    /// Creates both tables, if there are none. They are `MergeTree`'s ordered by the user and the time.
+    pub async fn create_tables(&self) -> clickhouse::error::Result<()> {
+        self.client.query(&format!(
+            "CREATE TABLE IF NOT EXISTS {0} (time DateTime, connection UInt64, user String, room Nullable(String), `to` Nullable(String), message String, delivered Bool) ENGINE = MergeTree ORDER BY (user, time)",
+            self.sent_table
+        )).execute().await?;
+
+        self.client.query(&format!(
+            "CREATE TABLE IF NOT EXISTS {0} (time DateTime, event LowCardinality(String), connection UInt64, user Nullable(String)) ENGINE = MergeTree ORDER BY (event, time)",
+            self.events_table
+        )).execute().await
+    }
+
+    /// Adds the sent message to the batch. It could be used from the custom wares too.
+    pub fn sent(&self, row: SentRow) {
+        let _ = self.tx.send(Command::Sent(row));
+    }
+
+    /// Adds the event to the batch. It could be used from the custom wares too.
+    pub fn event(&self, event: &str, connection: u64, user: Option<String>) {
+        let _ = self.tx.send(Command::Event(EventRow {
+            time: Utc::now(),
+            event: event.to_string(),
+            connection,
+            user,
+        }));
+    }
+
+    /// Sends everything that is batched right now, and waits for it.
+    pub async fn flush(&self) {
+        let (done, wait) = oneshot::channel();
+        if self.tx.send(Command::Flush(done)).is_ok() {
+            let _ = wait.await;
+        }
+    }
+}
+
+// Connection id and the bound name, if there is one
+async fn who(state: &Arc<Mutex<State>>) -> (u64, Option<String>) {
+    let locked = state.lock().await;
+    (locked.id.map(|val| val.0).unwrap_or_default(), locked.varmap.get::<String>().cloned())
+}
+
+#[async_trait]
+impl Layer for ClickHouseSink {
+    async fn call(&self, req: Request, state: Arc<Mutex<State>>, next: Next<'_>) -> Result<Response, Response> {
+        let method = req.method.clone();
+        let room = req.custom.get("Room").cloned();
+        let to = req.custom.get("To").cloned();
+        let value = req.value.clone();     // Message for `Send`, name for `Bind`
+
+        // Only what went through is written
+        let res = next.run(req, state.clone()).await?;
+
+        let (connection, user) = who(&state).await;
+        match method {
+            Method::Send => {
+                // Router delivers `<To@...>` right after, to the same `online` connection, if there is one
+                let delivered = match &to {
+                    Some(to) => state.lock().await.app.lock().await.online.contains_key(to),
+                    None => true,
+                };
+
+                self.sent(SentRow {
+                    time: Utc::now(),
+                    connection,
+                    user: user.unwrap_or_default(),
+                    room,
+                    to,
+                    message: value,
+                    delivered,
+                })
+            },
+            // Bind is usually on its own connection, so name is from the request
+            Method::Bind => self.event("bind", connection, Some(value)),
+            Method::Handshake => self.event("handshake", connection, user),
+            _ => {},
+        }
+
+        Ok(res)
+    }
+}
+
+#[async_trait]
+impl BeforeConnect for ClickHouseSink {
+    async fn execute(&self, state: Arc<Mutex<State>>) {
+        let (connection, user) = who(&state).await;
+        self.event("connect", connection, user);
+    }
+}
+
+#[async_trait]
+impl AfterConnect for ClickHouseSink {
+    async fn execute(&self, state: Arc<Mutex<State>>) {
+        let (connection, user) = who(&state).await;
+        self.event("disconnect", connection, user);
+    }
+}
+
+// Sink together with the `before` / `after` it replaced in the `attach`
+#[derive(Debug)]
+struct Chained<T: ?Sized> {
+    sink: ClickHouseSink,
+    inner: Option<Box<T>>,
+}
+
+#[async_trait]
+impl BeforeConnect for Chained<dyn BeforeConnect> {
+    async fn execute(&self, state: Arc<Mutex<State>>) {
+        BeforeConnect::execute(&self.sink, state.clone()).await;
+        if let Some(inner) = &self.inner {
+            inner.execute(state).await;
+        }
+    }
+}
+
+#[async_trait]
+impl AfterConnect for Chained<dyn AfterConnect> {
+    async fn execute(&self, state: Arc<Mutex<State>>) {
+        AfterConnect::execute(&self.sink, state.clone()).await;
+        if let Some(inner) = &self.inner {
+            inner.execute(state).await;
+        }
+    }
+}
+
+// Rows of one table. Inserter is made again after the error, so one failed insert doesnt stop the sink.
+struct Batch<T> {
+    client: Client,
+    table: String,
+    max_rows: u64,
+    inserter: Option<Inserter<T>>,
+}
+
+impl<T: Row + Serialize> Batch<T> {
+    fn new(client: &Client, table: &str, max_rows: u64) -> Self {
+        Batch {
+            client: client.clone(),
+            table: table.to_string(),
+            max_rows,
+            inserter: None,
+        }
+    }
+
+    async fn write(&mut self, row: &T) {
+        if self.inserter.is_none() {
+            match self.client.inserter::<T>(&self.table) {
+                Ok(val) => self.inserter = Some(val.with_max_rows(self.max_rows)),
+                Err(e) => {
+                    warn!(error = %e, table = %self.table, "Couldnt make the inserter");
+
+                    return;
+                }
+            }
+        }
+
+        let Some(inserter) = self.inserter.as_mut() else {
+            return;
+        };
+
+        // Commit only sends, when there are `max_rows`
+        let res = match inserter.write(row) {
+            Ok(()) => inserter.commit().await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = res {
+            warn!(error = %e, table = %self.table, "Failed to write");
+
+            self.inserter = None;
+        }
+    }
+
+    async fn flush(&mut self) {
+        let Some(inserter) = self.inserter.as_mut() else {
+            return;
+        };
+
+        if inserter.pending().rows == 0 {
+            return;
+        }
+
+        match inserter.force_commit().await {
+            Ok(quantities) => {
+                trace!(rows = quantities.rows, table = %self.table, "Flushed rows");
+            },
+            Err(e) => {
+                warn!(error = %e, table = %self.table, "Failed to flush");
+
+                self.inserter = None;
+            }
+        }
+    }
+}
+
+async fn run(mut rx: UnboundedReceiver<Command>, mut sent: Batch<SentRow>, mut events: Batch<EventRow>, flush_interval: Duration) {
+    // First tick is after the whole interval, not right away
+    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + flush_interval, flush_interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            command = rx.recv() => match command {
+                Some(Command::Sent(row)) => sent.write(&row).await,
+                Some(Command::Event(row)) => events.write(&row).await,
+                Some(Command::Flush(done)) => {
+                    sent.flush().await;
+                    events.flush().await;
+                    let _ = done.send(());
+                },
+                // Every sink is dropped
+                None => break,
+            },
+            _ = ticks.tick() => {
+                sent.flush().await;
+                events.flush().await;
+            }
+        }
+    }
+
+    sent.flush().await;
+    events.flush().await;
+}
@@ -5,7 +5,7 @@
 //! [`Method`]: crate::protocol::request::Method
 use std::sync::Arc;
 use async_trait::async_trait;
-use crate::protocol::request::Version;
+use bytes::Bytes;
 use crate::protocol::response::{Response, ResponseBuilder, ResponseCode};
 use crate::protocol::wares::EndingBytesware;
 use crate::router::State;
@@ -19,14 +19,14 @@ use tokio::sync::Mutex;
 /// ```
 /// #[async_trait]
 /// impl EndingBytesware for DefaultEndingBytesware {
-///     async fn bytesware(&self, _: Arc<Mutex<State>>, res: Result<Response, Response>) -> Result<[u8; 512], [u8; 512]> {
+///     async fn bytesware(&self, _: Arc<Mutex<State>>, res: Result<Response, Response>) -> Result<Bytes, Bytes> {
 ///         match res {
 ///             Ok(res) => {
 ///                 match res.as_bytes() {
 ///                     Ok(val) => Ok(val),
 ///                     Err(_) => {
 ///                         let res = ResponseBuilder::new()
-///                             .version(Version::CHAT10)
+///                             .version(res.version)
 ///                             .code(ResponseCode::ParseError)
 ///                             .build()
 ///                             .unwrap();
@@ -40,7 +40,7 @@ use tokio::sync::Mutex;
 ///                     Ok(val) => Err(val),
 ///                     Err(_) => {
 ///                         let res = ResponseBuilder::new()
-///                             .version(Version::CHAT10)
+///                             .version(res.version)
 ///                             .code(ResponseCode::ParseError)
 ///                             .build()
 ///                             .unwrap();
@@ -59,14 +59,14 @@ pub struct DefaultEndingBytesware;
 
 #[async_trait]
 impl EndingBytesware for DefaultEndingBytesware {
-    async fn bytesware(&self, _: Arc<Mutex<State>>, res: Result<Response, Response>) -> Result<[u8; 512], [u8; 512]> {
+    async fn bytesware(&self, _: Arc<Mutex<State>>, res: Result<Response, Response>) -> Result<Bytes, Bytes> {
         match res {
             Ok(res) => {
                 match res.as_bytes() {
                     Ok(val) => Ok(val),
                     Err(_) => {
                         let res = ResponseBuilder::new()
-                            .version(Version::CHAT10)
+                            .version(res.version)
                             .code(ResponseCode::ParseError)
                             .build()
                             .unwrap();
@@ -80,7 +80,7 @@ impl EndingBytesware for DefaultEndingBytesware {
                     Ok(val) => Err(val),
                     Err(_) => {
                         let res = ResponseBuilder::new()
-                            .version(Version::CHAT10)
+                            .version(res.version)
                             .code(ResponseCode::ParseError)
                             .build()
                             .unwrap();
@@ -2,6 +2,7 @@
 //! 
 //! This is the module... go look at default implementators and [`EndingBytesware`] trait.
 use async_trait::async_trait;
+use bytes::Bytes;
 use tokio::sync::Mutex;
 use std::{fmt::Debug, sync::Arc};
 
@@ -10,6 +11,7 @@ use crate::{protocol::response::Response, router::State};
 pub mod default_bind;
 pub mod default_handshake;
 pub mod default_send;
+pub mod default_room;
 
 /// ## `EndingBytesware`
 /// 
@@ -20,7 +22,7 @@ pub mod default_send;
 /// ```
 /// #[async_trait]
 /// pub trait EndingBytesware: Debug + Send + Sync {
-///     async fn bytesware(&self, state: Arc<Mutex<State>>, res: Result<Response, Response>) -> Result<[u8; 512], [u8; 512]>;
+///     async fn bytesware(&self, state: Arc<Mutex<State>>, res: Result<Response, Response>) -> Result<Bytes, Bytes>;
 /// }
 /// ``` 
 /// 
@@ -30,5 +32,5 @@ pub mod default_send;
 /// [`Router`]: crate::router::Router
 #[async_trait]
 pub trait EndingBytesware: Debug + Send + Sync {
-    async fn bytesware(&self, state: Arc<Mutex<State>>, res: Result<Response, Response>) -> Result<[u8; 512], [u8; 512]>;
+    async fn bytesware(&self, state: Arc<Mutex<State>>, res: Result<Response, Response>) -> Result<Bytes, Bytes>;
 }
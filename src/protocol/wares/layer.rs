@@ -0,0 +1,116 @@
+//! ## `Layer`
+//!
+//! [`Middleware`] of the route could be wrapped into layers, same as in `tower`. Every layer gets the request
+//! and [`Next`], and either calls `next.run(req, state)` or answers right away. So auth, rate limiting,
+//! filtering and logging could be stacked on `Send`, without writing one giant middleware.
+//!
+//! Layers are added via [`RouterBuilder::layer`], first added is the outermost one (runs first).
+//!
+//! ## Example
+//! ```
+//! #[derive(Debug)]
+//! struct NoShouting;
+//!
+//! #[async_trait]
+//! impl Layer for NoShouting {
+//!     async fn call(&self, req: Request, state: Arc<Mutex<State>>, next: Next<'_>) -> Result<Response, Response> {
+//!         if req.value.chars().any(|c| c.is_lowercase()) {
+//!             return next.run(req, state).await;
+//!         }
+//!
+//!         Err(ResponseBuilder::new()
+//!             .version(req.version)
+//!             .code(ResponseCode::Error)
+//!             .reason("no shouting".to_string())
+//!             .build()
+//!             .unwrap())
+//!     }
+//! }
+//!
+//! let router = RouterBuilder::new()
+//!     .layer("Send", Box::new(Logger))
+//!     .layer("Send", Box::new(NoShouting))
+//!     .build();
+//! ```
+//!
+//! [`RouterBuilder::layer`]: crate::router::RouterBuilder::layer
+use async_trait::async_trait;
+use crate::protocol::{request::Request, response::Response};
+use crate::router::State;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::Middleware;
+
+/// ## `Layer`
+///
+/// One layer around the [`Middleware`]. Not calling `next` means that the request stops here,
+/// and the returned `Response` goes to the `EndingBytesware`.
+///
+/// ## How it looks like in the human way
+/// ```
+/// #[async_trait]
+/// pub trait Layer: Debug + Send + Sync {
+///     async fn call(&self, req: Request, state: Arc<Mutex<State>>, next: Next<'_>) -> Result<Response, Response>;
+/// }
+/// ```
+#[async_trait]
+pub trait Layer: Debug + Send + Sync {
+    async fn call(&self, req: Request, state: Arc<Mutex<State>>, next: Next<'_>) -> Result<Response, Response>;
+}
+
+/// ## `Next`
+///
+/// Rest of the chain: layers that are left and the [`Middleware`] in the end of it.
+#[derive(Debug, Clone, Copy)]
+pub struct Next<'a> {
+    layers: &'a [Box<dyn Layer>],
+    middleware: &'a dyn Middleware,
+}
+
+impl<'a> Next<'a> {
+    /// Passes the request to the next layer, or to the middleware, if there are no layers left.
+    pub async fn run(self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+        match self.layers.split_first() {
+            Some((layer, rest)) => {
+                let next = Next {
+                    layers: rest,
+                    middleware: self.middleware,
+                };
+
+                layer.call(req, state, next).await
+            },
+            None => self.middleware.middleware(req, state).await,
+        }
+    }
+}
+
+/// ## `Layered`
+///
+/// [`Middleware`] with the layers around it. It is still the [`Middleware`], so it is a usual [`Route`].
+///
+/// [`Route`]: crate::protocol::wares::Route
+#[derive(Debug)]
+pub struct Layered {
+    pub layers: Vec<Box<dyn Layer>>,    // Outermost first
+    pub middleware: Box<dyn Middleware>,
+}
+
+impl Layered {
+    pub fn new(layers: Vec<Box<dyn Layer>>, middleware: Box<dyn Middleware>) -> Self {
+        Layered { layers, middleware }
+    }
+}
+
+#[async_trait]
+impl Middleware for Layered {
+    async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+        let next = Next {
+            layers: &self.layers,
+            middleware: self.middleware.as_ref(),
+        };
+
+        next.run(req, state).await
+    }
+}
@@ -1,13 +1,16 @@
 use async_trait::async_trait;
-use crate::{protocol::{wares::Middleware, request::{Request, Version}, response::{Response, ResponseCode, ResponseBuilder}}, router::State};
+use crate::{protocol::{wares::Middleware, request::Request, response::{Response, ResponseCode, ResponseBuilder}}, router::{AuthError, State}};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 /// ## `DefaultMiddleware`
 /// 
-/// This is the default implementator for the [`Middleware`] for the `Bind` [`Method`]
+/// This is the default implementator for the [`Middleware`] for the `Bind` [`Method`].
+/// Name is given by the [`Authenticator`] of the router, password is from the `<Password@...>` header.
+/// Name against the `NamePolicy` is answered with `InvalidName` and the reason.
 /// 
 /// [`Method`]: crate::protocol::request::Method
+/// [`Authenticator`]: crate::router::Authenticator
 /// 
 /// ## How does it look in the human way
 /// 
@@ -15,29 +18,43 @@ use tokio::sync::Mutex;
 /// #[async_trait]
 /// impl Middleware for DefaultMiddleware {
 ///     async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
-///         let state = state.lock().await;
-///         let name = &req.value;
+///         let app = state.lock().await.app.clone();
+///         let authenticator = app.lock().await.authenticator.clone();
+///         let password = req.custom.get("Password").map(String::as_str);
 /// 
-///         // Idk why it wanst me 
-///         let _ = match state.app.lock().await.register(name.clone()) { // Crazy amount of things need to be done, todo! change it 
+///         match authenticator.bind(&app, &req.value, password).await {
 ///             Ok(token) => {
-///                 return Ok(ResponseBuilder::new()
-///                     .version(Version::CHAT10)
+///                 Ok(ResponseBuilder::new()
+///                     .version(req.version)
 ///                     .code(ResponseCode::AuthOK)
 ///                     .token(token)
 ///                     .build()
-///                     .unwrap()
-///                 );
+///                     .unwrap())
 ///             },
-///             Err(_) => {
-///                 return Err(ResponseBuilder::new()
-///                     .version(Version::CHAT10)
+///             Err(AuthError::Taken) => {
+///                 Err(ResponseBuilder::new()
+///                     .version(req.version)
 ///                     .code(ResponseCode::AlreadyTaken)
 ///                     .build()
-///                     .unwrap()
-///                 );
-///             }
-///         };
+///                     .unwrap())
+///             },
+///             Err(e @ AuthError::InvalidName(_)) => {
+///                 Err(ResponseBuilder::new()
+///                     .version(req.version)
+///                     .code(ResponseCode::InvalidName)
+///                     .reason(e.to_string())
+///                     .build()
+///                     .unwrap())
+///             },
+///             Err(e) => {
+///                 Err(ResponseBuilder::new()
+///                     .version(req.version)
+///                     .code(ResponseCode::Unauthorized)
+///                     .reason(e.to_string())
+///                     .build()
+///                     .unwrap())
+///             },
+///         }
 ///     }
 /// }
 /// ```
@@ -47,28 +64,43 @@ pub struct DefaultMiddleware;
 #[async_trait]
 impl Middleware for DefaultMiddleware {
     async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
-        let state = state.lock().await;
-        let name = &req.value;
+        // Nothing is locked while the password is checked, it is slow
+        let app = state.lock().await.app.clone();
+        let authenticator = app.lock().await.authenticator.clone();
+        let password = req.custom.get("Password").map(String::as_str);
 
-        // Idk why it wanst me 
-        let _ = match state.app.lock().await.register(name.clone()) { // Crazy amount of things need to be done, todo! change it 
+        match authenticator.bind(&app, &req.value, password).await {
             Ok(token) => {
-                return Ok(ResponseBuilder::new()
-                    .version(Version::CHAT10)
+                Ok(ResponseBuilder::new()
+                    .version(req.version)
                     .code(ResponseCode::AuthOK)
                     .token(token)
                     .build()
-                    .unwrap()
-                );
+                    .unwrap())
             },
-            Err(_) => {
-                return Err(ResponseBuilder::new()
-                    .version(Version::CHAT10)
+            Err(AuthError::Taken) => {
+                Err(ResponseBuilder::new()
+                    .version(req.version)
                     .code(ResponseCode::AlreadyTaken)
                     .build()
-                    .unwrap()
-                );
-            }
-        };
+                    .unwrap())
+            },
+            Err(e @ AuthError::InvalidName(_)) => {
+                Err(ResponseBuilder::new()
+                    .version(req.version)
+                    .code(ResponseCode::InvalidName)
+                    .reason(e.to_string())
+                    .build()
+                    .unwrap())
+            },
+            Err(e) => {
+                Err(ResponseBuilder::new()
+                    .version(req.version)
+                    .code(ResponseCode::Unauthorized)
+                    .reason(e.to_string())
+                    .build()
+                    .unwrap())
+            },
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,45 +1,49 @@
 use async_trait::async_trait;
-use crate::{protocol::{wares::Middleware, request::{Request, Version}, response::{Response, ResponseCode, ResponseBuilder}}, router::State};
+use crate::{protocol::{wares::Middleware, request::Request, response::{Response, ResponseCode, ResponseBuilder}}, router::State};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 /// ## `DefaultMiddleware`
 /// 
-/// This is the default implementator for [`Middleware`] trait for `Handshake` [`Method`]
+/// This is the default implementator for [`Middleware`] trait for `Handshake` [`Method`].
+/// Token is checked by the [`Authenticator`] of the router.
 /// 
 /// ## How does it look in the human way
 /// ```
 /// #[async_trait]
 /// impl Middleware for DefaultMiddleware {
 ///     async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
-///         let mut state = state.lock().await;
-///         let token = &req.value;
-///         if let Some(name) = state.clone().app.lock().await.auth.get(token) { // Clonning whole state.custom, locking, awaiting is crazy, need to change that for sure
-///             state.varmap.insert(name.clone());
+///         let app = state.lock().await.app.clone();
+///         let authenticator = app.lock().await.authenticator.clone();
 /// 
-///             return Ok(ResponseBuilder::new().version(Version::CHAT10).code(ResponseCode::AuthOK).build().unwrap());
+///         if let Some(name) = authenticator.verify(&app, &req.value).await {
+///             state.lock().await.varmap.insert(name);
+/// 
+///             return Ok(ResponseBuilder::new().version(req.version).code(ResponseCode::AuthOK).build().unwrap());
 ///         } 
 /// 
-///         Err(ResponseBuilder::new().version(Version::CHAT10).code(ResponseCode::Unauthorized).build().unwrap())
+///         Err(ResponseBuilder::new().version(req.version).code(ResponseCode::Unauthorized).build().unwrap())
 ///     }
 /// }
 /// ```
 /// 
 /// [`Method`]: crate::protocol::request::Method
+/// [`Authenticator`]: crate::router::Authenticator
 #[derive(Debug)]
 pub struct DefaultMiddleware;
 
 #[async_trait]
 impl Middleware for DefaultMiddleware {
     async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
-        let mut state = state.lock().await;
-        let token = &req.value;
-        if let Some(name) = state.clone().app.lock().await.auth.get(token) { // Clonning whole state.custom, locking, awaiting is crazy, need to change that for sure
-            state.varmap.insert(name.clone());
+        let app = state.lock().await.app.clone();
+        let authenticator = app.lock().await.authenticator.clone();
+
+        if let Some(name) = authenticator.verify(&app, &req.value).await {
+            state.lock().await.varmap.insert(name);
 
-            return Ok(ResponseBuilder::new().version(Version::CHAT10).code(ResponseCode::AuthOK).build().unwrap());
+            return Ok(ResponseBuilder::new().version(req.version).code(ResponseCode::AuthOK).build().unwrap());
         } 
 
-        Err(ResponseBuilder::new().version(Version::CHAT10).code(ResponseCode::Unauthorized).build().unwrap())
+        Err(ResponseBuilder::new().version(req.version).code(ResponseCode::Unauthorized).build().unwrap())
     }
 }
\ No newline at end of file
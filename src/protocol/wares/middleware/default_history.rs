@@ -0,0 +1,132 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use crate::{protocol::{wares::Middleware, request::Request, response::{Response, ResponseCode, ResponseBuilder}}, router::{HistoryPage, HistoryQuery, State, MAX_HISTORY_LIMIT}};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// ## `DefaultMiddleware`
+///
+/// This is the default implementator for [`Middleware`] trait for `History` [`Method`].
+/// Up to `<Limit@...>` messages older than `<Before@...>` id (or the newest ones) are taken from the `MessageStore`,
+/// of the `<Room@...>` if there is one (only for the ones in the room). They are left in the state as [`HistoryPage`],
+/// router writes them first, and then the answer with `<End@'History'>` and `<Count@...>`.
+///
+/// After the `Handshake` user is known, before it `<Authorization@...>` is checked the same way as for the `Handshake`.
+///
+/// ## How does it look in the human way
+/// ```
+/// #[async_trait]
+/// impl Middleware for DefaultMiddleware {
+///     async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+///         let name = user(&req, &state).await?;
+///         let query = HistoryQuery { room, before, ..HistoryQuery::new(limit) };
+///
+///         let messages = store.query(&query).await?;
+///         state.lock().await.varmap.insert(HistoryPage(messages));
+///
+///         Ok(ResponseBuilder::new()
+///             .version(req.version)
+///             .code(ResponseCode::OK)
+///             .custom_insert("End".to_string(), "History".to_string())
+///             .custom_insert("Count".to_string(), count.to_string())
+///             .build()
+///             .unwrap())
+///     }
+/// }
+/// ```
+///
+/// [`Method`]: crate::protocol::request::Method
+#[derive(Debug)]
+pub struct DefaultMiddleware;
+
+#[async_trait]
+impl Middleware for DefaultMiddleware {
+    async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+        let error = |code: ResponseCode, reason: String| {
+            ResponseBuilder::new()
+                .version(req.version)
+                .code(code)
+                .reason(reason)
+                .build()
+                .unwrap()
+        };
+
+        let app = state.lock().await.app.clone();
+        let (store, authenticator) = {
+            let app = app.lock().await;
+            (app.message_store.clone(), app.authenticator.clone())
+        };
+
+        let Some(store) = store else {
+            return Err(error(ResponseCode::Error, "history is not kept".to_string()));
+        };
+
+        // After the Handshake name is known, before it token is needed
+        let name = state.lock().await.varmap.get::<String>().cloned();
+        let name = match (name, req.custom.get("Authorization")) {
+            (Some(name), _) => name,
+            (None, Some(token)) => match authenticator.verify(&app, token).await {
+                Some(name) => name,
+                None => return Err(error(ResponseCode::Unauthorized, "invalid token".to_string())),
+            },
+            (None, None) => return Err(error(ResponseCode::Unauthorized, "`Authorization` is needed before the Handshake".to_string())),
+        };
+
+        let Ok(limit) = req.value.parse::<usize>() else {
+            return Err(error(ResponseCode::InvalidHeader, "`Limit` is not a number".to_string()));
+        };
+
+        let before = match req.custom.get("Before").map(|val| val.parse::<u64>()) {
+            Some(Ok(val)) => Some(val),
+            Some(Err(_)) => return Err(error(ResponseCode::InvalidHeader, "`Before` is not a message id".to_string())),
+            None => None,
+        };
+
+        // Room history is only for the ones in the room
+        let room = req.custom.get("Room").cloned();
+        if let Some(room) = &room {
+            if !app.lock().await.rooms.get(room).is_some_and(|val| val.members.contains_key(&name)) {
+                return Err(error(ResponseCode::Error, format!("not in the room `{room}`")));
+            }
+        }
+
+        let query = HistoryQuery {
+            room: room.clone(),
+            before,
+            ..HistoryQuery::new(limit.min(MAX_HISTORY_LIMIT))
+        };
+
+        let messages = match store.query(&query).await {
+            Ok(val) => val,
+            Err(e) => {
+                warn!(error = %e, "Failed to read the history");
+
+                return Err(error(ResponseCode::Error, "history couldnt be read".to_string()));
+            }
+        };
+
+        // Messages are in the version of the user, same as the live ones
+        let page: Vec<Bytes> = messages.iter()
+            .filter_map(|val| val.response())
+            .filter_map(|mut res| {
+                res.version = req.version;
+                res.as_bytes().ok()
+            })
+            .collect();
+
+        let mut response = ResponseBuilder::new()
+            .version(req.version)
+            .code(ResponseCode::OK)
+            .custom_insert("End".to_string(), "History".to_string())
+            .custom_insert("Count".to_string(), page.len().to_string());
+
+        if let Some(room) = room {
+            response = response.custom_insert("Room".to_string(), room);
+        }
+
+        state.lock().await.varmap.insert(HistoryPage(page));
+
+        Ok(response.build().unwrap())
+    }
+}
@@ -0,0 +1,74 @@
+use async_trait::async_trait;
+use crate::{protocol::{wares::Middleware, request::Request, response::{Response, ResponseCode, ResponseBuilder}}, router::{RoomError, State}};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// ## `DefaultMiddleware`
+/// 
+/// This is the default implementator for [`Middleware`] trait for `Join` [`Method`].
+/// User joins the room from `<Room@...>`, and from now on gets every message sent there.
+/// 
+/// ## How does it look in the human way
+/// ```
+/// #[async_trait]
+/// impl Middleware for DefaultMiddleware {
+///     async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+///         let mut state = state.lock().await;
+/// 
+///         match state.join(&req.value).await {
+///             Ok(()) => {
+///                 Ok(ResponseBuilder::new()
+///                     .version(req.version)
+///                     .code(ResponseCode::OK)
+///                     .custom_insert("Room".to_string(), req.value)
+///                     .build()
+///                     .unwrap())
+///             },
+///             Err(RoomError::NotHandshaked) => {
+///                 Err(ResponseBuilder::new().version(req.version).code(ResponseCode::Unauthorized).build().unwrap())
+///             },
+///             Err(RoomError::InvalidRoom) => {
+///                 Err(ResponseBuilder::new()
+///                     .version(req.version)
+///                     .code(ResponseCode::InvalidHeader)
+///                     .reason("room name is empty".to_string())
+///                     .build()
+///                     .unwrap())
+///             }
+///         }
+///     }
+/// }
+/// ```
+/// 
+/// [`Method`]: crate::protocol::request::Method
+#[derive(Debug)]
+pub struct DefaultMiddleware;
+
+#[async_trait]
+impl Middleware for DefaultMiddleware {
+    async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+        let mut state = state.lock().await;
+
+        match state.join(&req.value).await {
+            Ok(()) => {
+                Ok(ResponseBuilder::new()
+                    .version(req.version)
+                    .code(ResponseCode::OK)
+                    .custom_insert("Room".to_string(), req.value)
+                    .build()
+                    .unwrap())
+            },
+            Err(RoomError::NotHandshaked) => {
+                Err(ResponseBuilder::new().version(req.version).code(ResponseCode::Unauthorized).build().unwrap())
+            },
+            Err(RoomError::InvalidRoom) => {
+                Err(ResponseBuilder::new()
+                    .version(req.version)
+                    .code(ResponseCode::InvalidHeader)
+                    .reason("room name is empty".to_string())
+                    .build()
+                    .unwrap())
+            }
+        }
+    }
+}
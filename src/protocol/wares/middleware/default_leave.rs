@@ -0,0 +1,62 @@
+use async_trait::async_trait;
+use crate::{protocol::{wares::Middleware, request::Request, response::{Response, ResponseCode, ResponseBuilder}}, router::State};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// ## `DefaultMiddleware`
+/// 
+/// This is the default implementator for [`Middleware`] trait for `Leave` [`Method`].
+/// Room is dropped, when the last user leaves it.
+/// 
+/// ## How does it look in the human way
+/// ```
+/// #[async_trait]
+/// impl Middleware for DefaultMiddleware {
+///     async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+///         let mut state = state.lock().await;
+/// 
+///         if state.leave(&req.value).await {
+///             return Ok(ResponseBuilder::new()
+///                 .version(req.version)
+///                 .code(ResponseCode::OK)
+///                 .custom_insert("Room".to_string(), req.value)
+///                 .build()
+///                 .unwrap());
+///         }
+/// 
+///         Err(ResponseBuilder::new()
+///             .version(req.version)
+///             .code(ResponseCode::Error)
+///             .reason(format!("not in the room `{0}`", req.value))
+///             .build()
+///             .unwrap())
+///     }
+/// }
+/// ```
+/// 
+/// [`Method`]: crate::protocol::request::Method
+#[derive(Debug)]
+pub struct DefaultMiddleware;
+
+#[async_trait]
+impl Middleware for DefaultMiddleware {
+    async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+        let mut state = state.lock().await;
+
+        if state.leave(&req.value).await {
+            return Ok(ResponseBuilder::new()
+                .version(req.version)
+                .code(ResponseCode::OK)
+                .custom_insert("Room".to_string(), req.value)
+                .build()
+                .unwrap());
+        }
+
+        Err(ResponseBuilder::new()
+            .version(req.version)
+            .code(ResponseCode::Error)
+            .reason(format!("not in the room `{0}`", req.value))
+            .build()
+            .unwrap())
+    }
+}
@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use crate::{protocol::{wares::Middleware, request::{Request, Version}, response::{Response, ResponseCode, ResponseBuilder}}, router::State};
+use crate::{protocol::{wares::Middleware, request::Request, response::{Response, ResponseCode, ResponseBuilder}}, router::State};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use chrono::Utc;
@@ -18,12 +18,21 @@ use chrono::Utc;
 ///             let dt = Utc::now();
 ///             let naive_utc = dt.naive_utc().format("%Y-%m-%d %H:%M:%S").to_string();
 ///             
-///             let response = ResponseBuilder::new()
-///                 .version(Version::CHAT10)
+///             let mut response = ResponseBuilder::new()
+///                 .version(req.version)
 ///                 .code(ResponseCode::OK)
 ///                 .custom_init()
 ///                 .custom_insert("Time".to_string(), naive_utc)
-///                 .user(name.clone())
+///                 .user(name.clone());
+///             
+///             // Messages to the room and direct messages say where they were sent
+///             for key in ["Room", "To"] {
+///                 if let Some(val) = req.custom.get(key) {
+///                     response = response.custom_insert(key.to_string(), val.clone());
+///                 }
+///             }
+///             
+///             let response = response
 ///                 .message(req.value)
 ///                 .build();
 ///             
@@ -33,7 +42,7 @@ use chrono::Utc;
 ///                 },
 ///                 Err(_) => {
 ///                     let response = ResponseBuilder::new()
-///                         .version(Version::CHAT10)
+///                         .version(req.version)
 ///                         .code(ResponseCode::ParseError)
 ///                         .build()
 ///                         .unwrap();
@@ -44,7 +53,7 @@ use chrono::Utc;
 ///         }
 /// 
 ///         return Err(ResponseBuilder::new()
-///             .version(Version::CHAT10)
+///             .version(req.version)
 ///             .code(ResponseCode::InvalidName)
 ///             .build()
 ///             .unwrap());
@@ -64,12 +73,21 @@ impl Middleware for DefaultMiddleware {
             let dt = Utc::now();
             let naive_utc = dt.naive_utc().format("%Y-%m-%d %H:%M:%S").to_string();
             
-            let response = ResponseBuilder::new()
-                .version(Version::CHAT10)
+            let mut response = ResponseBuilder::new()
+                .version(req.version)
                 .code(ResponseCode::OK)
                 .custom_init()
                 .custom_insert("Time".to_string(), naive_utc)
-                .user(name.clone())
+                .user(name.clone());
+            
+            // Messages to the room and direct messages say where they were sent
+            for key in ["Room", "To"] {
+                if let Some(val) = req.custom.get(key) {
+                    response = response.custom_insert(key.to_string(), val.clone());
+                }
+            }
+            
+            let response = response
                 .message(req.value)
                 .build();
             
@@ -79,7 +97,7 @@ impl Middleware for DefaultMiddleware {
                 },
                 Err(_) => {
                     let response = ResponseBuilder::new()
-                        .version(Version::CHAT10)
+                        .version(req.version)
                         .code(ResponseCode::ParseError)
                         .build()
                         .unwrap();
@@ -90,7 +108,7 @@ impl Middleware for DefaultMiddleware {
         }
 
         return Err(ResponseBuilder::new()
-            .version(Version::CHAT10)
+            .version(req.version)
             .code(ResponseCode::InvalidName)
             .build()
             .unwrap());
@@ -0,0 +1,56 @@
+use async_trait::async_trait;
+use crate::{protocol::{wares::Middleware, request::Request, response::{Response, ResponseCode, ResponseBuilder}}, router::State};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// ## `DefaultMiddleware`
+/// 
+/// This is the default implementator for [`Middleware`] trait for `Unbind` [`Method`].
+/// Token from `<Authorization@...>` is revoked, so the name is free, and every connection that used it is closed
+/// (the one that sent `Unbind` too, after it gets the answer). It is done by the [`Authenticator`] of the router.
+/// 
+/// ## How does it look in the human way
+/// ```
+/// #[async_trait]
+/// impl Middleware for DefaultMiddleware {
+///     async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+///         let app = state.lock().await.app.clone();
+///         let authenticator = app.lock().await.authenticator.clone();
+/// 
+///         if let Some(name) = authenticator.unbind(&app, &req.value).await {
+///             return Ok(ResponseBuilder::new()
+///                 .version(req.version)
+///                 .code(ResponseCode::OK)
+///                 .user(name)
+///                 .build()
+///                 .unwrap());
+///         }
+/// 
+///         Err(ResponseBuilder::new().version(req.version).code(ResponseCode::Unauthorized).build().unwrap())
+///     }
+/// }
+/// ```
+/// 
+/// [`Method`]: crate::protocol::request::Method
+/// [`Authenticator`]: crate::router::Authenticator
+#[derive(Debug)]
+pub struct DefaultMiddleware;
+
+#[async_trait]
+impl Middleware for DefaultMiddleware {
+    async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+        let app = state.lock().await.app.clone();
+        let authenticator = app.lock().await.authenticator.clone();
+
+        if let Some(name) = authenticator.unbind(&app, &req.value).await {
+            return Ok(ResponseBuilder::new()
+                .version(req.version)
+                .code(ResponseCode::OK)
+                .user(name)
+                .build()
+                .unwrap());
+        }
+
+        Err(ResponseBuilder::new().version(req.version).code(ResponseCode::Unauthorized).build().unwrap())
+    }
+}
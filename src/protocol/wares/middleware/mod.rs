@@ -12,6 +12,10 @@ use crate::router::State;
 pub mod default_bind;
 pub mod default_handshake;
 pub mod default_send;
+pub mod default_join;
+pub mod default_leave;
+pub mod default_unbind;
+pub mod default_history;
 
 /// ## `Middleware`
 /// 
@@ -37,6 +41,14 @@ pub mod default_send;
 /// Implementator for this [`Method`] in the [`Middleware`] execution function is expected to form valid `message` (*not the message, but message
 /// for the clients*) thawt would be sent to all listening clients.
 /// 
+/// ### [`Method`]::Unbind
+/// Implementator for this [`Method`] in the [`Middleware`] execution function is expected to free the name of the `token`
+/// via `state.app.lock().await.revoke(token)`.
+/// 
+/// ### [`Method`]::History
+/// Implementator for this [`Method`] in the [`Middleware`] execution function is expected to put the found messages
+/// into the `state.varmap` as `HistoryPage`, they are written before the returned answer.
+/// 
 /// ### [`Method`]::Handshake
 /// Implementator for this [`Method`] in the [`Middleware`] execution function is expected to check if `token` is valid,
 /// and if it is, add `name` to the `state.varmap::insert(name)`. It is retrievable by `state.varmap::get::<String>()`.
@@ -3,18 +3,25 @@
 //! This module holds traits for [`StartingBytesware`], [`Middleware`], [`EndingBytesware`] and their default implementations.
 //! Because they are async traits, they will look dumb, but look at the examples and everything would be more understandable.
 //! 
-//! Also, it holds [`AfterConnect`] and [`BeforeConnect`]
+//! Also, it holds [`AfterConnect`] and [`BeforeConnect`], [`Layer`]'s for stacking middlewares, and built-in [`rate_limit`]
+//! and [`clickhouse_sink`] wares.
 pub mod starting_bytesware;
 pub mod middleware;
 pub mod ending_bytesware;
 pub mod before_connect;
 pub mod after_connect;
+pub mod layer;
+pub mod rate_limit;
+pub mod clickhouse_sink;
 
 pub use starting_bytesware::StartingBytesware;
 pub use middleware::Middleware;
 pub use ending_bytesware::EndingBytesware;
 pub use before_connect::BeforeConnect;
 pub use after_connect::AfterConnect;
+pub use layer::{Layer, Layered, Next};
+pub use rate_limit::{Limit, RateLimiter, RateLimitBytesware, RateLimitMiddleware};
+pub use clickhouse_sink::{ClickHouseSink, ClickHouseSinkBuilder, SentRow, EventRow};
 
 /// I know thats just the definition, and not a real type, but still easier to carry around.
 pub type Route = (Box<dyn Middleware>, Box<dyn EndingBytesware>);
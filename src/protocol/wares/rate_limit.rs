@@ -0,0 +1,362 @@
+//! ## `rate_limit`
+//!
+//! Built-in token-bucket rate limiting. One spamming client would saturate the broadcast for everyone,
+//! so requests over the limit are answered with `<Code@27>` and `<RetryAfter@ms>`, and never reach the route.
+//!
+//! Limits are kept in the [`RateLimiter`], which is used by [`RateLimitBytesware`] (every request) or
+//! [`RateLimitMiddleware`] (only one route). [`RateLimiter`] is the [`Layer`] too, so it could be stacked
+//! with others via `RouterBuilder::layer`. Same limiter could be used in many places, buckets are shared.
+//!
+//! ## Example
+//! ```
+//! let limiter = RateLimiter::new()
+//!     .per_ip(Limit::new(50, Duration::from_secs(1)))     // Everyone behind the IP together
+//!     .per_user(Limit::new(10, Duration::from_secs(1)))   // Bound user on every connection together
+//!     .method("Send", Limit::new(5, Duration::from_secs(1)))
+//!     .disconnect_after(20);                              // 20 rate limited requests and connection is closed
+//!
+//! let router = RouterBuilder::new()
+//!     .starting_bytesware(Box::new(RateLimitBytesware::new(limiter)))
+//!     .build();
+//! ```
+use async_trait::async_trait;
+use crate::protocol::request::{RawRequest, Request, Version};
+use crate::protocol::response::{Response, ResponseBuilder, ResponseCode};
+use crate::router::{ConnectionId, State};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use super::{Layer, Middleware, Next, StartingBytesware, starting_bytesware::DefaultStartingBytesware};
+
+// Buckets are cleaned up, when there are more of them than this
+const PRUNE_AFTER: usize = 1024;
+
+/// Shortest `per` of the [`Limit`].
+pub const MIN_PER: Duration = Duration::from_millis(1);
+
+/// ## `Limit`
+///
+/// `capacity` requests per `per`. Bucket starts full, so up to `capacity` requests could come at once,
+/// then they are refilled evenly (`Limit::new(10, 1s)` is one request every 100 ms).
+/// `Limit::new` makes `capacity` at least 1 and `per` at least [`MIN_PER`], so the rate is never 0 or infinite.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+    pub capacity: u32,
+    pub per: Duration,
+}
+
+impl Limit {
+    pub fn new(capacity: u32, per: Duration) -> Self {
+        Limit {
+            capacity: capacity.max(1),
+            per: per.max(MIN_PER),
+        }
+    }
+
+    // Tokens per second. Fields are pub, so they are clamped here too
+    fn rate(&self) -> f64 {
+        self.capacity.max(1) as f64 / self.per.max(MIN_PER).as_secs_f64()
+    }
+}
+
+/// What the bucket is counted for. Method ones are for the user, if it is known, and for the IP otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LimitKey {
+    Ip(IpAddr),
+    User(String),
+    Connection(ConnectionId),
+    Method(String, Box<LimitKey>),
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    limit: Limit,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.limit.rate()).min(self.limit.capacity as f64);
+        self.updated = now;
+    }
+
+    // How long until there is a whole token
+    fn wait(&self) -> Duration {
+        if self.tokens >= 1.0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_secs_f64((1.0 - self.tokens) / self.limit.rate())
+    }
+}
+
+// Strikes of the connection and when the last one was, kept in the State varmap
+#[derive(Debug, Clone, Copy)]
+struct Strikes(u32, Instant);
+
+/// ## `RateLimiter`
+///
+/// Limits and their buckets. Cloning it gives the same buckets, so it could be shared between the wares.
+/// Limit that is not set is not checked.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    pub per_ip: Option<Limit>,
+    pub per_user: Option<Limit>,
+    pub per_connection: Option<Limit>,
+    pub per_method: HashMap<String, Limit>,     // Method name -> Limit
+    pub disconnect_after: Option<u32>,          // Rate limited requests, after which the connection is closed
+    buckets: Arc<std::sync::Mutex<HashMap<LimitKey, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit for everyone from the same IP. Transports without the IP (unix sockets, memory) are not limited by it.
+    pub fn per_ip(mut self, limit: Limit) -> Self {
+        self.per_ip = Some(limit);
+        self
+    }
+
+    /// Limit for the user, after the `Handshake`. Every connection of the same name is counted together.
+    pub fn per_user(mut self, limit: Limit) -> Self {
+        self.per_user = Some(limit);
+        self
+    }
+
+    /// Limit for the single connection.
+    pub fn per_connection(mut self, limit: Limit) -> Self {
+        self.per_connection = Some(limit);
+        self
+    }
+
+    /// Limit for the `method` (`Send`, `Join`, custom ones...), counted per user (or per IP before the `Handshake`).
+    pub fn method(mut self, method: &str, limit: Limit) -> Self {
+        self.per_method.insert(method.to_string(), limit);
+        self
+    }
+
+    /// Closes the connection, after it got rate limited `strikes` times. Strikes are forgotten, when connection
+    /// wasnt rate limited for the longest `per` of its limits (every bucket is full again by then).
+    pub fn disconnect_after(mut self, strikes: u32) -> Self {
+        self.disconnect_after = Some(strikes);
+        self
+    }
+
+    /// Takes one token from every bucket of `keys`. If any of them is empty, nothing is taken,
+    /// and how long to wait is returned.
+    pub fn check(&self, keys: Vec<(LimitKey, Limit)>) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let mut wait = Duration::ZERO;
+        for (key, limit) in &keys {
+            let bucket = buckets.entry(key.clone()).or_insert(Bucket {
+                tokens: limit.capacity as f64,
+                updated: now,
+                limit: *limit,
+            });
+            bucket.refill(now);
+            wait = wait.max(bucket.wait());
+        }
+
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+
+        for (key, _) in &keys {
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+
+        // Full buckets are the same as no buckets, so they are dropped
+        if buckets.len() > PRUNE_AFTER {
+            buckets.retain(|_, bucket| {
+                bucket.refill(now);
+                bucket.tokens < bucket.limit.capacity as f64
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Keys and limits for the request from `addr`. `req` is None, if it couldnt be parsed.
+    pub fn keys(&self, addr: &SocketAddr, id: Option<ConnectionId>, name: Option<&String>, req: Option<&Request>) -> Vec<(LimitKey, Limit)> {
+        let mut keys = Vec::new();
+        let ip = Some(addr.ip()).filter(|ip| !ip.is_unspecified());
+
+        if let (Some(limit), Some(ip)) = (self.per_ip, ip) {
+            keys.push((LimitKey::Ip(ip), limit));
+        }
+
+        if let (Some(limit), Some(id)) = (self.per_connection, id) {
+            keys.push((LimitKey::Connection(id), limit));
+        }
+
+        if let (Some(limit), Some(name)) = (self.per_user, name) {
+            keys.push((LimitKey::User(name.clone()), limit));
+        }
+
+        if let Some(req) = req {
+            if let Some(limit) = self.per_method.get(req.method.to_str()) {
+                let owner = match (name, ip, id) {
+                    (Some(name), _, _) => Some(LimitKey::User(name.clone())),
+                    (None, Some(ip), _) => Some(LimitKey::Ip(ip)),
+                    (None, None, Some(id)) => Some(LimitKey::Connection(id)),
+                    _ => None,
+                };
+
+                if let Some(owner) = owner {
+                    keys.push((LimitKey::Method(req.method.to_str().to_string(), Box::new(owner)), *limit));
+                }
+            }
+        }
+
+        keys
+    }
+
+    // Checks the request for the connection of the `state`, and counts the strike if it is over the limit
+    async fn limit(&self, state: &Arc<Mutex<State>>, addr: &SocketAddr, req: Option<&Request>) -> Result<(), Response> {
+        let mut locked = state.lock().await;
+        let keys = self.keys(addr, locked.id, locked.varmap.get::<String>(), req);
+        let window = keys.iter().map(|(_, limit)| limit.per).max().unwrap_or_default();
+
+        let wait = match self.check(keys) {
+            Ok(_) => return Ok(()),
+            Err(wait) => wait,
+        };
+
+        // Old burst doesnt count anymore
+        let now = Instant::now();
+        let strikes = match locked.varmap.get::<Strikes>() {
+            Some(Strikes(count, last)) if now.duration_since(*last) < window => count + 1,
+            _ => 1,
+        };
+        locked.varmap.insert(Strikes(strikes, now));
+
+        warn!(%addr, wait_ms = wait.as_millis() as u64, strikes, "Rate limited");
+
+        if self.disconnect_after.is_some_and(|max| strikes >= max) {
+            warn!(%addr, "Closing connection, too many strikes");
+
+            locked.close();
+        }
+
+        let version = req.map(|req| req.version).unwrap_or(locked.version);
+        Err(rate_limited(version, wait))
+    }
+}
+
+/// `<Code@27>` with `<RetryAfter@ms>`, what rate limited request is answered with.
+pub fn rate_limited(version: Version, wait: Duration) -> Response {
+    // Rounded up, so retrying right after `RetryAfter` always works
+    let ms = wait.as_micros().div_ceil(1000);
+
+    ResponseBuilder::new()
+        .version(version)
+        .code(ResponseCode::RateLimited)
+        .custom_insert("RetryAfter".to_string(), ms.to_string())
+        .build()
+        .unwrap()
+}
+
+/// ## `RateLimitBytesware`
+///
+/// [`StartingBytesware`] that checks every request against the [`RateLimiter`]. Parsing is done by the `inner`
+/// one ([`DefaultStartingBytesware`] by default), requests that couldnt be parsed are counted too.
+///
+/// ## How it looks like in the human way
+/// ```
+/// #[async_trait]
+/// impl StartingBytesware for RateLimitBytesware {
+///     async fn bytesware(&self, state: Arc<Mutex<State>>, raw_req: RawRequest) -> Result<Request, Response> {
+///         let addr = raw_req.addr.clone();
+///         let req = self.inner.bytesware(state.clone(), raw_req).await;
+///
+///         self.limiter.limit(&state, &addr, req.as_ref().ok()).await?;
+///         req
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RateLimitBytesware {
+    pub limiter: RateLimiter,
+    pub inner: Box<dyn StartingBytesware>,
+}
+
+impl RateLimitBytesware {
+    pub fn new(limiter: RateLimiter) -> Self {
+        Self::wrap(limiter, Box::new(DefaultStartingBytesware))
+    }
+
+    /// Same as [`RateLimitBytesware::new`], but with the custom parsing.
+    pub fn wrap(limiter: RateLimiter, inner: Box<dyn StartingBytesware>) -> Self {
+        RateLimitBytesware { limiter, inner }
+    }
+}
+
+#[async_trait]
+impl StartingBytesware for RateLimitBytesware {
+    async fn bytesware(&self, state: Arc<Mutex<State>>, raw_req: RawRequest) -> Result<Request, Response> {
+        let addr = raw_req.addr.clone();
+        let req = self.inner.bytesware(state.clone(), raw_req).await;
+
+        self.limiter.limit(&state, &addr, req.as_ref().ok()).await?;
+        req
+    }
+}
+
+/// ## `RateLimitMiddleware`
+///
+/// [`Middleware`] that checks requests of one route against the [`RateLimiter`], and passes them to the `inner` one.
+///
+/// ## Example
+/// ```
+/// let limiter = RateLimiter::new().per_user(Limit::new(1, Duration::from_secs(2)));
+/// let send = RateLimitMiddleware::new(limiter, Box::new(middleware::default_send::DefaultMiddleware));
+///
+/// let router = RouterBuilder::new()
+///     .send_middleware(Box::new(send))
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct RateLimitMiddleware {
+    pub limiter: RateLimiter,
+    pub inner: Box<dyn Middleware>,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(limiter: RateLimiter, inner: Box<dyn Middleware>) -> Self {
+        RateLimitMiddleware { limiter, inner }
+    }
+}
+
+#[async_trait]
+impl Middleware for RateLimitMiddleware {
+    async fn middleware(&self, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+        let addr = req.addr.clone();
+        self.limiter.limit(&state, &addr, Some(&req)).await?;
+
+        self.inner.middleware(req, state).await
+    }
+}
+
+#[async_trait]
+impl Layer for RateLimiter {
+    async fn call(&self, req: Request, state: Arc<Mutex<State>>, next: Next<'_>) -> Result<Response, Response> {
+        let addr = req.addr.clone();
+        self.limit(&state, &addr, Some(&req)).await?;
+
+        next.run(req, state).await
+    }
+}
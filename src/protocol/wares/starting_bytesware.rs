@@ -3,11 +3,12 @@
 //! This is the trait, which have `bytesware` function. It would be executed on request recieve
 //! in the router. 
 use async_trait::async_trait;
-use crate::protocol::{request::{ParseError, RawRequest, Request, Version}, response::{Response, ResponseBuilder, ResponseCode}};
+use crate::protocol::{parser::peek_version, request::{ParseErrorKind, RawRequest, Request, Version}, response::{Response, ResponseBuilder, ResponseCode}};
 use crate::router::State;
 use std::fmt::Debug;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tracing::warn;
 
 /// ## `StartingByteswqare`
 /// 
@@ -40,28 +41,33 @@ pub trait StartingBytesware: Debug + Send + Sync {
 /// #[async_trait]
 /// impl StartingBytesware for DefaultStartingBytesware {
 ///     async fn bytesware(&self, _: Arc<Mutex<State>>, raw_req: RawRequest) -> Result<Request, Response> {
+///         // Answering in the version client have spoken, if it is known
+///         let version = peek_version(&raw_req.bytes).unwrap_or(Version::CHAT10);
 ///         let request = Request::from_raw_request(raw_req);
 ///     
 ///         match request {
 ///             Ok(req) => {
 ///                 return Ok(req);
 ///             },
-///             Err(e) => {
+///             Err(e) if e.kind == ParseErrorKind::UnsupportedVersion => {
 ///                 let response = ResponseBuilder::new()
-///                     .version(Version::CHAT10);
+///                     .version(Version::LATEST)
+///                     .code(ResponseCode::UnsupportedVersion)
+///                     .build()
+///                     .unwrap();
 /// 
-///                 // Should've moved it into some sort of function, but i want to give better experience
-///                 let code = match e {
-///                     ParseError::InvalidFormat => ResponseCode::ParseError,
-///                     ParseError::InvalidKey => ResponseCode::Unauthorized,
-///                     ParseError::MissingMethod => ResponseCode::InvalidHeader,
-///                     ParseError::MissingRequestValue => ResponseCode::InvalidName,
-///                     ParseError::MissingVersion => ResponseCode::InvalidHeader,
-///                     ParseError::MissingCode => ResponseCode::Error, // How in the world would you get it here?
-///                     ParseError::NotFound => ResponseCode::Error,
-///                 };
+///                 return Err(response);
+///             },
+///             Err(e) => {
+///                 // Every other parse error is `ParseError`, and `<Reason@...>` tells what exactly was wrong
+///                 let response = ResponseBuilder::new()
+///                     .version(version)
+///                     .code(ResponseCode::ParseError)
+///                     .reason(e.to_string())
+///                     .build()
+///                     .unwrap();
 /// 
-///                 return Err(response.code(code).build().unwrap());
+///                 return Err(response);
 ///             }
 ///         }
 ///     }
@@ -73,28 +79,37 @@ pub struct DefaultStartingBytesware;
 #[async_trait]
 impl StartingBytesware for DefaultStartingBytesware {
     async fn bytesware(&self, _: Arc<Mutex<State>>, raw_req: RawRequest) -> Result<Request, Response> {
+        // Answering in the version client have spoken, if it is known
+        let version = peek_version(&raw_req.bytes).unwrap_or(Version::CHAT10);
         let request = Request::from_raw_request(raw_req);
     
         match request {
             Ok(req) => {
                 return Ok(req);
             },
-            Err(e) => {
+            Err(e) if e.kind == ParseErrorKind::UnsupportedVersion => {
+                warn!(error = %e, "Unsupported version");
+
                 let response = ResponseBuilder::new()
-                    .version(Version::CHAT10);
+                    .version(Version::LATEST)
+                    .code(ResponseCode::UnsupportedVersion)
+                    .build()
+                    .unwrap();
 
-                // Should've moved it into some sort of function, but i want to give better experience
-                let code = match e {
-                    ParseError::InvalidFormat => ResponseCode::ParseError,
-                    ParseError::InvalidKey => ResponseCode::Unauthorized,
-                    ParseError::MissingMethod => ResponseCode::InvalidHeader,
-                    ParseError::MissingRequestValue => ResponseCode::InvalidName,
-                    ParseError::MissingVersion => ResponseCode::InvalidHeader,
-                    ParseError::MissingCode => ResponseCode::Error, // How in the world would you get it here?
-                    ParseError::NotFound => ResponseCode::Error,
-                };
+                return Err(response);
+            },
+            Err(e) => {
+                warn!(error = %e, "Failed to parse request");
+
+                // Every other parse error is `ParseError`, and `<Reason@...>` tells what exactly was wrong
+                let response = ResponseBuilder::new()
+                    .version(version)
+                    .code(ResponseCode::ParseError)
+                    .reason(e.to_string())
+                    .build()
+                    .unwrap();
 
-                return Err(response.code(code).build().unwrap());
+                return Err(response);
             }
         }
     }
@@ -1,6 +1,17 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use bytes::Bytes;
+use tokio::sync::{broadcast, mpsc::UnboundedSender};
+use tokio::time::Instant;
 use uuid::Uuid;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::protocol::response::Response;
 use crate::protocol::Varmap;
+use super::auth::{Authenticator, OpenAuthenticator};
+use super::name_policy::{NamePolicy, NameViolation};
+use super::store::{MessageStore, DEFAULT_REPLAY};
+use super::metrics::RouterMetrics;
 
 /// This things is subfield of the [`State`]. But  `App` is actually app state, and [`State`] is connection state.
 /// I dont know on what i was while writing it.
@@ -13,6 +24,22 @@ use crate::protocol::Varmap;
 /// *`self.extension` field is clone of the Router varmap, in which we were inserting values at the router creation*
 /// If im not mistaking LOL.
 /// 
+/// `rooms` are created on the first [`App::join`] and dropped, when the last member leaves.
+/// `connections` are all open connections by their [`ConnectionId`], so anything could be pushed to them (see [`App::push_to`]).
+/// `online` are the connections of the users after the `Handshake`, by the name, so direct messages could be sent.
+/// `sessions` are the same, but by the connection, so every connection of the name could be found.
+/// 
+/// Tokens from `register` live for `token_ttl` (forever if None, see `RouterBuilder::token_ttl`), `expires` is when.
+/// Expired token cant be used for the `Handshake`, and its name is freed as soon as nobody is online with it.
+/// Name could be freed right away via [`App::unbind`] or [`App::revoke`].
+/// 
+/// Default `Bind`, `Handshake` and `Unbind` middlewares go through the `authenticator` (see [`Authenticator`]),
+/// which is [`OpenAuthenticator`] unless `RouterBuilder::authenticator` was set.
+/// Names are checked against the `name_policy` before they are given (see [`App::check_name`]).
+/// 
+/// If there is the `message_store`, messages to everyone and to the rooms are saved there, and last `replay` of them
+/// are written to the user after the `Handshake` (see `RouterBuilder::message_store`).
+/// 
 /// ## Example
 /// 
 /// ```
@@ -63,7 +90,62 @@ use crate::protocol::Varmap;
 pub struct App {
     pub auth: HashMap<String, String>,
     pub names: HashMap<String, String>,
+    pub rooms: HashMap<String, Room>,
+    pub connections: HashMap<ConnectionId, UnboundedSender<Outbound>>,
+    pub online: HashMap<String, ConnectionId>,
+    pub sessions: HashMap<ConnectionId, String>,
+    pub expires: HashMap<String, Instant>,  // Token -> when it expires. Tokens without TTL are not here.
+    pub room_capacity: usize,               // Capacity of every room broadcast channel. Router sets it to its own capacity.
+    pub token_ttl: Option<Duration>,        // How long new tokens live. Router sets it to its own token_ttl.
+    pub authenticator: Arc<dyn Authenticator>, // Router sets it to its own authenticator.
+    pub name_policy: Option<NamePolicy>,    // Router sets it to its own name_policy. Any name goes if None.
+    pub message_store: Option<Arc<dyn MessageStore>>, // Router sets it to its own message_store. Nothing is saved if None.
+    pub replay: usize,                      // How many messages are replayed after the Handshake. Router sets it to its own replay.
+    pub metrics: Arc<RouterMetrics>,        // Router sets it to its own metrics.
     pub extension: Varmap,
+    next_connection: u64,
+}
+
+/// ## `ConnectionId`
+/// 
+/// Id of the connection, unique for the router run. It is in `State::id` of every connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConnectionId(pub u64);
+
+impl Display for ConnectionId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{0}", self.0)
+    }
+}
+
+/// ## `Outbound`
+/// 
+/// What could be sent to the connection from the outside of it: frame, that is written to the user as it is,
+/// or the order to close the connection.
+#[derive(Debug, Clone)]
+pub enum Outbound {
+    Frame(Bytes),
+    Close,
+}
+
+/// ## `Room`
+/// 
+/// Room has its own broadcast channel, so messages with `<Room@...>` go only to the ones who joined it.
+/// `members` are the names of the users in the room, with how many of their connections joined it
+/// (same user could be in the room from few connections).
+#[derive(Debug, Clone)]
+pub struct Room {
+    pub sender: broadcast::Sender<Bytes>,
+    pub members: HashMap<String, usize>,
+}
+
+/// ## `RoomError`
+/// 
+/// Why user couldnt join the room.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoomError {
+    NotHandshaked,      // There is no name / connection to send messages to yet
+    InvalidRoom,        // Room name is empty
 }
 
 impl App {
@@ -71,18 +153,305 @@ impl App {
         App {
             auth: HashMap::new(),
             names: HashMap::new(),
-            extension
+            rooms: HashMap::new(),
+            connections: HashMap::new(),
+            online: HashMap::new(),
+            sessions: HashMap::new(),
+            expires: HashMap::new(),
+            room_capacity: 32,
+            token_ttl: None,
+            authenticator: Arc::new(OpenAuthenticator),
+            name_policy: None,
+            message_store: None,
+            replay: DEFAULT_REPLAY,
+            metrics: Arc::new(RouterMetrics::new()),
+            extension,
+            next_connection: 0,
         }
     }
 
     /// This function is intended to be used inside of `Bind` `middleware`.
+    /// Name with the expired token is taken again, if nobody is online with it.
     pub fn register(&mut self, name: String) -> Result<String, ()> {
-        if self.names.get(&name) == None {
-            let token = Uuid::new_v4().to_string();
-            self.auth.insert(token.clone(), name.clone());
-            self.names.insert(name, token.clone());
-            return Ok(token);
+        if let Some(token) = self.names.get(&name) {
+            if !self.is_expired(token, Instant::now()) || self.is_online(&name) {
+                return Err(());
+            }
+
+            let token = token.clone();
+            self.unbind(&token);
         }
-        Err(())
+
+        Ok(self.new_token(name))
     } 
-}
\ No newline at end of file
+
+    /// Token for the `name`, that was already checked some other way (password for example), so it is given even if it is taken.
+    /// Valid token of the name is returned as it is, expired one is replaced.
+    /// This function is intended to be used inside of the [`Authenticator`].
+    pub fn issue(&mut self, name: &str) -> String {
+        if let Some(token) = self.names.get(name) {
+            if !self.is_expired(token, Instant::now()) {
+                return token.clone();
+            }
+
+            let token = token.clone();
+            self.unbind(&token);
+        }
+
+        self.new_token(name.to_string())
+    }
+
+    fn new_token(&mut self, name: String) -> String {
+        let token = Uuid::new_v4().to_string();
+        self.auth.insert(token.clone(), name.clone());
+        self.names.insert(name, token.clone());
+
+        if let Some(ttl) = self.token_ttl {
+            self.expires.insert(token.clone(), Instant::now() + ttl);
+        }
+
+        token
+    }
+
+    /// Checks the `name` against the `name_policy`, and the names that are already bound.
+    /// This function is intended to be used inside of the [`Authenticator`], before the name is given.
+    /// 
+    /// ## Example
+    /// ```
+    /// app.name_policy = Some(NamePolicy::new());
+    /// app.register("Jeff".to_string())?;
+    /// 
+    /// assert_eq!(app.check_name("jеff"), Err(NameViolation::LooksLike("Jeff".to_string())));
+    /// ```
+    pub fn check_name(&self, name: &str) -> Result<(), NameViolation> {
+        match &self.name_policy {
+            Some(policy) => policy.check(name, self.names.keys()),
+            None => Ok(()),
+        }
+    }
+
+    /// Name of the `token`, if it could be used for the `Handshake` (it is known and not expired).
+    /// This function is intended to be used inside of `Handshake` `middleware`.
+    pub fn authorize(&self, token: &str) -> Option<&String> {
+        if self.is_expired(token, Instant::now()) {
+            return None;
+        }
+
+        self.auth.get(token)
+    }
+
+    /// Forgets the `token` and frees its name. Connections that are using it stay, see [`App::revoke`] for that.
+    /// Returns the name, or None if there was no such token.
+    pub fn unbind(&mut self, token: &str) -> Option<String> {
+        let name = self.auth.remove(token)?;
+
+        self.names.remove(&name);
+        self.expires.remove(token);
+        Some(name)
+    }
+
+    /// Same as [`App::unbind`], but every connection that did the `Handshake` with the `token` is closed too.
+    /// Returns false, if there was no such token.
+    /// 
+    /// ## Example
+    /// ```
+    /// // Token was leaked, kicking everyone who uses it
+    /// state.lock().await.app.lock().await.revoke(&token);
+    /// ```
+    pub fn revoke(&mut self, token: &str) -> bool {
+        let Some(name) = self.unbind(token) else {
+            return false;
+        };
+
+        for (id, _) in self.sessions.iter().filter(|(_, val)| **val == name) {
+            self.close(*id);
+        }
+
+        true
+    }
+
+    /// Frees names of the expired tokens, that nobody is online with. Returns freed names.
+    /// Router does it in the background, when `token_ttl` is set.
+    pub fn sweep(&mut self) -> Vec<String> {
+        let now = Instant::now();
+        let expired: Vec<String> = self.expires.iter()
+            .filter(|(token, at)| **at <= now && self.auth.get(*token).is_some_and(|name| !self.is_online(name)))
+            .map(|(token, _)| token.clone())
+            .collect();
+
+        expired.iter().filter_map(|token| self.unbind(token)).collect()
+    }
+
+    fn is_expired(&self, token: &str, now: Instant) -> bool {
+        self.expires.get(token).is_some_and(|at| *at <= now)
+    }
+
+    // Online on any connection, not only on the latest one
+    fn is_online(&self, name: &str) -> bool {
+        self.sessions.values().any(|val| val == name)
+    }
+
+    /// Adds `name` to the `room`, room is created if there is no such room yet.
+    /// Returned reciever gets every message published to the room. Call it once per connection,
+    /// every join needs its own `leave`.
+    /// 
+    /// Most of the time you want `State::join`, which also delivers them to the user.
+    pub fn join(&mut self, room: &str, name: &str) -> broadcast::Receiver<Bytes> {
+        let capacity = self.room_capacity;
+        let room = self.rooms.entry(room.to_string()).or_insert_with(|| Room {
+            sender: broadcast::channel(capacity).0,
+            members: HashMap::new(),
+        });
+
+        *room.members.entry(name.to_string()).or_insert(0) += 1;
+        room.sender.subscribe()
+    }
+
+    /// Removes one connection of the `name` from the `room`, `name` is not a member anymore, when all of them left.
+    /// Room is dropped if it is empty after that. Returns false, if `name` wasnt in the room.
+    pub fn leave(&mut self, room: &str, name: &str) -> bool {
+        let Some(val) = self.rooms.get_mut(room) else {
+            return false;
+        };
+
+        let Some(count) = val.members.get_mut(name) else {
+            return false;
+        };
+
+        *count -= 1;
+        if *count == 0 {
+            val.members.remove(name);
+        }
+
+        if val.members.is_empty() {
+            self.rooms.remove(room);
+        }
+
+        true
+    }
+
+    /// Sends `bytes` to everyone in the `room`. Returns false, if there is no such room, or nobody is listening.
+    pub fn publish(&self, room: &str, bytes: Bytes) -> bool {
+        match self.rooms.get(room) {
+            Some(val) => val.sender.send(bytes).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Names of all rooms, sorted.
+    /// 
+    /// ## Example
+    /// ```
+    /// // Somewhere inside of the Middleware
+    /// let rooms = state.lock().await.app.lock().await.rooms();
+    /// ```
+    pub fn rooms(&self) -> Vec<String> {
+        let mut rooms: Vec<String> = self.rooms.keys().cloned().collect();
+        rooms.sort();
+        rooms
+    }
+
+    /// Names of the users in the `room`, sorted. `None` if there is no such room.
+    pub fn members(&self, room: &str) -> Option<Vec<String>> {
+        let mut members: Vec<String> = self.rooms.get(room)?.members.keys().cloned().collect();
+        members.sort();
+        Some(members)
+    }
+
+    /// Registers new connection, everything sent to `outbound` is written to it.
+    /// Router does it on every accepted connection (see `State::attach`).
+    pub fn add_connection(&mut self, outbound: UnboundedSender<Outbound>) -> ConnectionId {
+        self.next_connection += 1;
+        let id = ConnectionId(self.next_connection);
+
+        self.connections.insert(id, outbound);
+        id
+    }
+
+    /// Removes connection, user is not `online` with it anymore. If the user has other connections,
+    /// the newest of them is the one [`App::send_to`] reaches now.
+    pub fn remove_connection(&mut self, id: ConnectionId) {
+        self.connections.remove(&id);
+
+        let Some(name) = self.sessions.remove(&id) else {
+            return;
+        };
+
+        if self.online.get(&name) == Some(&id) {
+            let newest = self.sessions.iter()
+                .filter(|(_, val)| **val == name)
+                .map(|(id, _)| *id)
+                .max();
+
+            match newest {
+                Some(other) => self.online.insert(name, other),
+                None => self.online.remove(&name),
+            };
+        }
+    }
+
+    /// Marks `name` as online on the connection `id`, so [`App::send_to`] could reach it.
+    /// If the user connected one more time, the newest connection wins.
+    pub fn connect(&mut self, name: &str, id: ConnectionId) {
+        self.online.insert(name.to_string(), id);
+        self.sessions.insert(id, name.to_string());
+    }
+
+    /// Sends `response` only to the connection `id`. Returns false, if there is no such connection,
+    /// or response couldnt be turned into bytes.
+    /// 
+    /// ## Example
+    /// ```
+    /// // Somewhere inside of the Middleware, lets kick everyone except ourselves
+    /// let state = state.lock().await;
+    /// let app = state.app.lock().await;
+    /// 
+    /// for id in app.connections.keys().filter(|id| Some(**id) != state.id) {
+    ///     let notice = ResponseBuilder::new()
+    ///         .version(Version::CHAT11)
+    ///         .code(ResponseCode::Error)
+    ///         .reason("kicked".to_string())
+    ///         .build()
+    ///         .unwrap();
+    /// 
+    ///     app.push_to(*id, notice);
+    ///     app.close(*id);
+    /// }
+    /// ```
+    pub fn push_to(&self, id: ConnectionId, response: Response) -> bool {
+        match response.as_bytes() {
+            Ok(bytes) => self.push_bytes_to(id, bytes),
+            Err(_) => false,
+        }
+    }
+
+    /// Same as [`App::push_to`], but for the bytes, that are already prepared (by the `EndingBytesware` for example).
+    pub fn push_bytes_to(&self, id: ConnectionId, bytes: Bytes) -> bool {
+        match self.connections.get(&id) {
+            Some(outbound) => outbound.send(Outbound::Frame(bytes)).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Closes the connection `id`, after everything that was pushed before is written.
+    pub fn close(&self, id: ConnectionId) -> bool {
+        match self.connections.get(&id) {
+            Some(outbound) => outbound.send(Outbound::Close).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Sends `bytes` only to the user `name`. Returns false, if user is not connected.
+    /// 
+    /// ## Example
+    /// ```
+    /// // Somewhere inside of the Middleware
+    /// let delivered = state.lock().await.app.lock().await.send_to("Jeff", response.as_bytes().unwrap());
+    /// ```
+    pub fn send_to(&self, name: &str, bytes: Bytes) -> bool {
+        match self.online.get(name) {
+            Some(id) => self.push_bytes_to(*id, bytes),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,480 @@
+//! ## `Authenticator`
+//!
+//! Who could take which name is decided by the [`Authenticator`]. Default `Bind`, `Handshake` and `Unbind`
+//! middlewares ask it, so the whole auth could be swapped via [`RouterBuilder::authenticator`], without rewriting them.
+//!
+//! There are few of them out of the box:
+//! - [`OpenAuthenticator`] - default one, first come first served, same as it always was.
+//! - [`PasswordFile`] - `name:hash` lines from the file, hashes are made with [`hash_password`].
+//! - [`JsonUserStore`] - same, but users are in the JSON file, and could be added while router is running.
+//! - [`HmacAuthenticator`] - tokens are signed, so they are checked without storing them anywhere.
+//!
+//! Password goes in the `<Password@'...'>` header of the `Bind` request.
+//!
+//! ## Example
+//! ```
+//! // users.txt:
+//! // jeff:pbkdf2-sha256$100000$...$...
+//! let router = RouterBuilder::new()
+//!     .authenticator(Box::new(PasswordFile::load("users.txt")?))
+//!     .build();
+//! ```
+//!
+//! [`RouterBuilder::authenticator`]: crate::router::RouterBuilder::authenticator
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use once_cell::sync::Lazy;
+use ring::{hmac, pbkdf2, rand::{SecureRandom, SystemRandom}};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::io;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+use super::App;
+use super::name_policy::NameViolation;
+
+/// How many PBKDF2 rounds [`hash_password`] does.
+pub const PBKDF2_ITERATIONS: u32 = 100_000;
+
+const HASH_SCHEME: &str = "pbkdf2-sha256";
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+// Checked for the unknown users, so they take as long as the wrong password
+static DUMMY_HASH: Lazy<String> = Lazy::new(|| hash_password(""));
+
+/// ## `Authenticator`
+///
+/// Backend behind `Bind`, `Handshake` and `Unbind`. Every call gets the [`App`], so tokens could be kept there
+/// (as [`OpenAuthenticator`] does), or not kept at all (as [`HmacAuthenticator`] does).
+///
+/// ## How it looks like in the human way
+/// ```
+/// #[async_trait]
+/// pub trait Authenticator: Debug + Send + Sync {
+///     async fn bind(&self, app: &Mutex<App>, name: &str, credentials: Option<&str>) -> Result<String, AuthError>;
+///     async fn verify(&self, app: &Mutex<App>, token: &str) -> Option<String>;
+///     async fn unbind(&self, app: &Mutex<App>, token: &str) -> Option<String> { /* App::revoke */ }
+/// }
+/// ```
+#[async_trait]
+pub trait Authenticator: Debug + Send + Sync {
+    /// Token for the `name`, `credentials` are from the `<Password@...>` header.
+    async fn bind(&self, app: &Mutex<App>, name: &str, credentials: Option<&str>) -> Result<String, AuthError>;
+
+    /// Name of the `token`, if it could be used for the `Handshake`.
+    async fn verify(&self, app: &Mutex<App>, token: &str) -> Option<String>;
+
+    /// Forgets the `token` and closes its connections, returns its name. By default it is [`App::revoke`].
+    async fn unbind(&self, app: &Mutex<App>, token: &str) -> Option<String> {
+        let mut app = app.lock().await;
+
+        // Expired token still unbinds, it is the same name
+        let name = app.auth.get(token).cloned()?;
+        app.revoke(token);
+        Some(name)
+    }
+}
+
+/// ## `AuthError`
+///
+/// Why `Bind` failed. `Taken` is answered with `AlreadyTaken`, `InvalidCredentials` with `Unauthorized`,
+/// `InvalidName` with `InvalidName`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    Taken,                  // Someone else has the name
+    InvalidCredentials,     // No such user, or wrong password
+    InvalidName(NameViolation), // Name is against the `NamePolicy`
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Taken => write!(f, "name is already taken"),
+            AuthError::InvalidCredentials => write!(f, "wrong name or password"),
+            AuthError::InvalidName(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// ## `OpenAuthenticator`
+///
+/// Default [`Authenticator`]. Any free name, that is fine for the `NamePolicy`, could be taken without password, see [`App::register`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenAuthenticator;
+
+#[async_trait]
+impl Authenticator for OpenAuthenticator {
+    async fn bind(&self, app: &Mutex<App>, name: &str, _credentials: Option<&str>) -> Result<String, AuthError> {
+        let mut app = app.lock().await;
+
+        app.check_name(name).map_err(AuthError::InvalidName)?;
+        app.register(name.to_string()).map_err(|_| AuthError::Taken)
+    }
+
+    async fn verify(&self, app: &Mutex<App>, token: &str) -> Option<String> {
+        app.lock().await.authorize(token).cloned()
+    }
+}
+
+/// ## `UserStore`
+///
+/// Somewhere password hashes are kept. [`PasswordFile`] and [`JsonUserStore`] are both the store and the [`Authenticator`],
+/// and could be put under the [`HmacAuthenticator`] too.
+pub trait UserStore: Debug + Send + Sync {
+    /// Hash of the user password, made with [`hash_password`]. None if there is no such user.
+    fn password_hash(&self, name: &str) -> Option<String>;
+}
+
+/// Hashes the `password` with PBKDF2-HMAC-SHA256 and random salt.
+/// Result is `pbkdf2-sha256$<iterations>$<salt>$<hash>`, that is what [`PasswordFile`] and [`JsonUserStore`] keep.
+pub fn hash_password(password: &str) -> String {
+    let mut salt = [0u8; SALT_LEN];
+    SystemRandom::new().fill(&mut salt).expect("system random is not available");
+
+    let mut hash = [0u8; HASH_LEN];
+    let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).unwrap();
+    pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, &salt, password.as_bytes(), &mut hash);
+
+    format!("{HASH_SCHEME}${PBKDF2_ITERATIONS}${0}${1}", STANDARD_NO_PAD.encode(salt), STANDARD_NO_PAD.encode(hash))
+}
+
+/// Checks the `password` against the `hash` from [`hash_password`]. Broken hash never matches.
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    let mut parts = hash.split('$');
+    let (Some(HASH_SCHEME), Some(iterations), Some(salt), Some(hash), None) = (parts.next(), parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return false;
+    };
+
+    let (Some(iterations), Ok(salt), Ok(hash)) = (
+        iterations.parse().ok().and_then(NonZeroU32::new),
+        STANDARD_NO_PAD.decode(salt),
+        STANDARD_NO_PAD.decode(hash),
+    ) else {
+        return false;
+    };
+
+    pbkdf2::verify(pbkdf2::PBKDF2_HMAC_SHA256, iterations, &salt, password.as_bytes(), &hash).is_ok()
+}
+
+// Hashing is slow on purpose, so it is not done on the runtime threads.
+// Unknown name is hashed too, otherwise it answers faster and tells which users exist.
+async fn check_password(store: &dyn UserStore, name: &str, password: Option<&str>) -> Result<(), AuthError> {
+    let Some(password) = password else {
+        return Err(AuthError::InvalidCredentials);
+    };
+
+    let hash = store.password_hash(name);
+    let known = hash.is_some();
+    let hash = hash.unwrap_or_else(|| DUMMY_HASH.clone());
+
+    let password = password.to_string();
+    match tokio::task::spawn_blocking(move || verify_password(&hash, &password)).await {
+        Ok(true) if known => Ok(()),
+        _ => Err(AuthError::InvalidCredentials),
+    }
+}
+
+// Password is the proof, so the name is given even if someone has it
+async fn bind_account(store: &dyn UserStore, app: &Mutex<App>, name: &str, credentials: Option<&str>) -> Result<String, AuthError> {
+    check_password(store, name, credentials).await?;
+
+    let mut app = app.lock().await;
+    app.check_name(name).map_err(AuthError::InvalidName)?;
+    Ok(app.issue(name))
+}
+
+/// ## `PasswordFile`
+///
+/// Users from the text file, one `name:hash` per line. Empty lines and lines starting with `#` are skipped.
+/// File is read once, on `load`.
+///
+/// ## Example
+/// ```
+/// std::fs::write("users.txt", format!("jeff:{0}\n", hash_password("hunter2")))?;
+///
+/// let router = RouterBuilder::new()
+///     .authenticator(Box::new(PasswordFile::load("users.txt")?))
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PasswordFile {
+    pub users: HashMap<String, String>,     // Name -> hash
+}
+
+impl PasswordFile {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut users = HashMap::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // Hash has no `:` in it, name could
+            match line.rsplit_once(':') {
+                Some((name, hash)) if !name.is_empty() && !hash.is_empty() => {
+                    users.insert(name.to_string(), hash.to_string());
+                },
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("line {0}: expected `name:hash`", i + 1))),
+            }
+        }
+
+        Ok(PasswordFile { users })
+    }
+}
+
+impl UserStore for PasswordFile {
+    fn password_hash(&self, name: &str) -> Option<String> {
+        self.users.get(name).cloned()
+    }
+}
+
+#[async_trait]
+impl Authenticator for PasswordFile {
+    async fn bind(&self, app: &Mutex<App>, name: &str, credentials: Option<&str>) -> Result<String, AuthError> {
+        bind_account(self, app, name, credentials).await
+    }
+
+    async fn verify(&self, app: &Mutex<App>, token: &str) -> Option<String> {
+        app.lock().await.authorize(token).cloned()
+    }
+}
+
+/// ## `JsonUserStore`
+///
+/// Users in the JSON file, `{"name": "hash"}`. Unlike [`PasswordFile`], users could be added and removed
+/// while the router is running, file is rewritten every time.
+///
+/// ## Example
+/// ```
+/// let store = Arc::new(JsonUserStore::open("users.json")?);
+/// store.add_user("jeff", "hunter2")?;
+///
+/// let router = RouterBuilder::new()
+///     .authenticator(Box::new(store.clone()))
+///     .build();
+/// ```
+#[derive(Debug, Default)]
+pub struct JsonUserStore {
+    pub path: PathBuf,
+    users: RwLock<HashMap<String, String>>,
+}
+
+impl JsonUserStore {
+    /// Reads the users from `path`. There are no users, if there is no file yet.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let users = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(JsonUserStore {
+            path,
+            users: RwLock::new(users),
+        })
+    }
+
+    /// Adds the user, or changes the password of the existing one, and saves the file.
+    pub fn add_user(&self, name: &str, password: &str) -> io::Result<()> {
+        let hash = hash_password(password);
+        let mut users = self.users.write().unwrap();
+
+        users.insert(name.to_string(), hash);
+        self.save(&users)
+    }
+
+    /// Removes the user and saves the file. Returns false, if there was no such user.
+    /// Tokens that were already given stay, use `App::revoke` for them.
+    pub fn remove_user(&self, name: &str) -> io::Result<bool> {
+        let mut users = self.users.write().unwrap();
+
+        if users.remove(name).is_none() {
+            return Ok(false);
+        }
+
+        self.save(&users).map(|_| true)
+    }
+
+    // Written next to it and renamed, so the file is never half written
+    fn save(&self, users: &HashMap<String, String>) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+
+        std::fs::write(&tmp, serde_json::to_vec_pretty(users)?)?;
+        std::fs::rename(tmp, &self.path)
+    }
+}
+
+impl UserStore for JsonUserStore {
+    fn password_hash(&self, name: &str) -> Option<String> {
+        self.users.read().unwrap().get(name).cloned()
+    }
+}
+
+impl<S: UserStore + ?Sized> UserStore for Arc<S> {
+    fn password_hash(&self, name: &str) -> Option<String> {
+        self.as_ref().password_hash(name)
+    }
+}
+
+#[async_trait]
+impl Authenticator for JsonUserStore {
+    async fn bind(&self, app: &Mutex<App>, name: &str, credentials: Option<&str>) -> Result<String, AuthError> {
+        bind_account(self, app, name, credentials).await
+    }
+
+    async fn verify(&self, app: &Mutex<App>, token: &str) -> Option<String> {
+        app.lock().await.authorize(token).cloned()
+    }
+}
+
+#[async_trait]
+impl<A: Authenticator + ?Sized> Authenticator for Arc<A> {
+    async fn bind(&self, app: &Mutex<App>, name: &str, credentials: Option<&str>) -> Result<String, AuthError> {
+        self.as_ref().bind(app, name, credentials).await
+    }
+
+    async fn verify(&self, app: &Mutex<App>, token: &str) -> Option<String> {
+        self.as_ref().verify(app, token).await
+    }
+
+    async fn unbind(&self, app: &Mutex<App>, token: &str) -> Option<String> {
+        self.as_ref().unbind(app, token).await
+    }
+}
+
+/// How long [`HmacAuthenticator`] tokens live, when the router has no `token_ttl`.
+pub const DEFAULT_HMAC_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// ## `HmacAuthenticator`
+///
+/// Stateless tokens: `<name>.<expires>.<signature>`, signed with HMAC-SHA256 of the `secret`. Tokens are not kept in the [`App`],
+/// so every router with the same secret accepts them. `expires` is unix seconds from the `RouterBuilder::token_ttl`,
+/// or [`DEFAULT_HMAC_TTL`] if it is not set. Token without expiry is never accepted.
+///
+/// Names are not reserved, so `users` are required: password is the proof, same as with [`PasswordFile`].
+/// `Unbind` revokes the token on this router until it expires (other routers with the same secret dont know about it),
+/// and closes connections of its name.
+///
+/// ## Example
+/// ```
+/// let router = RouterBuilder::new()
+///     .authenticator(Box::new(HmacAuthenticator::new(b"long random secret", Box::new(PasswordFile::load("users.txt")?))))
+///     .token_ttl(Duration::from_secs(60 * 60))
+///     .build();
+/// ```
+pub struct HmacAuthenticator {
+    pub users: Box<dyn UserStore>,
+    key: hmac::Key,
+    revoked: std::sync::Mutex<HashMap<String, u64>>,    // Token -> when it expires, they are forgotten after it
+}
+
+impl HmacAuthenticator {
+    pub fn new(secret: &[u8], users: Box<dyn UserStore>) -> Self {
+        HmacAuthenticator {
+            users,
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            revoked: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Signed token for the `name`, that expires at `expires` unix seconds.
+    pub fn sign(&self, name: &str, expires: u64) -> String {
+        let payload = format!("{0}.{expires}", URL_SAFE_NO_PAD.encode(name));
+        let tag = hmac::sign(&self.key, payload.as_bytes());
+
+        format!("{payload}.{0}", URL_SAFE_NO_PAD.encode(tag.as_ref()))
+    }
+
+    /// Name from the token, if the signature is right, it is not expired and not revoked.
+    pub fn check(&self, token: &str) -> Option<String> {
+        let (name, _) = self.parse(token)?;
+
+        if self.revoked.lock().unwrap().contains_key(token) {
+            return None;
+        }
+
+        Some(name)
+    }
+
+    /// Revokes the `token` until it expires. Returns its name, None if it wasnt valid (or is revoked already).
+    pub fn revoke(&self, token: &str) -> Option<String> {
+        let (name, expires) = self.parse(token)?;
+
+        let now = unix_now();
+        let mut revoked = self.revoked.lock().unwrap();
+        revoked.retain(|_, at| *at > now);
+        if revoked.insert(token.to_string(), expires).is_some() {
+            return None;
+        }
+
+        Some(name)
+    }
+
+    // Name and expiry of the token, if the signature is right and it is not expired
+    fn parse(&self, token: &str) -> Option<(String, u64)> {
+        let (payload, tag) = token.rsplit_once('.')?;
+        hmac::verify(&self.key, payload.as_bytes(), &URL_SAFE_NO_PAD.decode(tag).ok()?).ok()?;
+
+        let (name, expires) = payload.split_once('.')?;
+        let expires: u64 = expires.parse().ok()?;
+        if expires <= unix_now() {
+            return None;
+        }
+
+        Some((String::from_utf8(URL_SAFE_NO_PAD.decode(name).ok()?).ok()?, expires))
+    }
+}
+
+impl Debug for HmacAuthenticator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HmacAuthenticator").field("users", &self.users).finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl Authenticator for HmacAuthenticator {
+    async fn bind(&self, app: &Mutex<App>, name: &str, credentials: Option<&str>) -> Result<String, AuthError> {
+        check_password(self.users.as_ref(), name, credentials).await?;
+
+        // Names are not stored, so only the policy rules are checked, not the look-alikes
+        let app = app.lock().await;
+        app.check_name(name).map_err(AuthError::InvalidName)?;
+
+        let ttl = app.token_ttl.unwrap_or(DEFAULT_HMAC_TTL);
+        Ok(self.sign(name, unix_now() + ttl.as_secs().max(1)))
+    }
+
+    async fn verify(&self, _app: &Mutex<App>, token: &str) -> Option<String> {
+        self.check(token)
+    }
+
+    async fn unbind(&self, app: &Mutex<App>, token: &str) -> Option<String> {
+        let name = self.revoke(token)?;
+
+        let app = app.lock().await;
+        for (id, _) in app.sessions.iter().filter(|(_, val)| **val == name) {
+            app.close(*id);
+        }
+
+        Some(name)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,127 @@
+//! ## `BoundRouter`
+//!
+//! [`Router`] that already has its listener. See [`Router::bind`].
+//!
+//! [`Router`]: crate::router::Router
+//! [`Router::bind`]: crate::router::Router::bind
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use super::{Listener, Router, RouterError, RouterHandle, RouterMetrics};
+
+/// ## `BoundRouter`
+///
+/// This struct is the [`Router`] with the listener, that is already bound. Binding is the only thing that
+/// could fail, so running it doesnt return an error. Listener is TCP one, unless other [`Listener`] was given
+/// via [`Router::with_listener`].
+/// 
+/// [`Listener`]: crate::router::Listener
+/// [`Router::with_listener`]: crate::router::Router::with_listener
+///
+/// ## Example
+/// ```
+/// let router = RouterBuilder::new()
+///     .port(0)
+///     .build()
+///     .bind()
+///     .await?;
+///
+/// println!("Running on {0}", router.local_addr());
+/// router.run().await;
+/// ```
+///
+/// [`Router`]: crate::router::Router
+pub struct BoundRouter<L: Listener = TcpListener> {
+    router: Router,
+    listener: L,
+    local_addr: SocketAddr,
+    tls: Option<TlsAcceptor>,   // Made from the router `tls`, so bad certificate is the bind error
+    websocket: Option<TcpListener>,
+    metrics: Option<TcpListener>,
+}
+
+impl<L: Listener> BoundRouter<L> {
+    // `addr` is only for the error, so it is clear what failed
+    pub(crate) fn new(router: Router, listener: L, websocket: Option<TcpListener>, metrics: Option<TcpListener>, addr: String) -> Result<Self, RouterError> {
+        let local_addr = match listener.local_addr() {
+            Ok(val) => val,
+            Err(e) => return Err(RouterError::Bind(addr, e)),
+        };
+
+        let tls = router.tls_acceptor()?;
+
+        Ok(BoundRouter {
+            router,
+            listener,
+            local_addr,
+            tls,
+            websocket,
+            metrics,
+        })
+    }
+
+    /// Address router really listens to (with the real port, if it was 0).
+    /// It is [`UNSPECIFIED_ADDR`] for the transports without the IP address.
+    /// 
+    /// [`UNSPECIFIED_ADDR`]: crate::router::UNSPECIFIED_ADDR
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Address WebSocket clients connect to, if `websocket()` was set.
+    pub fn websocket_addr(&self) -> Option<SocketAddr> {
+        self.websocket.as_ref().and_then(|val| val.local_addr().ok())
+    }
+
+    /// Address metrics are served on, if `metrics()` was set.
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        self.metrics.as_ref().and_then(|val| val.local_addr().ok())
+    }
+
+    /// Returns [`RouterMetrics`] of the router, same as `Router::metrics`.
+    ///
+    /// [`RouterMetrics`]: crate::router::RouterMetrics
+    pub fn metrics(&self) -> Arc<RouterMetrics> {
+        self.router.metrics.clone()
+    }
+
+    /// Returns [`RouterHandle`], same as [`Router::handle`].
+    ///
+    /// [`RouterHandle`]: crate::router::RouterHandle
+    /// [`Router::handle`]: crate::router::Router::handle
+    pub fn handle(&self) -> RouterHandle {
+        self.router.handle()
+    }
+
+    /// Accepting connections, until [`RouterHandle::shutdown`] is called. Same as [`Router::run`].
+    ///
+    /// [`RouterHandle::shutdown`]: crate::router::RouterHandle::shutdown
+    /// [`Router::run`]: crate::router::Router::run
+    pub async fn run(self) {
+        self.run_until(std::future::pending::<()>()).await
+    }
+
+    /// Accepting connections, until `shutdown` is done. Same as [`Router::run_until`].
+    ///
+    /// [`Router::run_until`]: crate::router::Router::run_until
+    pub async fn run_until<F: Future<Output = ()>>(self, shutdown: F) {
+        self.router.serve(self.listener, self.websocket, self.metrics, self.tls, shutdown).await
+    }
+}
+
+impl<L: Listener> Debug for BoundRouter<L> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoundRouter")
+            .field("router", &self.router)
+            .field("listener", &self.listener)
+            .field("local_addr", &self.local_addr)
+            .field("tls", &self.tls.is_some())
+            .field("websocket", &self.websocket)
+            .field("metrics", &self.metrics)
+            .finish()
+    }
+}
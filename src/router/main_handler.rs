@@ -1,16 +1,16 @@
+use bytes::Bytes;
 use tokio::sync::{mpsc::UnboundedReceiver, broadcast::Sender};
+use tracing::trace;
 
 /// Well, this function just transfers messages from senders to listeners... and thats it...
-pub async fn handle_main_thread(main_thread_writer: Sender<[u8; 512]>, mut mp_rx: UnboundedReceiver<[u8; 512]>) {
+pub async fn handle_main_thread(main_thread_writer: Sender<Bytes>, mut mp_rx: UnboundedReceiver<Bytes>) {
     while let Some(message) = mp_rx.recv().await {
         match main_thread_writer.send(message) {
-            Ok(_val) => {
-                #[cfg(feature = "debug_full")]
-                println!("<0> [MAIN] Sent {_val} bytes to broadcast");
+            Ok(val) => {
+                trace!(receivers = val, "Sent message to broadcast");
             },
-            Err(_e) => {
-                #[cfg(feature = "debug_full")]
-                println!(">0< [MAIN] Failed to send message to broadcast with error {_e}");
+            Err(e) => {
+                trace!(error = %e, "Failed to send message to broadcast");
             },
         }
     }
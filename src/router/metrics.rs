@@ -0,0 +1,318 @@
+//! ## `RouterMetrics`
+//!
+//! What the router did since it was built: connections, requests by the method and the `ResponseCode`,
+//! how long middlewares took, how many broadcast messages were missed by the slow users, and bytes in and out.
+//! It is in `Router::metrics`, `App::metrics` and `State::metrics`, so wares could look at it (or count something) too.
+//!
+//! [`RouterMetrics::render`] gives it in the Prometheus text format, `RouterBuilder::metrics(port)` serves it
+//! over HTTP on `/metrics`.
+//!
+//! ## Example
+//! ```
+//! let router = RouterBuilder::new()
+//!     .port(8080)
+//!     .metrics(9100)      // curl http://127.0.0.1:9100/metrics
+//!     .build();
+//!
+//! let metrics = router.metrics.clone();
+//! tokio::spawn(async move { router.run().await });
+//!
+//! println!("{0}", metrics.render());
+//! ```
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{trace, warn};
+
+use crate::protocol::request::Method;
+use crate::protocol::response::ResponseCode;
+use super::Connection;
+
+/// Upper bounds (in seconds) of the middleware latency buckets.
+pub const LATENCY_BUCKETS: [f64; 12] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+/// Path metrics are served on, everything else is `404`.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// How long the metrics endpoint waits for the HTTP request, before closing the connection.
+pub const METRICS_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Method label of the requests without the route. Their names are up to the user, so they are not labels.
+pub const UNROUTED_METHOD: &str = "unknown";
+
+// Longest HTTP request head metrics endpoint reads
+const MAX_HEAD_SIZE: usize = 8 * 1024;
+
+/// ## `RouterMetrics`
+///
+/// Counters of one [`Router`]. Everything is counted from the moment router was built, restarting the router doesnt
+/// reset them. Requests that couldnt be parsed are not counted, and the ones without the route are all under [`UNROUTED_METHOD`].
+///
+/// [`Router`]: crate::router::Router
+#[derive(Debug, Default)]
+pub struct RouterMetrics {
+    pub connections_total: AtomicU64,   // Every accepted connection
+    pub connections_active: AtomicI64,  // Connections that are open right now
+    pub lagged_total: AtomicU64,        // How many times the user was too slow for the broadcast (or the room)
+    pub lagged_messages: AtomicU64,     // How many messages they missed because of it
+    pub bytes_in: AtomicU64,            // Read from the connections (after TLS and WebSocket)
+    pub bytes_out: AtomicU64,           // Written to the connections (after TLS and WebSocket)
+    requests: Mutex<BTreeMap<(String, String), u64>>,   // (Method, ResponseCode) -> count
+    latency: Mutex<BTreeMap<String, Histogram>>,        // Method -> middleware latency
+}
+
+/// ## `Histogram`
+///
+/// Latency of one method. `buckets[i]` is how many took up to `LATENCY_BUCKETS[i]` (not cumulative, `render` sums them).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    pub buckets: [u64; LATENCY_BUCKETS.len()],
+    pub sum: f64,       // In seconds
+    pub count: u64,
+}
+
+impl Histogram {
+    pub fn observe(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|bound| secs <= *bound) {
+            self.buckets[i] += 1;
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+}
+
+impl RouterMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Router calls it, when the connection is accepted.
+    pub fn connected(&self) {
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+        self.connections_active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Router calls it, when the connection is closed.
+    pub fn disconnected(&self) {
+        self.connections_active.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Counts the request answered with the `code`. Method must have the route, otherwise it is [`RouterMetrics::unrouted`].
+    pub fn request(&self, method: &Method, code: &ResponseCode) {
+        let key = (method.to_str().to_string(), code_label(code));
+        *self.requests.lock().unwrap().entry(key).or_insert(0) += 1;
+    }
+
+    /// Counts the request of the method without the route, answered with the `code`.
+    pub fn unrouted(&self, code: &ResponseCode) {
+        let key = (UNROUTED_METHOD.to_string(), code_label(code));
+        *self.requests.lock().unwrap().entry(key).or_insert(0) += 1;
+    }
+
+    /// Records how long the middleware of the `method` took.
+    pub fn observe(&self, method: &Method, elapsed: Duration) {
+        self.latency.lock().unwrap()
+            .entry(method.to_str().to_string())
+            .or_default()
+            .observe(elapsed);
+    }
+
+    /// User was too slow and missed `skipped` messages.
+    pub fn lagged(&self, skipped: u64) {
+        self.lagged_total.fetch_add(1, Ordering::Relaxed);
+        self.lagged_messages.fetch_add(skipped, Ordering::Relaxed);
+    }
+
+    /// How many requests of the `method` were answered with the `code`.
+    pub fn requests(&self, method: &Method, code: &ResponseCode) -> u64 {
+        let key = (method.to_str().to_string(), code_label(code));
+        self.requests.lock().unwrap().get(&key).copied().unwrap_or(0)
+    }
+
+    /// Middleware latency of the `method`, None if it wasnt called yet.
+    pub fn latency(&self, method: &Method) -> Option<Histogram> {
+        self.latency.lock().unwrap().get(method.to_str()).cloned()
+    }
+
+    /// Everything in the Prometheus text format (version 0.0.4).
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let counters = [
+            ("pinguino_connections_total", "counter", "Connections accepted.", self.connections_total.load(Ordering::Relaxed) as i64),
+            ("pinguino_connections_active", "gauge", "Connections open right now.", self.connections_active.load(Ordering::Relaxed)),
+            ("pinguino_broadcast_lagged_total", "counter", "Times a connection fell behind the broadcast.", self.lagged_total.load(Ordering::Relaxed) as i64),
+            ("pinguino_broadcast_lagged_messages_total", "counter", "Messages connections missed by falling behind.", self.lagged_messages.load(Ordering::Relaxed) as i64),
+            ("pinguino_received_bytes_total", "counter", "Bytes read from connections.", self.bytes_in.load(Ordering::Relaxed) as i64),
+            ("pinguino_sent_bytes_total", "counter", "Bytes written to connections.", self.bytes_out.load(Ordering::Relaxed) as i64),
+        ];
+
+        for (name, kind, help, val) in counters {
+            let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {val}");
+        }
+
+        let _ = writeln!(out, "# HELP pinguino_requests_total Requests by method and response code.\n# TYPE pinguino_requests_total counter");
+        for ((method, code), val) in self.requests.lock().unwrap().iter() {
+            let _ = writeln!(out, "pinguino_requests_total{{method=\"{0}\",code=\"{1}\"}} {val}", escape_label(method), escape_label(code));
+        }
+
+        let _ = writeln!(out, "# HELP pinguino_middleware_duration_seconds Middleware latency by method.\n# TYPE pinguino_middleware_duration_seconds histogram");
+        for (method, histogram) in self.latency.lock().unwrap().iter() {
+            let method = escape_label(method);
+            let mut cumulative = 0;
+
+            for (bound, val) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += val;
+                let _ = writeln!(out, "pinguino_middleware_duration_seconds_bucket{{method=\"{method}\",le=\"{bound}\"}} {cumulative}");
+            }
+
+            let _ = writeln!(out, "pinguino_middleware_duration_seconds_bucket{{method=\"{method}\",le=\"+Inf\"}} {0}", histogram.count);
+            let _ = writeln!(out, "pinguino_middleware_duration_seconds_sum{{method=\"{method}\"}} {0}", histogram.sum);
+            let _ = writeln!(out, "pinguino_middleware_duration_seconds_count{{method=\"{method}\"}} {0}", histogram.count);
+        }
+
+        out
+    }
+}
+
+// `OK`, `Unauthorized`, `Custom(42)`...
+fn code_label(code: &ResponseCode) -> String {
+    format!("{code:?}")
+}
+
+// Custom method names could have anything in them
+fn escape_label(val: &str) -> String {
+    val.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// ## `Metered`
+///
+/// [`Connection`] that counts bytes read and written into the [`RouterMetrics`]. Router wraps every connection into it.
+#[derive(Debug)]
+pub struct Metered<C> {
+    pub stream: C,
+    pub metrics: Arc<RouterMetrics>,
+}
+
+impl<C: Connection> Metered<C> {
+    pub fn new(stream: C, metrics: Arc<RouterMetrics>) -> Self {
+        Metered { stream, metrics }
+    }
+}
+
+impl<C: Connection> Connection for Metered<C> {
+    fn keepalive(&self) -> std::io::Result<()> {
+        self.stream.keepalive()
+    }
+}
+
+impl<C: Connection> AsyncRead for Metered<C> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let res = Pin::new(&mut this.stream).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = res {
+            this.metrics.bytes_in.fetch_add((buf.filled().len() - before) as u64, Ordering::Relaxed);
+        }
+        res
+    }
+}
+
+impl<C: Connection> AsyncWrite for Metered<C> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.stream).poll_write(cx, buf);
+
+        if let Poll::Ready(Ok(n)) = res {
+            this.metrics.bytes_out.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// Serves `metrics` over HTTP on the `listener`, until the task is aborted. `GET /metrics` gets them in the
+/// Prometheus text format, anything else gets `404`. Router runs it itself, if `RouterBuilder::metrics(port)` was set,
+/// use it directly with `Router::with_listener`, or to serve them on the listener you bound yourself.
+///
+/// ## Example
+/// ```
+/// let listener = TcpListener::bind("127.0.0.1:9100").await?;
+/// tokio::spawn(serve_metrics(listener, router.metrics.clone()));
+/// ```
+pub async fn serve_metrics(listener: TcpListener, metrics: Arc<RouterMetrics>) {
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(val) => val,
+            Err(e) => {
+                warn!(error = %e, "Failed to accept metrics connection");
+
+                continue;
+            }
+        };
+
+        trace!(addr = %addr, "Metrics requested");
+        tokio::spawn(answer_metrics(stream, metrics.clone()));
+    }
+}
+
+// One HTTP request per connection, it is closed after the answer
+async fn answer_metrics(mut stream: TcpStream, metrics: Arc<RouterMetrics>) {
+    let Ok(Some(path)) = tokio::time::timeout(METRICS_READ_TIMEOUT, read_path(&mut stream)).await else {
+        return;
+    };
+
+    let (status, body) = if path == METRICS_PATH {
+        ("200 OK", metrics.render())
+    } else {
+        ("404 Not Found", "not found\n".to_string())
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {0}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    if let Err(e) = stream.write_all(response.as_bytes()).await {
+        warn!(error = %e, "Failed to write metrics");
+    }
+    let _ = stream.shutdown().await;
+}
+
+// Reads the request head, returns the path of the GET request (query is dropped)
+async fn read_path(stream: &mut TcpStream) -> Option<String> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    while !head.windows(4).any(|val| val == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await.ok()?;
+        if n == 0 || head.len() + n > MAX_HEAD_SIZE {
+            return None;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let mut line = head.lines().next()?.split(' ');
+
+    match (line.next(), line.next()) {
+        (Some("GET"), Some(target)) => Some(target.split('?').next().unwrap_or_default().to_string()),
+        _ => Some(String::new()),
+    }
+}
@@ -11,7 +11,7 @@
 //!     let router: Router = RouterBuilder::new()
 //!         .build();
 //! 
-//!     router.run().await
+//!     router.run().await.unwrap()
 //! }
 //! ```
 //! 
@@ -36,7 +36,7 @@
 //!             .insert(rx_clone)
 //!             .build();                                                   // Returning built router 
 //! 
-//!         router.run().await
+//!         router.run().await.unwrap()
 //!     });
 //! }
 //! ```
@@ -51,25 +51,58 @@
 //! [`Varmap`]: crate::protocol::Varmap
 
 use crate::protocol::wares::Route;
+use crate::protocol::frame::{FrameCodec, DEFAULT_MAX_FRAME_SIZE};
+use crate::protocol::request::{Method, Version};
+use crate::protocol::response::Response;
+use bytes::Bytes;
+use tokio::select;
 use tokio::sync::Mutex;
 use tokio::net::TcpListener;
-use tokio::sync::{mpsc, broadcast};
+use tokio::sync::{mpsc, broadcast, watch};
+use tokio::task::JoinSet;
 use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use crate::protocol::Varmap;
 use crate::protocol::wares::{BeforeConnect, AfterConnect};
 use crate::protocol::wares::{{starting_bytesware, middleware, ending_bytesware}, StartingBytesware, Middleware, EndingBytesware};
+use crate::protocol::wares::{Layer, Layered};
+use tracing::{info, warn};
 
 mod main_handler;
 mod request_handler;
 mod send_handler;
 mod state;
 mod app;
+mod shutdown;
+mod bound;
+mod transport;
+mod tls;
+mod websocket;
+mod auth;
+mod name_policy;
+mod store;
+mod metrics;
 
-use request_handler::handle_wrapper;
+use request_handler::{handle_wrapper, ConnectionContext};
 use main_handler::handle_main_thread;
-pub use app::App;
+pub use app::{App, ConnectionId, Outbound, Room, RoomError};
 pub use state::State;
+pub use shutdown::{RouterHandle, default_goodbye, DEFAULT_SHUTDOWN_TIMEOUT};
+pub use bound::BoundRouter;
+pub use transport::{Both, Connection, Either, Listener, MemoryConnector, MemoryListener, UNSPECIFIED_ADDR};
+pub use tls::{TlsIdentity, TlsListener, TLS_HANDSHAKE_TIMEOUT};
+pub use websocket::{WsConnection, WsListener, WS_HANDSHAKE_TIMEOUT};
+pub use store::{MessageStore, StoredMessage, HistoryQuery, HistoryPage, MemoryStore, FileStore, Since, parse_time, DEFAULT_REPLAY, DEFAULT_STORE_CAPACITY, MAX_HISTORY_LIMIT};
+pub use metrics::{RouterMetrics, Histogram, Metered, serve_metrics, LATENCY_BUCKETS, METRICS_PATH, UNROUTED_METHOD, METRICS_READ_TIMEOUT};
+pub use name_policy::{NamePolicy, NameViolation, default_allowed, skeleton};
+pub use auth::{Authenticator, AuthError, OpenAuthenticator, UserStore, PasswordFile, JsonUserStore, HmacAuthenticator, hash_password, verify_password, PBKDF2_ITERATIONS, DEFAULT_HMAC_TTL};
+pub use tokio_rustls::rustls;
 
 
 /// ## RouterBuilder
@@ -99,7 +132,7 @@ pub use state::State;
 ///             .insert(rx_clone)
 ///             .build();                                                   // Returning built router 
 /// 
-///         router.run().await
+///         router.run().await.unwrap()
 ///     });
 /// }
 /// ```
@@ -109,13 +142,24 @@ pub use state::State;
 #[derive(Debug)]
 pub struct RouterBuilder {  
     pub starting_bytesware: Box<dyn StartingBytesware>,
-    pub send: Route,
-    pub handshake: Route,
-    pub bind: Route,
+    pub routes: HashMap<String, Route>,         // Method name -> Route. `Bind`, `Handshake`, `Send`, `Join`, `Leave`, `Unbind` and `History` are there from the start.
+    pub layers: HashMap<String, Vec<Box<dyn Layer>>>, // Method name -> Layers around its Middleware, outermost first.
     pub before: Option<Box<dyn BeforeConnect>>,
     pub after: Option<Box<dyn AfterConnect>>,
     pub varmap: Varmap,
     pub capacity: Option<usize>,                // Capacity of the broadcast channel. If not set will be equal to the 32.
+    pub max_frame_size: Option<usize>,          // Max size of the incoming frame payload. If not set will be equal to the DEFAULT_MAX_FRAME_SIZE.
+    pub supported_versions: Option<Vec<Version>>, // Versions router accepts. If not set will be equal to the Version::ALL.
+    pub goodbye: Option<Response>,              // Sent to everyone on shutdown. If not set will be equal to the default_goodbye().
+    pub shutdown_timeout: Option<Duration>,     // How long to wait for connections on shutdown. If not set will be equal to the DEFAULT_SHUTDOWN_TIMEOUT.
+    pub tls: Option<TlsIdentity>,               // Certificate and key. If not set connections are plain.
+    pub websocket: Option<u16>,                 // Port for the WebSocket clients. If not set there is only TCP.
+    pub token_ttl: Option<Duration>,            // How long tokens from `Bind` live. If not set they live forever.
+    pub authenticator: Option<Box<dyn Authenticator>>, // Who gets which name. If not set will be equal to the OpenAuthenticator.
+    pub name_policy: Option<NamePolicy>,        // Which names could be taken. If not set any name goes.
+    pub message_store: Option<Box<dyn MessageStore>>, // Where messages are saved. If not set they are not.
+    pub replay: Option<usize>,                  // How many messages are replayed after the Handshake. If not set will be equal to the DEFAULT_REPLAY.
+    pub metrics_port: Option<u16>,              // Port for the Prometheus metrics over HTTP. If not set they are not served.
     pub config: Config,
 }
 
@@ -136,7 +180,7 @@ pub struct RouterBuilder {
 ///     let router: Router = RouterBuilder::new()
 ///         .build();
 /// 
-///     router.run().await
+///     router.run().await.unwrap()
 /// }
 /// ```
 /// 
@@ -158,13 +202,14 @@ pub struct RouterBuilder {
 ///             .insert(rx_clone)
 ///             .build();                                                   // Returning built router 
 /// 
-///         router.run().await
+///         router.run().await.unwrap()
 ///     });
 /// }
 /// ```
 /// 
-/// ## Panic
-/// it **WILL** panic, if it fails to create TcpListener.
+/// ## Errors
+/// `run()` returns [`RouterError`], if it fails to create TcpListener (port is taken for example).
+/// Use [`Router::bind`], if you want to know the address before running, or to use port 0.
 /// 
 /// [`router`]: crate::router
 /// [`Bytesware`]: crate::protocol::wares::StartingBytesware
@@ -177,21 +222,54 @@ pub struct Router {
     pub after: Arc<Option<Box<dyn AfterConnect>>>,
     pub extension: Varmap,
     pub capacity: usize,
+    pub codec: FrameCodec,
     pub config: Config,
+    pub goodbye: Response,
+    pub shutdown_timeout: Duration,
+    pub tls: Option<TlsIdentity>,
+    pub websocket: Option<u16>,
+    pub token_ttl: Option<Duration>,
+    pub authenticator: Arc<dyn Authenticator>,
+    pub name_policy: Option<NamePolicy>,
+    pub message_store: Option<Arc<dyn MessageStore>>,
+    pub replay: usize,
+    pub metrics: Arc<RouterMetrics>,
+    pub metrics_port: Option<u16>,
+    shutdown: Arc<watch::Sender<bool>>,
 }
 
 
 /// ## Routes struct
-/// This struct holds [`Route`]'s and is just the way to not have this 3 fields in Router. Thats it. Nothing fancy. See [`Wares`] for more info <3.
+/// This struct holds [`Route`]'s by the method name, so custom methods (`Typing`, `Edit`, `React`...) are
+/// routed the same way as `Bind`, `Handshake` and `Send`. See [`Wares`] for more info <3.
 /// 
 /// [`Route`]: crate::protocol::wares::Route
 /// [`Wares`]: crate::protocol::wares
 #[derive(Debug)]
 pub struct Routes {
     pub starting_bytesware: Box<dyn StartingBytesware>,
-    pub send: Route,
-    pub handshake: Route,
-    pub bind: Route,
+    pub registry: HashMap<String, Route>,
+    pub supported_versions: Vec<Version>,
+}
+
+impl Routes {
+    /// [`Route`] registered for the `method`, if there is one.
+    /// 
+    /// [`Route`]: crate::protocol::wares::Route
+    pub fn get(&self, method: &Method) -> Option<&Route> {
+        self.registry.get(method.to_str())
+    }
+
+    /// [`EndingBytesware`] for responses, that are not tied to any route (parse errors, too large frames, unknown methods).
+    /// It is the `Send` one, and if it was not registered - default `Send` one.
+    /// 
+    /// [`EndingBytesware`]: crate::protocol::wares::EndingBytesware
+    pub fn fallback(&self) -> &dyn EndingBytesware {
+        match self.get(&Method::Send) {
+            Some(route) => route.1.as_ref(),
+            None => &ending_bytesware::default_send::DefaultEndingBytesware,
+        }
+    }
 }
 
 /// ## RouteRes enum
@@ -204,10 +282,11 @@ pub struct Routes {
 /// [`Method`]: crate::protocol::request::Method
 /// [`EndingBytesware`]: crate::protocol::wares::ending_bytesware
 pub enum RouteRes {
-    Handshake(Result<[u8; 512], [u8; 512]>),
-    Send(Result<[u8; 512], [u8; 512]>),
-    Bind(Result<[u8; 512], [u8; 512]>),
-    None(Result<[u8; 512], [u8; 512]>)          // no identified method
+    Handshake(Result<Bytes, Bytes>),
+    Send(Result<Bytes, Bytes>),
+    Bind(Result<Bytes, Bytes>),
+    Custom(Result<Bytes, Bytes>),       // any of the `RouterBuilder::route()` methods
+    None(Result<Bytes, Bytes>)          // no identified method
 }
 
 /// ## Router Config
@@ -229,22 +308,47 @@ pub struct Config {
     pub port: u16,
 }
 
+/// ## `RouterError`
+/// 
+/// Errors [`Router`] could fail to start with. Once it is running, it doesnt fail.
+/// 
+/// [`Router`]: crate::router::Router
+#[derive(Debug)]
+pub enum RouterError {
+    Bind(String, std::io::Error),   // Address from the Config, and why it couldnt be bound
+    Tls(rustls::Error),             // Certificate / key given to `tls()` couldnt be used
+}
+
+impl Display for RouterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RouterError::Bind(addr, e) => write!(f, "failed to bind to {addr}: {e}"),
+            RouterError::Tls(e) => write!(f, "invalid tls certificate or key: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RouterError {}
+
 // Well, if you are here anyway, and reading my crappy code...
 // This function create listener with given Config. Nothing fancy, works like a charm.
-// Yes, it will panic, and yes, providing .expect() message is not that usefull, but still.
-async fn create_listener(config: &Config) -> TcpListener {
+async fn create_listener(config: &Config) -> Result<TcpListener, RouterError> {
     let addr = format!("{0}:{1}", config.ip, config.port);
-    TcpListener::bind(addr).await.expect("Failed to create listener")
+
+    match TcpListener::bind(&addr).await {
+        Ok(listener) => Ok(listener),
+        Err(e) => Err(RouterError::Bind(addr, e)),
+    }
 }
 
 /// Simple builder pattern.
-/// Fields `send`, `bind`, `handshake`, `config`, `varmap` are not `Option<>` fields,
+/// Fields `routes`, `config`, `varmap` are not `Option<>` fields,
 /// which means that they take their default values at the call of the new() function.
 /// 
 /// Call of setter *(they are called like that, right?)* functions for mentioned fields will replace default values.
 /// Call of setter functions on un mentioned fields will change None to Some(val) 
 impl RouterBuilder {
-    /// Initiates [`RouterBuilder`]. Important to note, that fields `send`, `bind`, `handshake`, `config`, `varmap` are not `Option<>` fields.
+    /// Initiates [`RouterBuilder`]. Important to note, that fields `routes`, `config`, `varmap` are not `Option<>` fields.
     /// Which means that they take their default values at the call of the new() function.
     /// If you want to squize maximum, you could just manually create [`RouterBuilder`] or [`Router`].
     /// 
@@ -273,21 +377,64 @@ impl RouterBuilder {
             Box::new(ending_bytesware::default_bind::DefaultEndingBytesware)
         );
 
+        // Setting up default Join / Leave routes, they share EndingBytesware.
+        let jroute: Route = (
+            Box::new(middleware::default_join::DefaultMiddleware),
+            Box::new(ending_bytesware::default_room::DefaultEndingBytesware)
+        );
+
+        let lroute: Route = (
+            Box::new(middleware::default_leave::DefaultMiddleware),
+            Box::new(ending_bytesware::default_room::DefaultEndingBytesware)
+        );
+
+        // Setting up default Unbind routes, it is answered same as Bind.
+        let uroute: Route = (
+            Box::new(middleware::default_unbind::DefaultMiddleware),
+            Box::new(ending_bytesware::default_bind::DefaultEndingBytesware)
+        );
+
+        // Setting up default History routes, it is answered only to the user, same as rooms.
+        let historyroute: Route = (
+            Box::new(middleware::default_history::DefaultMiddleware),
+            Box::new(ending_bytesware::default_room::DefaultEndingBytesware)
+        );
+
         // Setting up default Config
         let config = Config {
             ip: "127.0.0.1".to_string(),
             port: 8080,
         };
 
+        let mut routes = HashMap::new();
+        routes.insert(Method::Send.to_str().to_string(), sroute);
+        routes.insert(Method::Handshake.to_str().to_string(), hroute);
+        routes.insert(Method::Bind.to_str().to_string(), broute);
+        routes.insert(Method::Join.to_str().to_string(), jroute);
+        routes.insert(Method::Leave.to_str().to_string(), lroute);
+        routes.insert(Method::Unbind.to_str().to_string(), uroute);
+        routes.insert(Method::History.to_str().to_string(), historyroute);
+
         RouterBuilder {
             starting_bytesware,
-            send: sroute,
-            handshake: hroute,
-            bind: broute,
+            routes,
+            layers: HashMap::new(),
             varmap: Varmap::new(),
             capacity: None,
+            max_frame_size: None,
+            supported_versions: None,
             before: None,
             after: None,
+            goodbye: None,
+            shutdown_timeout: None,
+            tls: None,
+            websocket: None,
+            token_ttl: None,
+            authenticator: None,
+            name_policy: None,
+            message_store: None,
+            replay: None,
+            metrics_port: None,
             config
         }
     }
@@ -304,7 +451,7 @@ impl RouterBuilder {
     /// 
     /// [`SendMiddleware`]: crate::protocol::wares::middleware::default_send::DefaultMiddleware
     pub fn send_middleware(mut self, new_val: Box<dyn Middleware>) -> Self {
-        self.send.0 = new_val;
+        self.set_ware(Method::Send, |route| route.0 = new_val);
         self
     }
 
@@ -312,7 +459,7 @@ impl RouterBuilder {
     /// 
     /// [`SendEndingBytesware`]: crate::protocol::wares::ending_bytesware::default_send::DefaultEndingBytesware
     pub fn send_ending_bytesware(mut self, new_val: Box<dyn EndingBytesware>) -> Self {
-        self.send.1 = new_val;
+        self.set_ware(Method::Send, |route| route.1 = new_val);
         self
     }
 
@@ -320,7 +467,7 @@ impl RouterBuilder {
     /// 
     /// [`HandshakeMiddleware`]: crate::protocol::wares::middleware::default_handshake::DefaultMiddleware
     pub fn handshake_middleware(mut self, new_val: Box<dyn Middleware>) -> Self {
-        self.handshake.0 = new_val;
+        self.set_ware(Method::Handshake, |route| route.0 = new_val);
         self
     }
 
@@ -328,7 +475,7 @@ impl RouterBuilder {
     /// 
     /// [`HandshakeEndingBytesware`]: crate::protocol::wares::ending_bytesware::default_handshake::DefaultEndingBytesware
     pub fn handshake_ending_bytesware(mut self, new_val: Box<dyn EndingBytesware>) -> Self {
-        self.handshake.1 = new_val;
+        self.set_ware(Method::Handshake, |route| route.1 = new_val);
         self
     }
 
@@ -336,7 +483,7 @@ impl RouterBuilder {
     /// 
     /// [`BindMiddleware`]: crate::protocol::wares::middleware::default_bind::DefaultMiddleware
     pub fn bind_middleware(mut self, new_val: Box<dyn Middleware>) -> Self {
-        self.bind.0 = new_val;
+        self.set_ware(Method::Bind, |route| route.0 = new_val);
         self
     }
 
@@ -344,10 +491,62 @@ impl RouterBuilder {
     /// 
     /// [`BindEndingBytesware`]: crate::protocol::wares::ending_bytesware::default_bind::DefaultEndingBytesware
     pub fn bind_ending_bytesware(mut self, new_val: Box<dyn EndingBytesware>) -> Self {
-        self.bind.1 = new_val;
+        self.set_ware(Method::Bind, |route| route.1 = new_val);
+        self
+    }
+
+    /// Registering [`Route`] for the custom method, so requests with `<Method@{name}>` would go
+    /// through `middleware` and `ending_bytesware`. Registering `Bind`, `Handshake`, `Send`, `Join` or `Leave` replaces the default route.
+    /// 
+    /// Before the `Handshake` response goes only to the user who sent the request, and connection is closed after it (same as `Bind`).
+    /// After the `Handshake` it works like `Send`: `Ok` is sent to everyone, `Err` only back to the user.
+    /// 
+    /// Custom methods have no value, so every header is in `req.custom`.
+    /// 
+    /// ## Example
+    /// 
+    /// ```
+    /// let router = RouterBuilder::new()
+    ///     .route("Typing", Box::new(TypingMiddleware), Box::new(TypingEndingBytesware))
+    ///     .route("React", Box::new(ReactMiddleware), Box::new(ReactEndingBytesware))
+    ///     .build();
+    /// ```
+    /// 
+    /// [`Route`]: crate::protocol::wares::Route
+    pub fn route(mut self, name: &str, middleware: Box<dyn Middleware>, ending_bytesware: Box<dyn EndingBytesware>) -> Self {
+        self.routes.insert(name.to_string(), (middleware, ending_bytesware));
+        self
+    }
+
+    /// Wrapping the [`Middleware`] of the `method` into the [`Layer`]. Unlike `send_middleware` it doesnt replace anything,
+    /// layers stack: first added is the outermost one. They are put around the middleware on `build()`, so it doesnt matter
+    /// if the route is replaced after. Layers of the method without the route are dropped.
+    /// 
+    /// ## Example
+    /// 
+    /// ```
+    /// let router = RouterBuilder::new()
+    ///     .layer("Send", Box::new(Logger))                    // Sees every Send, even rate limited
+    ///     .layer("Send", Box::new(limiter))                   // RateLimiter is the Layer too
+    ///     .layer("Send", Box::new(ProfanityFilter))
+    ///     .build();
+    /// ```
+    /// 
+    /// [`Middleware`]: crate::protocol::wares::Middleware
+    /// [`Layer`]: crate::protocol::wares::Layer
+    pub fn layer(mut self, method: &str, layer: Box<dyn Layer>) -> Self {
+        self.layers.entry(method.to_string()).or_default().push(layer);
         self
     }
 
+    // Setters for the default routes go through this one.
+    // Route is always there, unless someone removed it from `routes` by hand.
+    fn set_ware(&mut self, method: Method, set: impl FnOnce(&mut Route)) {
+        if let Some(route) = self.routes.get_mut(method.to_str()) {
+            set(route);
+        }
+    }
+
     /// Inserting values into [`Router`] to later on be used as shared state in 
     /// custom implementations of the [`StartingBytesware`], [`Middleware`],
     /// [`EndingBytesware`], [`AfterConnection`], [`BeforeConnection`].
@@ -376,6 +575,45 @@ impl RouterBuilder {
         self
     }
     
+    /// Setting up the max size of the incoming frame payload (in bytes).
+    /// If isnt set, it would be set to the [`DEFAULT_MAX_FRAME_SIZE`]
+    /// 
+    /// ## Example
+    /// 
+    /// ```
+    /// let router = RouterBuilder::new()
+    ///     .max_frame_size(4096)
+    ///     .build();
+    /// ```
+    /// 
+    /// [`DEFAULT_MAX_FRAME_SIZE`]: crate::protocol::frame::DEFAULT_MAX_FRAME_SIZE
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = Some(max_frame_size);
+        self
+    }
+
+    /// Setting up which protocol versions router accepts.
+    /// If isnt set, every version from [`Version::ALL`] is accepted.
+    /// 
+    /// Requests with other versions are answered with `<Code@25>` ([`ResponseCode::UnsupportedVersion`])
+    /// and `<Supported@'1.1'>` header, which lists accepted versions.
+    /// 
+    /// ## Example
+    /// 
+    /// ```
+    /// // Only new clients are welcome
+    /// let router = RouterBuilder::new()
+    ///     .supported_versions(vec![Version::CHAT11])
+    ///     .build();
+    /// ```
+    /// 
+    /// [`Version::ALL`]: crate::protocol::request::Version::ALL
+    /// [`ResponseCode::UnsupportedVersion`]: crate::protocol::response::ResponseCode::UnsupportedVersion
+    pub fn supported_versions(mut self, versions: Vec<Version>) -> Self {
+        self.supported_versions = Some(versions);
+        self
+    }
+    
     /// Changing default ip to new one.
     /// 
     /// ## Example
@@ -416,9 +654,164 @@ impl RouterBuilder {
         self
     }
 
+    /// Changing the [`Response`] every connection gets on shutdown. It is rewritten in the version client speaks.
+    /// 
+    /// [`Response`]: crate::protocol::response::Response
+    pub fn goodbye(mut self, goodbye: Response) -> Self {
+        self.goodbye = Some(goodbye);
+        self
+    }
+
+    /// Changing how long [`Router`] waits for connections to finish their requests on shutdown.
+    /// Connections that didnt make it are aborted (`AfterConnect` is still ran for them).
+    /// 
+    /// [`Router`]: crate::router::Router
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = Some(timeout);
+        self
+    }
+
+    /// Making [`Router`] talk over TLS. `certs` is the certificate chain (first is the router one), `key` is its private key.
+    /// If they dont match, [`RouterError::Tls`] is returned on `bind()` / `run()`.
+    /// 
+    /// ## Example
+    /// ```
+    /// let certs = CertificateDer::pem_file_iter("cert.pem")?.collect::<Result<Vec<_>, _>>()?;
+    /// let key = PrivateKeyDer::from_pem_file("key.pem")?;
+    /// 
+    /// let router = RouterBuilder::new()
+    ///     .tls(certs, key)
+    ///     .build();
+    /// ```
+    /// 
+    /// [`Router`]: crate::router::Router
+    /// [`RouterError::Tls`]: crate::router::RouterError::Tls
+    pub fn tls(mut self, certs: Vec<CertificateDer<'static>>, key: PrivateKeyDer<'static>) -> Self {
+        self.tls = Some(TlsIdentity { certs, key });
+        self
+    }
+
+    /// Making [`Router`] listen to WebSocket clients (browsers) on the `port`, next to the TCP ones. Ip is the one from `Config`.
+    /// Every WS message is one CHAT frame, and WS clients are in the same chat with the TCP ones.
+    /// If `tls()` is set, it is `wss://`.
+    /// 
+    /// ## Example
+    /// ```
+    /// let router = RouterBuilder::new()
+    ///     .port(8080)         // TCP clients
+    ///     .websocket(8081)    // ws://127.0.0.1:8081
+    ///     .build();
+    /// ```
+    /// 
+    /// [`Router`]: crate::router::Router
+    pub fn websocket(mut self, port: u16) -> Self {
+        self.websocket = Some(port);
+        self
+    }
+
+    /// Making tokens from `Bind` expire after `ttl`. Expired token cant be used for the `Handshake`,
+    /// and its name is freed in the background, as soon as nobody is online with it. Connections that are already
+    /// handshaked stay, use `App::revoke` to close them.
+    /// 
+    /// ## Example
+    /// ```
+    /// let router = RouterBuilder::new()
+    ///     .token_ttl(Duration::from_secs(60 * 60))   // User has an hour to connect with the token
+    ///     .build();
+    /// ```
+    pub fn token_ttl(mut self, ttl: Duration) -> Self {
+        self.token_ttl = Some(ttl);
+        self
+    }
+
+    /// Replacing default [`OpenAuthenticator`] with custom. It is used by the default `Bind`, `Handshake` and `Unbind`
+    /// middlewares, so the custom ones dont need to be written just for passwords.
+    /// 
+    /// ## Example
+    /// ```
+    /// let router = RouterBuilder::new()
+    ///     .authenticator(Box::new(PasswordFile::load("users.txt")?))
+    ///     .build();
+    /// ```
+    /// 
+    /// [`OpenAuthenticator`]: crate::router::OpenAuthenticator
+    pub fn authenticator(mut self, authenticator: Box<dyn Authenticator>) -> Self {
+        self.authenticator = Some(authenticator);
+        self
+    }
+
+    /// Checking names on `Bind` against the [`NamePolicy`]. Bad name is answered with `InvalidName` and the reason.
+    /// 
+    /// ## Example
+    /// ```
+    /// let router = RouterBuilder::new()
+    ///     .name_policy(NamePolicy::new().length(3, 16).reserve("admin"))
+    ///     .build();
+    /// ```
+    /// 
+    /// [`NamePolicy`]: crate::router::NamePolicy
+    pub fn name_policy(mut self, policy: NamePolicy) -> Self {
+        self.name_policy = Some(policy);
+        self
+    }
+
+    /// Saving messages to everyone and to the rooms in the [`MessageStore`]. Every message gets its `<Id@...>`,
+    /// and last ones are written to the user right after the `Handshake` (see [`RouterBuilder::replay`]).
+    /// 
+    /// ## Example
+    /// ```
+    /// let router = RouterBuilder::new()
+    ///     .message_store(Box::new(MemoryStore::new(500)))
+    ///     .build();
+    /// ```
+    /// 
+    /// [`MessageStore`]: crate::router::MessageStore
+    pub fn message_store(mut self, store: Box<dyn MessageStore>) -> Self {
+        self.message_store = Some(store);
+        self
+    }
+
+    /// How many last messages are replayed after the `Handshake`, 0 to not replay at all.
+    /// Makes sense only with [`RouterBuilder::message_store`].
+    pub fn replay(mut self, count: usize) -> Self {
+        self.replay = Some(count);
+        self
+    }
+
+    /// Serving [`RouterMetrics`] over HTTP on the `port` (`GET /metrics`), in the Prometheus text format. Ip is the one from `Config`.
+    /// Metrics are counted anyway, they are in `Router::metrics` without this too.
+    /// 
+    /// ## Example
+    /// ```
+    /// let router = RouterBuilder::new()
+    ///     .port(8080)
+    ///     .metrics(9100)      // http://127.0.0.1:9100/metrics
+    ///     .build();
+    /// ```
+    /// 
+    /// [`RouterMetrics`]: crate::router::RouterMetrics
+    pub fn metrics(mut self, port: u16) -> Self {
+        self.metrics_port = Some(port);
+        self
+    }
+
     /// Building [`Router`] and setting self.capacity to 32 if not `Some(val)`
+    /// and self.max_frame_size to [`DEFAULT_MAX_FRAME_SIZE`] if not `Some(val)`
+    /// and self.supported_versions to [`Version::ALL`] if not `Some(val)`
+    /// and self.goodbye to [`default_goodbye`] if not `Some(val)`
+    /// and self.shutdown_timeout to [`DEFAULT_SHUTDOWN_TIMEOUT`] if not `Some(val)`
+    /// and self.authenticator to [`OpenAuthenticator`] if not `Some(val)`
+    /// and self.replay to [`DEFAULT_REPLAY`] if not `Some(val)`.
+    /// Every route with layers gets its middleware wrapped into [`Layered`]
     /// 
     /// [`Router`]: crate::router::Router
+    /// [`Layered`]: crate::protocol::wares::Layered
+    /// [`DEFAULT_MAX_FRAME_SIZE`]: crate::protocol::frame::DEFAULT_MAX_FRAME_SIZE
+    /// [`Version::ALL`]: crate::protocol::request::Version::ALL
+    /// [`default_goodbye`]: crate::router::default_goodbye
+    /// [`DEFAULT_SHUTDOWN_TIMEOUT`]: crate::router::DEFAULT_SHUTDOWN_TIMEOUT
+    /// [`OpenAuthenticator`]: crate::router::OpenAuthenticator
+    /// [`DEFAULT_REPLAY`]: crate::router::DEFAULT_REPLAY
     pub fn build(self) -> Router {
         let capacity = if let Some(val) = self.capacity {
             val
@@ -426,36 +819,91 @@ impl RouterBuilder {
             32
         };
 
-        Router::new(self.starting_bytesware, self.send, self.handshake, self.bind, self.after, self.before, self.varmap, capacity, self.config)
+        let codec = FrameCodec::new(self.max_frame_size.unwrap_or(DEFAULT_MAX_FRAME_SIZE));
+
+        let supported_versions = self.supported_versions.unwrap_or(Version::ALL.to_vec());
+
+        // Putting layers around the middlewares, so Router sees usual routes
+        let mut routes = self.routes;
+        for (method, layers) in self.layers {
+            if let Some((middleware, ending_bytesware)) = routes.remove(&method) {
+                routes.insert(method, (Box::new(Layered::new(layers, middleware)), ending_bytesware));
+            }
+        }
+
+        let mut router = Router::new(self.starting_bytesware, routes, self.after, self.before, self.varmap, capacity, codec, supported_versions, self.config);
+
+        if let Some(goodbye) = self.goodbye {
+            router.goodbye = goodbye;
+        }
+
+        if let Some(timeout) = self.shutdown_timeout {
+            router.shutdown_timeout = timeout;
+        }
+
+        router.tls = self.tls;
+        router.websocket = self.websocket;
+        router.token_ttl = self.token_ttl;
+        router.name_policy = self.name_policy;
+        router.message_store = self.message_store.map(Arc::from);
+        router.replay = self.replay.unwrap_or(DEFAULT_REPLAY);
+        router.metrics_port = self.metrics_port;
+
+        if let Some(authenticator) = self.authenticator {
+            router.authenticator = Arc::from(authenticator);
+        }
+
+        router
     }
 }
 
 impl Router {
     /// If you dont want to use RouterBuilder, or you want to squize maximum startup time (LOL i dont have any arguments)
     /// you could use Router::new() to  
-    pub fn new(starting_bytesware: Box<dyn StartingBytesware>, send: Route, handshake: Route, bind: Route, after: Option<Box<dyn AfterConnect>>, before: Option<Box<dyn BeforeConnect>>, extension: Varmap, capacity: usize, config: Config) -> Self {
+    pub fn new(starting_bytesware: Box<dyn StartingBytesware>, registry: HashMap<String, Route>, after: Option<Box<dyn AfterConnect>>, before: Option<Box<dyn BeforeConnect>>, extension: Varmap, capacity: usize, codec: FrameCodec, supported_versions: Vec<Version>, config: Config) -> Self {
         Router {
             routes: Arc::new(Routes {
                 starting_bytesware,
-                send,
-                handshake,
-                bind,
+                registry,
+                supported_versions,
             }),
             before,
             after: Arc::new(after),
             extension,
             capacity,
+            codec,
             config,
+            goodbye: default_goodbye(),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            tls: None,
+            websocket: None,
+            token_ttl: None,
+            authenticator: Arc::new(OpenAuthenticator),
+            name_policy: None,
+            message_store: None,
+            replay: DEFAULT_REPLAY,
+            metrics: Arc::new(RouterMetrics::new()),
+            metrics_port: None,
+            shutdown: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Returns [`RouterHandle`], that could stop this router from anywhere.
+    /// 
+    /// [`RouterHandle`]: crate::router::RouterHandle
+    pub fn handle(&self) -> RouterHandle {
+        RouterHandle {
+            shutdown: self.shutdown.clone(),
         }
     }
 
     /// `Router::run()` creates `loop` that recieves incoming request through `TcpLictener::accept()`.
     /// It spawns one additional thread for handling `tokio` channels `MPSC` and `broadcast`. 
     /// First as it recieves connection, it runs [`Before`] (for example - connection incrementer). 
-    /// Then it reades bytes `[u8; 512]` from TcpListener.
+    /// Then it reades one frame (see [`frame`]) from TcpListener.
     /// After that it runs [`StartingBytesware`]. If everything is okay, next [`Middleware`] is executed.
     /// We collect Result<>, no matter of the return we pass it to the [`EndingBytesware`].
-    /// When we get the result - RouteRes(Result<[u8; 512], [u8; 512]>) we send it to the client, or
+    /// When we get the result - RouteRes(Result<Bytes, Bytes>) we send it framed to the client, or
     /// all clients depending on the context. When connection is closed [`After`] is ran (for example - connection decrementer).
     /// 
     /// # Examples
@@ -463,43 +911,262 @@ impl Router {
     /// ```
     /// let router = RouterBuilder::new().build();
     /// 
-    /// router.run().await;
+    /// if let Err(e) = router.run().await {
+    ///     println!("Couldnt start: {e}");
+    /// }
     /// ```
     /// 
-    /// # Panic
-    /// This function will panic, if it will fail to create listener.
-    /// This function may panic with the error "too many opened files" if ulimit is reached (if im not mistaking, because i could be)
+    /// # Errors
+    /// This function returns [`RouterError::Bind`], if it fails to create listener.
     ///
     /// [`Before`]: crate::protocol::wares::before_connect
     /// [`After`]: crate::protocol::wares::after_connect
-    pub async fn run(&self) {
-        let listener = create_listener(&self.config).await;
-        
-        println!("Listening to {0}:{1}", self.config.ip, self.config.port);
+    /// [`frame`]: crate::protocol::frame
+    pub async fn run(&self) -> Result<(), RouterError> {
+        self.run_until(std::future::pending::<()>()).await
+    }
 
-        let app = Arc::new(Mutex::new(App::new(self.extension.clone())));
+    /// Same as [`Router::run`], but stops, when `shutdown` is done (or [`RouterHandle::shutdown`] is called).
+    /// Then it stops accepting connections, sends `goodbye` to every connection and waits up to `shutdown_timeout`
+    /// for them to finish the request they are in. `AfterConnect` is ran for every connection before it returns.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// let router = RouterBuilder::new()
+    ///     .shutdown_timeout(Duration::from_secs(1))
+    ///     .build();
+    /// 
+    /// router.run_until(async { tokio::signal::ctrl_c().await.unwrap() }).await.unwrap();
+    /// ```
+    /// 
+    /// [`RouterHandle::shutdown`]: crate::router::RouterHandle::shutdown
+    pub async fn run_until<F: Future<Output = ()>>(&self, shutdown: F) -> Result<(), RouterError> {
+        let tls = self.tls_acceptor()?;
+        let listener = create_listener(&self.config).await?;
+        let websocket = self.websocket_listener().await?;
+        let metrics = self.metrics_listener().await?;
+
+        self.serve(listener, websocket, metrics, tls, shutdown).await;
+        Ok(())
+    }
+
+    /// Binds the listener, but doesnt run yet. Returned [`BoundRouter`] knows the real address,
+    /// so port 0 could be used (OS picks the free one), and many routers could run side by side.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// let router = RouterBuilder::new().port(0).build().bind().await?;
+    /// let addr = router.local_addr();
+    /// 
+    /// tokio::spawn(router.run());
+    /// let client = DefaultClient::new(addr);
+    /// ```
+    /// 
+    /// [`BoundRouter`]: crate::router::BoundRouter
+    pub async fn bind(self) -> Result<BoundRouter, RouterError> {
+        let listener = create_listener(&self.config).await?;
+        let websocket = self.websocket_listener().await?;
+        let metrics = self.metrics_listener().await?;
+        let addr = format!("{0}:{1}", self.config.ip, self.config.port);
+
+        BoundRouter::new(self, listener, websocket, metrics, addr)
+    }
+
+    /// Uses given [`Listener`] instead of binding to the `Config`. It could be `UnixListener`, [`MemoryListener`],
+    /// or `TcpListener` that was bound before (for example by the systemd socket activation).
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// let listener = tokio::net::UnixListener::bind("/tmp/pinguino.sock")?;
+    /// 
+    /// RouterBuilder::new()
+    ///     .build()
+    ///     .with_listener(listener)?
+    ///     .run()
+    ///     .await;
+    /// ```
+    /// 
+    /// `websocket` port is not bound here, wrap the listener into [`Both`] with the [`WsListener`] for that.
+    /// Same with the `metrics` port, use [`serve_metrics`] with your own listener.
+    /// 
+    /// [`Listener`]: crate::router::Listener
+    /// [`MemoryListener`]: crate::router::MemoryListener
+    /// [`Both`]: crate::router::Both
+    /// [`WsListener`]: crate::router::WsListener
+    /// [`serve_metrics`]: crate::router::serve_metrics
+    pub fn with_listener<L: Listener>(self, listener: L) -> Result<BoundRouter<L>, RouterError> {
+        BoundRouter::new(self, listener, None, None, "given listener".to_string())
+    }
 
-        let (mp_tx, mp_rx) = mpsc::unbounded_channel::<[u8; 512]>();
-        let (br_tx, _) = broadcast::channel::<[u8; 512]>(self.capacity);
+    // Second listener for the WebSocket clients, on the same ip
+    async fn websocket_listener(&self) -> Result<Option<TcpListener>, RouterError> {
+        match self.websocket {
+            Some(port) => {
+                let config = Config {
+                    ip: self.config.ip.clone(),
+                    port,
+                };
+
+                Ok(Some(create_listener(&config).await?))
+            },
+            None => Ok(None),
+        }
+    }
+
+    // Listener for the metrics endpoint, on the same ip
+    async fn metrics_listener(&self) -> Result<Option<TcpListener>, RouterError> {
+        match self.metrics_port {
+            Some(port) => {
+                let config = Config {
+                    ip: self.config.ip.clone(),
+                    port,
+                };
+
+                Ok(Some(create_listener(&config).await?))
+            },
+            None => Ok(None),
+        }
+    }
+
+    // Acceptor is made before binding, so broken certificate is found out right away
+    fn tls_acceptor(&self) -> Result<Option<TlsAcceptor>, RouterError> {
+        match &self.tls {
+            Some(identity) => match identity.acceptor() {
+                Ok(acceptor) => Ok(Some(acceptor)),
+                Err(e) => Err(RouterError::Tls(e)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    // Wrapping the listener into TLS, and adding the WebSocket one, if they are needed. Metrics are served while router runs.
+    async fn serve<L: Listener, F: Future<Output = ()>>(&self, listener: L, websocket: Option<TcpListener>, metrics: Option<TcpListener>, tls: Option<TlsAcceptor>, shutdown: F) {
+        let max = self.codec.max_frame_size;
+        let metrics = metrics.map(|val| tokio::spawn(serve_metrics(val, self.metrics.clone())));
+
+        match (websocket, tls) {
+            (None, None) => self.accept_loop(listener, shutdown).await,
+            (None, Some(acceptor)) => self.accept_loop(TlsListener::new(listener, acceptor), shutdown).await,
+            (Some(ws), None) => self.accept_loop(Both::new(listener, WsListener::new(ws, max)), shutdown).await,
+            (Some(ws), Some(acceptor)) => {
+                let ws = WsListener::new(TlsListener::new(ws, acceptor.clone()), max);
+                self.accept_loop(Both::new(TlsListener::new(listener, acceptor), ws), shutdown).await
+            },
+        }
+
+        if let Some(metrics) = metrics {
+            metrics.abort();
+        }
+    }
+
+    // Accepting connections from the listener, until shutdown. Everything that is after the bind.
+    async fn accept_loop<L: Listener, F: Future<Output = ()>>(&self, mut listener: L, shutdown: F) {
+        if let Ok(addr) = listener.local_addr() {
+            info!(%addr, "Listening");
+        }
+
+        let mut app = App::new(self.extension.clone());
+        app.room_capacity = self.capacity;
+        app.token_ttl = self.token_ttl;
+        app.authenticator = self.authenticator.clone();
+        app.name_policy = self.name_policy.clone();
+        app.message_store = self.message_store.clone();
+        app.replay = self.replay;
+        app.metrics = self.metrics.clone();
+        let app = Arc::new(Mutex::new(app));
+
+        // Expired names are freed in the background, so they are not taken until someone tries to bind them
+        let sweeper = self.token_ttl.map(|ttl| tokio::spawn(sweep_tokens(app.clone(), ttl.clamp(Duration::from_millis(1), MAX_SWEEP_INTERVAL))));
+
+        let (mp_tx, mp_rx) = mpsc::unbounded_channel::<Bytes>();
+        let (br_tx, _) = broadcast::channel::<Bytes>(self.capacity);
 
         let main_thread_writer = br_tx.clone();
         tokio::spawn( handle_main_thread(main_thread_writer, mp_rx));
 
+        // Connections are kept, so they could be closed on shutdown
+        let mut connections = JoinSet::new();
+        let mut states = HashMap::new();
+        let mut stop = self.shutdown.subscribe();
+        tokio::pin!(shutdown);
+
         loop {
-            let (stream, addr) = match listener.accept().await {
-                Ok(val) => val,
-                Err(_) => { continue; }
+            let (stream, addr) = select! {
+                _ = &mut shutdown => break,
+                _ = stop.wait_for(|val| *val) => break,
+                Some(done) = connections.join_next_with_id() => {
+                    let id = match done {
+                        Ok((id, _)) => id,
+                        Err(e) => e.id(),
+                    };
+                    states.remove(&id);
+
+                    continue;
+                },
+                val = listener.accept() => match val {
+                    Ok(val) => val,
+                    Err(e) => {
+                        warn!(error = %e, "Failed to accept the connection");
+
+                        continue;
+                    }
+                },
             };
 
             let br_tx_sub = br_tx.subscribe();
             let mp_tx_sub = mp_tx.clone();
-            let state = Arc::new(Mutex::new(State::new(app.clone(), self.after.clone())));
+            let mut state = State::new(app.clone(), self.after.clone());
+
+            // Connection is registered in the App first, so BeforeConnect could already push to it
+            let outbound = state.attach().await;
+            let state = Arc::new(Mutex::new(state));
             
             if let Some(before) = &self.before {
                 before.execute(state.clone()).await;
             }
             
-            tokio::spawn(handle_wrapper(self.routes.clone(), stream, state, Arc::new(addr), br_tx_sub, mp_tx_sub));
+            let context = ConnectionContext {
+                routes: self.routes.clone(),
+                addr: Arc::new(addr),
+                codec: self.codec,
+                br_tx_sub,
+                mp_tx_sub,
+                outbound,
+            };
+
+            let handle = connections.spawn(handle_wrapper(stream, state.clone(), context));
+            states.insert(handle.id(), state);
+        }
+
+        // Not accepting anyone from now on
+        drop(listener);
+
+        info!(connections = states.len(), "Shutting down, closing connections");
+
+        shutdown::close_connections(app, &self.goodbye, self.shutdown_timeout, connections, states).await;
+
+        if let Some(sweeper) = sweeper {
+            sweeper.abort();
+        }
+    }
+}
+
+// Longest time expired name could stay taken, when `token_ttl` is long
+const MAX_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+// Frees names of the expired tokens every `every`
+async fn sweep_tokens(app: Arc<Mutex<App>>, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+
+    loop {
+        interval.tick().await;
+        let freed = app.lock().await.sweep();
+
+        if !freed.is_empty() {
+            info!(names = freed.len(), "Freed expired names");
         }
     }
 }
@@ -0,0 +1,211 @@
+//! ## `NamePolicy`
+//!
+//! Which names could be taken on `Bind`. Without the policy any name goes, with it name is checked for the length,
+//! characters, reserved names and for looking like someone else who is already bound (`Jeff` and `jeff`,
+//! `jeff` and `jеff` with the cyrillic `е`). Bad name is answered with `InvalidName` and `<Reason@...>`.
+//!
+//! ## Example
+//! ```
+//! let router = RouterBuilder::new()
+//!     .name_policy(NamePolicy::new()
+//!         .length(3, 16)
+//!         .reserve("admin")
+//!         .reserve("server"))
+//!     .build();
+//! ```
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+/// ## `NamePolicy`
+///
+/// Rules for the names. Default one allows 1 to 32 letters, digits, `_`, `-` and `.`, names are unique
+/// ignoring the case and the look-alike characters, nothing is reserved.
+#[derive(Debug, Clone)]
+pub struct NamePolicy {
+    pub min_len: usize,             // In chars, not bytes
+    pub max_len: usize,
+    pub allowed: fn(char) -> bool,  // Every char of the name must pass it
+    pub reserved: HashSet<String>,  // Compared the same way as other names, so `Аdmin` is reserved too
+    pub case_insensitive: bool,     // `Jeff` and `jeff` are the same name
+    pub confusables: bool,          // `jeff` and `jеff` are the same name. Implies case_insensitive.
+}
+
+/// ## `NameViolation`
+///
+/// Why the name was not accepted. `Display` is what goes in the `<Reason@...>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NameViolation {
+    TooShort(usize),        // Min length
+    TooLong(usize),         // Max length
+    InvalidChar(char),
+    Reserved,
+    LooksLike(String),      // Name that is already taken, and looks the same
+}
+
+impl Display for NameViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameViolation::TooShort(min) => write!(f, "name is shorter than {min} characters"),
+            NameViolation::TooLong(max) => write!(f, "name is longer than {max} characters"),
+            NameViolation::InvalidChar(c) => write!(f, "character {c:?} is not allowed in the name"),
+            NameViolation::Reserved => write!(f, "name is reserved"),
+            NameViolation::LooksLike(other) => write!(f, "name looks like already taken {other:?}"),
+        }
+    }
+}
+
+impl std::error::Error for NameViolation {}
+
+impl Default for NamePolicy {
+    fn default() -> Self {
+        NamePolicy {
+            min_len: 1,
+            max_len: 32,
+            allowed: default_allowed,
+            reserved: HashSet::new(),
+            case_insensitive: true,
+            confusables: true,
+        }
+    }
+}
+
+/// Letters and digits of any language, `_`, `-` and `.`.
+pub fn default_allowed(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+impl NamePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn length(mut self, min: usize, max: usize) -> Self {
+        self.min_len = min;
+        self.max_len = max;
+        self
+    }
+
+    /// Replacing default [`default_allowed`] with custom.
+    ///
+    /// ## Example
+    /// ```
+    /// let policy = NamePolicy::new().allowed(|c| c.is_ascii_alphanumeric());
+    /// ```
+    pub fn allowed(mut self, allowed: fn(char) -> bool) -> Self {
+        self.allowed = allowed;
+        self
+    }
+
+    pub fn reserve(mut self, name: &str) -> Self {
+        self.reserved.insert(name.to_string());
+        self
+    }
+
+    pub fn case_insensitive(mut self, val: bool) -> Self {
+        self.case_insensitive = val;
+        self
+    }
+
+    pub fn confusables(mut self, val: bool) -> Self {
+        self.confusables = val;
+        self
+    }
+
+    /// Checks the `name` against the rules and the names that are `taken` already.
+    /// Exactly the same name is not a violation, it is up to the caller (`AlreadyTaken`, or the same user binding again).
+    pub fn check<'a>(&self, name: &str, taken: impl IntoIterator<Item = &'a String>) -> Result<(), NameViolation> {
+        let len = name.chars().count();
+        if len < self.min_len {
+            return Err(NameViolation::TooShort(self.min_len));
+        }
+
+        if len > self.max_len {
+            return Err(NameViolation::TooLong(self.max_len));
+        }
+
+        if let Some(c) = name.chars().find(|c| !(self.allowed)(*c)) {
+            return Err(NameViolation::InvalidChar(c));
+        }
+
+        let key = self.key(name);
+        if self.reserved.iter().any(|val| self.key(val) == key) {
+            return Err(NameViolation::Reserved);
+        }
+
+        match taken.into_iter().find(|val| *val != name && self.key(val) == key) {
+            Some(other) => Err(NameViolation::LooksLike(other.clone())),
+            None => Ok(()),
+        }
+    }
+
+    // What names are compared by
+    fn key(&self, name: &str) -> String {
+        if self.confusables {
+            skeleton(name)
+        } else if self.case_insensitive {
+            name.to_lowercase()
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+/// Lowercased `name` with the look-alike characters replaced by the latin ones they look like, so
+/// `Jeff`, `jeff`, `jеff` (cyrillic `е`), `ｊｅｆｆ` (fullwidth) and `jéff` have the same skeleton.
+/// It is not the whole Unicode confusables table, only the characters that are usually used for impersonating.
+pub fn skeleton(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        // `I` and `l` are the same in most of the fonts, so it is done before lowercasing
+        if c == 'I' {
+            out.push('l');
+            continue;
+        }
+
+        for c in c.to_lowercase() {
+            // Combining marks and invisible characters
+            if matches!(c, '\u{0300}'..='\u{036F}' | '\u{200B}'..='\u{200F}' | '\u{2060}' | '\u{FEFF}') {
+                continue;
+            }
+
+            out.push(prototype(c));
+        }
+    }
+
+    // Pairs, that look like one letter
+    out.replace("rn", "m").replace("vv", "w")
+}
+
+fn prototype(c: char) -> char {
+    match c {
+        // Fullwidth ascii
+        '\u{FF01}'..='\u{FF5E}' => prototype(char::from_u32(c as u32 - 0xFEE0).unwrap_or(c)),
+
+        'à'..='å' | 'ā' | 'ă' | 'ą' | 'а' | 'α' => 'a',
+        'ь' | 'в' | 'β' => 'b',
+        'ç' | 'ć' | 'č' | 'с' | 'ϲ' => 'c',
+        'ď' | 'đ' | 'ԁ' => 'd',
+        'è'..='ë' | 'ē' | 'ė' | 'ę' | 'ě' | 'е' | 'ё' | 'ε' => 'e',
+        'ğ' | 'ġ' | 'ģ' => 'g',
+        'һ' => 'h',
+        'ì'..='ï' | 'ī' | 'į' | 'ı' | 'і' | 'ї' | 'ι' => 'i',
+        'ј' => 'j',
+        'к' | 'κ' => 'k',
+        '1' | '|' | 'ł' | 'ӏ' | 'ℓ' => 'l',
+        'м' => 'm',
+        'ñ' | 'ń' | 'ň' | 'п' | 'η' => 'n',
+        '0' | 'ò'..='ö' | 'ø' | 'ō' | 'ő' | 'о' | 'ο' | 'σ' => 'o',
+        'р' | 'ρ' => 'p',
+        'ԛ' => 'q',
+        'ś' | 'š' | 'ş' | 'ѕ' | '5' => 's',
+        'т' | 'τ' => 't',
+        'ù'..='ü' | 'ū' | 'ů' | 'ű' | 'υ' => 'u',
+        'ν' => 'v',
+        'ԝ' | 'ω' => 'w',
+        'х' | 'χ' => 'x',
+        'ý' | 'ÿ' | 'у' | 'γ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        _ => c,
+    }
+}
@@ -1,19 +1,23 @@
-use crate::protocol::request::{RawRequest, Version};
+use crate::protocol::request::{RawRequest, Request};
 use crate::protocol::response::{Response, ResponseCode};
-use crate::protocol::set_keepalive;
+use crate::protocol::frame::{write_frame, FrameCodec, FrameError, FrameReader};
+use bytes::Bytes;
+use tokio::select;
 use tokio::sync::Mutex;
-use tokio::task::Id;
-use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
-use tokio::sync::{mpsc::UnboundedSender as MpscSender, broadcast::Receiver as BrReceiver};
+use tokio::sync::{mpsc::UnboundedSender as MpscSender, mpsc::UnboundedReceiver, broadcast::Receiver as BrReceiver};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Instant;
 use crate::protocol::request::Method;
 use crate::protocol::response::ResponseBuilder;
+use crate::protocol::wares::Route;
+use tracing::{error, field, info, info_span, trace, warn, Instrument, Span};
 
-use super::{Routes, State, RouteRes, send_handler::handle_send};
+use super::{Connection, Metered, Outbound, Routes, State, RouteRes, send_handler::handle_send};
+use super::store::{parse_time, HistoryPage, HistoryQuery, Since};
 
-// This function is where Request is processed
-pub async fn handle_request(routes: Arc<Routes>, req_bytes: [u8; 512], addr: Arc<SocketAddr>, state: Arc<Mutex<State>>, _thread_id: Id, is_handshake: bool) -> RouteRes {
+// This function is where Request is processed, it is called inside of the connection span
+pub async fn handle_request(routes: Arc<Routes>, req_bytes: Bytes, addr: Arc<SocketAddr>, state: Arc<Mutex<State>>, is_handshake: bool) -> RouteRes {
     // We have RawRequest for easier tossing around bytes and SocketAddr, if user would like to save it.
     let raw_req = RawRequest {
         bytes: req_bytes,
@@ -27,46 +31,190 @@ pub async fn handle_request(routes: Arc<Routes>, req_bytes: [u8; 512], addr: Arc
     let req = match req_res {
         Ok(val) => val,
         Err(res) => {
-            #[cfg(feature = "debug_light")]
-            println!("<<< [SUB:{_thread_id}] Failed to get parse request via bytesware");
+            warn!("Failed to get parse request via bytesware");
             
             // I almosst forgot, that we need to route it to the EndingBytesware
-            return RouteRes::None(routes.send.1.bytesware(state, Err(res)).await);
+            return RouteRes::None(routes.fallback().bytesware(state, Err(res)).await);
+        }
+    };
+
+    // Version is known, but router could be told to not accept it
+    let req = match check_version(&routes, state.clone(), req).await {
+        Ok(val) => val,
+        Err(res) => {
+            warn!("User uses version, that is not supported");
+
+            return RouteRes::None(routes.fallback().bytesware(state, Err(res)).await);
         }
     };
 
     // If everything went OK, we just proceed depending on the method
-    match req.method {
-        Method::Bind => {
-            let second_res: Result<Response, Response> = routes.bind.0.middleware(req, state.clone()).await;
+    let route = match routes.get(&req.method) {
+        Some(val) => val,
+        None => {
+            warn!(method = req.method.to_str(), "There is no route for the method");
 
-            RouteRes::Bind(routes.bind.1.bytesware(state.clone(), second_res).await)
-        },
-        Method::Send => {
-            if is_handshake {
-                let second_res: Result<Response, Response> = routes.send.0.middleware(req, state.clone()).await;
+            let res = method_error(&req);
+            state.lock().await.metrics.unrouted(&res.code);
+
+            return RouteRes::None(routes.fallback().bytesware(state, Err(res)).await);
+        }
+    };
+
+    // User is known after the Bind or the Handshake, it is recorded in the connection span
+    let connection = Span::current();
+    let span = request_span(&req);
+
+    async move {
+        match req.method {
+            // Unbind and History are answered the same way, they dont need the Handshake
+            Method::Bind | Method::Unbind | Method::History => {
+                let bound = (req.method == Method::Bind).then(|| req.value.clone());
+                let second_res: Result<Response, Response> = run_middleware(route, req, state.clone()).await;
+
+                if let (Some(name), Ok(_)) = (bound, &second_res) {
+                    connection.record("user", name.as_str());
+                }
+
+                RouteRes::Bind(route.1.bytesware(state.clone(), second_res).await)
+            },
+            // Rooms need the connection, that is there only after the Handshake, so they are handled the same as Send
+            Method::Send | Method::Join | Method::Leave => {
+                if is_handshake {
+                    let second_res: Result<Response, Response> = run_middleware(route, req, state.clone()).await;
             
-                RouteRes::Send(routes.send.1.bytesware(state.clone(), second_res).await)
-            } else {
-                let res = ResponseBuilder::new()
-                    .version(Version::CHAT10)
-                    .code(ResponseCode::Unauthorized)
-                    .build()
-                    .unwrap();
-
-                RouteRes::Send(routes.send.1.bytesware(state.clone(), Err(res)).await)
-            }
-        },
-        Method::Handshake => {
-            let second_res = routes.handshake.0.middleware(req, state.clone()).await;
+                    RouteRes::Send(route.1.bytesware(state.clone(), second_res).await)
+                } else {
+                    let res = ResponseBuilder::new()
+                        .version(req.version)
+                        .code(ResponseCode::Unauthorized)
+                        .build()
+                        .unwrap();
+                    state.lock().await.metrics.request(&req.method, &res.code);
+
+                    RouteRes::Send(route.1.bytesware(state.clone(), Err(res)).await)
+                }
+            },
+            Method::Handshake => {
+                // Messages are replayed from it after the Handshake, bad time is the same as no time
+                if let Some(since) = req.custom.get("Since").and_then(|val| parse_time(val)) {
+                    state.lock().await.varmap.insert(Since(since));
+                }
+
+                let second_res = run_middleware(route, req, state.clone()).await;
+
+                if second_res.is_ok() {
+                    if let Some(name) = state.lock().await.varmap.get::<String>() {
+                        connection.record("user", name.as_str());
+                    }
+                }
         
-            RouteRes::Handshake(routes.handshake.1.bytesware(state.clone(), second_res).await)
+                RouteRes::Handshake(route.1.bytesware(state.clone(), second_res).await)
+            },
+            Method::Custom(_) => {
+                let second_res = run_middleware(route, req, state.clone()).await;
+
+                RouteRes::Custom(route.1.bytesware(state.clone(), second_res).await)
+            }
         }
+    }.instrument(span).await
+}
+
+// Span of one request, it is the child of the connection span
+pub(crate) fn request_span(req: &Request) -> Span {
+    info_span!("request", method = req.method.to_str())
+}
+
+// Runs the middleware of the `route`, how long it took and the code it answered with go to the `RouterMetrics`
+pub(crate) async fn run_middleware(route: &Route, req: Request, state: Arc<Mutex<State>>) -> Result<Response, Response> {
+    let metrics = state.lock().await.metrics.clone();
+    let method = req.method.clone();
+
+    let started = Instant::now();
+    let res = route.0.middleware(req, state).await;
+    metrics.observe(&method, started.elapsed());
+
+    match &res {
+        Ok(val) | Err(val) => metrics.request(&method, &val.code),
+    }
+
+    res
+}
+
+// This function builds response for the method, that cant be used right now:
+// there is no route for it, or it is not allowed during the handshake.
+pub(crate) fn method_error(req: &Request) -> Response {
+    let reason = if let Method::Custom(name) = &req.method {
+        format!("unknown method `{name}`")
+    } else {
+        format!("method `{0}` is not allowed here", req.method.to_str())
+    };
+
+    ResponseBuilder::new()
+        .version(req.version)
+        .code(ResponseCode::Error)
+        .reason(reason)
+        .build()
+        .unwrap()
+}
+
+// This function checks, that router accepts the version of the parsed request.
+// If it does, version is remembered in the State, so every response on this connection is in it.
+// If it doesnt, `UnsupportedVersion` with `<Supported@...>` is returned (1.0 clients wont see that header, but code is enough).
+pub(crate) async fn check_version(routes: &Routes, state: Arc<Mutex<State>>, req: Request) -> Result<Request, Response> {
+    if routes.supported_versions.contains(&req.version) {
+        state.lock().await.version = req.version;
+
+        return Ok(req);
+    }
+
+    let supported = routes.supported_versions.iter()
+        .map(|version| version.to_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let res = ResponseBuilder::new()
+        .version(req.version)
+        .code(ResponseCode::UnsupportedVersion)
+        .custom_insert("Supported".to_string(), supported)
+        .build()
+        .unwrap();
+
+    Err(res)
+}
+
+// This function is used, when incoming frame is too large.
+// Its routed to the EndingBytesware the same way, as failed StartingBytesware.
+pub(crate) async fn handle_bad_frame(routes: &Routes, state: Arc<Mutex<State>>, reason: String) -> Bytes {
+    let version = state.lock().await.version;
+
+    let res = ResponseBuilder::new()
+        .version(version)
+        .code(ResponseCode::ParseError)
+        .reason(reason)
+        .build()
+        .unwrap();
+
+    match routes.fallback().bytesware(state, Err(res)).await {
+        Ok(val) => val,
+        Err(val) => val,
     }
 }
 
+/// Everything the connection gets from the router, besides the stream and the state.
+pub(crate) struct ConnectionContext {
+    pub routes: Arc<Routes>,
+    pub addr: Arc<SocketAddr>,
+    pub codec: FrameCodec,
+    pub br_tx_sub: BrReceiver<Bytes>,           // Messages to everyone
+    pub mp_tx_sub: MpscSender<Bytes>,           // Way to send messages to everyone
+    pub outbound: UnboundedReceiver<Outbound>,  // Everything pushed to this connection, see `State::attach`
+}
+
 /// This function is used to route request and send responses.
-pub async fn handle_request1(routes: Arc<Routes>, mut stream: TcpStream, addr: Arc<SocketAddr>, state: Arc<Mutex<State>>, br_tx_sub: BrReceiver<[u8; 512]>, mp_tx_sub: MpscSender<[u8; 512]>) {
+pub(crate) async fn handle_request1<C: Connection>(stream: C, state: Arc<Mutex<State>>, context: ConnectionContext) {
+    let ConnectionContext { routes, addr, codec, mut br_tx_sub, mp_tx_sub, mut outbound } = context;
+
     // Getting thread_id for better debugging experience, so there wont be the mess
     let thread_id = tokio::task::id();
 
@@ -77,120 +225,135 @@ pub async fn handle_request1(routes: Arc<Routes>, mut stream: TcpStream, addr: A
     // Not forgetting to drop it, so there wont be infinit lock()
     drop(blocked);
 
-    #[cfg(feature = "debug_light")]
-    println!(">>> [SUB:{thread_id}] Recieved request from {addr}");
+    info!("Connection opened");
+
+    // Reader keeps everything that came after the first frame, so nothing is lost on Handshake
+    let mut reader = FrameReader::new(stream, codec);
+
+    // Until the first frame, everything pushed to the connection (in the BeforeConnect too) is written right away
+    let first = loop {
+        select! {
+            val = reader.read_frame() => break val,
+            Some(val) = outbound.recv() => {
+                match val {
+                    Outbound::Frame(bytes) => {
+                        if let Ok(0) | Err(_) = write_frame(reader.get_mut(), &codec, &bytes).await {
+                            return;
+                        }
+                    },
+                    Outbound::Close => {
+                        info!("Connection closed by the server");
+
+                        return;
+                    }
+                }
+            }
+        }
+    };
 
-    let mut buf = [0u8; 512];
-    match stream.read(&mut buf).await {
-        Ok(0) => {
-            #[cfg(feature = "debug_light")]
-            println!(">>> [SUB:{thread_id}] Connection closed by user");
+    match first {
+        Ok(None) => {
+            info!("Connection closed by user");
             
             return;
         },
-        Ok(_val) => {
-            #[cfg(feature = "debug_full")]
-            println!("--> [SUB:{thread_id}] Read {_val} bytes from user");
+        Ok(Some(req_bytes)) => {
+            trace!(bytes = req_bytes.len(), "Read bytes from user");
 
             // If we read, and there is something, just handle the request.
-            let res = handle_request(routes.clone(), buf, addr.clone(), state.clone(), thread_id, false).await;
+            let res = handle_request(routes.clone(), req_bytes, addr.clone(), state.clone(), false).await;
 
             match res {
-                RouteRes::Bind(val) => {
+                // Custom methods before the Handshake are answered the same way as Bind
+                RouteRes::Bind(val) | RouteRes::Custom(val) => {
                     let write_buf = match val {
                         Ok(val) => val,
                         Err(val) => val,
                     };
 
-                    match stream.write(&write_buf).await {
+                    if !write_page(&mut reader, &codec, &state).await {
+                        return;
+                    }
+
+                    match write_frame(reader.get_mut(), &codec, &write_buf).await {
                         Ok(0) => {
-                            #[cfg(feature = "debug_light")]
-                            println!(">>> [SUB:{thread_id}] Conncetion closed");
+                            info!("Connection closed");
 
                             return;
                         },
-                        Ok(_val) => {
-                            #[cfg(feature = "debug_full")]
-                            println!("--> [SUB:{thread_id}] Wrote {_val} to user");
+                        Ok(val) => {
+                            trace!(bytes = val, "Wrote bytes to user");
                         },
-                        Err(_e) => {
-                            #[cfg(feature = "debug_full")]
-                            println!("<<< [SUB:{thread_id}] Failed to write to user with error: {_e}")
+                        Err(e) => {
+                            trace!(error = %e, "Failed to write to user")
                         }
                     }
                 },
                 RouteRes::Send(val) => {
-                    #[cfg(feature = "debug_light")]
-                    println!("<<< [SUB:{thread_id}] User shouldnt use Method::Send outside of the Handshake.");
+                    warn!("User shouldnt use Method::Send outside of the Handshake");
 
                     // We got 100% error message, because of is_handshake = false
-                    match stream.write(&val.err().unwrap()).await {
+                    match write_frame(reader.get_mut(), &codec, &val.err().unwrap()).await {
                         Ok(0) => {
-                            #[cfg(feature = "debug_light")]
-                            println!(">>> [SUB:{thread_id}] Connection closed");
+                            info!("Connection closed");
 
                             return;
                         },
-                        Ok(_val) => {
-                            #[cfg(feature = "debug_full")]
-                            println!("--> [SUB:{thread_id}] Wrote {_val} bytes to user.");
+                        Ok(val) => {
+                            trace!(bytes = val, "Wrote bytes to user");
                         },
-                        Err(_e) => {
-                            #[cfg(feature = "debug_light")]
-                            println!("<<< [SUB:{thread_id}] Failed to write error response to user with error {_e}");
+                        Err(e) => {
+                            warn!(error = %e, "Failed to write error response to user");
                         }
                     }    
                 },
                 RouteRes::Handshake(val) => {
                     match val {
                         Ok(val) => {
-                            let mut stream = match set_keepalive(stream).await {
-                                Ok(val)=> val,
-                                Err(_e) => {
-                                    #[cfg(feature = "debug_light")]
-                                    println!("<<< [SUB:{thread_id}] Faileld to start keepalive, panic! {_e}");
-                                    
-                                    return;
-                                }
-                            };
+                            if let Err(e) = reader.get_ref().keepalive() {
+                                error!(error = %e, "Failed to start keepalive");
+                                
+                                return;
+                            }
 
-                            match stream.write(&val).await {
+                            match write_frame(reader.get_mut(), &codec, &val).await {
                                 Ok(0) => {
-                                    #[cfg(feature = "debug_light")]
-                                    println!("<<< [SUB:{thread_id}] Connection is closed");
+                                    warn!("Connection is closed");
 
                                     return;
                                 },
-                                Ok(_val) => {
-                                    #[cfg(feature = "debug_full")]
-                                    println!("--> [SUB:{thread_id}] Wrote {_val} bytes to user");
+                                Ok(val) => {
+                                    trace!(bytes = val, "Wrote bytes to user");
+
+                                    // Missed messages go first, and only then the live ones
+                                    if !replay(&mut reader, &codec, &state, &mut br_tx_sub).await {
+                                        warn!("Connection closed during the replay");
+
+                                        return;
+                                    }
 
                                     // Here we start Handshake, and accepting only Method::Send from now on from this client     
-                                    handle_send(stream, routes.clone(), addr.clone(), state.clone(), br_tx_sub, mp_tx_sub, thread_id).await;
+                                    handle_send(reader, routes.clone(), addr.clone(), state.clone(), br_tx_sub, mp_tx_sub, outbound).await;
                                 },
-                                Err(_e) => {
-                                    #[cfg(feature = "debug_light")]
-                                    println!("<<< [SUB:{thread_id}] Failed to write to user with error {_e}");
+                                Err(e) => {
+                                    warn!(error = %e, "Failed to write to user");
                                     
                                     return;
                                 }
                             }
                         },
                         Err(val) => {
-                            match stream.write(&val).await {
+                            match write_frame(reader.get_mut(), &codec, &val).await {
                                 Ok(0) => {
-                                    #[cfg(feature = "debug_light")]
-                                    println!(">>> [SUB:{thread_id}] Connection was closed before closing");
+                                    info!("Connection was closed before closing");
 
                                     return;
                                 },
-                                Ok(_val) => {
-                                    #[cfg(feature = "debug_full")] 
-                                    println!("--> [SUB:{thread_id}] Wrote {_val} bytes to the user, but closing"); 
+                                Ok(val) => {
+                                    trace!(bytes = val, "Wrote bytes to the user, but closing");
                                 },
-                                Err(_e) => {
-                                    #[cfg(feature = "debug_light")]
-                                    println!("<<< [SUB:{thread_id}] Failed to write to user with error: {_e}");
+                                Err(e) => {
+                                    warn!(error = %e, "Failed to write to user");
                                     return;
                                 }
                             }
@@ -204,22 +367,19 @@ pub async fn handle_request1(routes: Arc<Routes>, mut stream: TcpStream, addr: A
                         Err(val) => val
                     };
 
-                    match stream.write(&resp).await {
+                    match write_frame(reader.get_mut(), &codec, &resp).await {
                         Ok(0) => {
-                            #[cfg(feature = "debug_light")]
-                            println!(">>> [SUB:{thread_id}] Connection is closed");
+                            info!("Connection is closed");
 
                             return;
                         },
-                        Ok(_val) => {
-                            #[cfg(feature = "debug_full")]
-                            println!("--> [SUB:{thread_id}] Wrote {_val} bytes to user");
+                        Ok(val) => {
+                            trace!(bytes = val, "Wrote bytes to user");
                             
                             return;
                         },
-                        Err(_e) => {
-                            #[cfg(feature = "debug_light")]
-                            println!("<<< [SUB:{thread_id}] Failed to write to user with error {_e}");
+                        Err(e) => {
+                            warn!(error = %e, "Failed to write to user");
 
                             return;
                         }
@@ -227,9 +387,16 @@ pub async fn handle_request1(routes: Arc<Routes>, mut stream: TcpStream, addr: A
                 },
             }
         },
-        Err(_e) => {
-            #[cfg(feature = "debug_light")]
-            println!("<<< [SUB:{thread_id}] Failed to read from user with error {_e}");
+        Err(FrameError::TooLarge(len)) => {
+            warn!(bytes = len, "User sent frame, which is too large");
+
+            let resp = handle_bad_frame(&routes, state.clone(), FrameError::TooLarge(len).to_string()).await;
+            if let Err(e) = write_frame(reader.get_mut(), &codec, &resp).await {
+                warn!(error = %e, "Failed to write to user");
+            }
+        },
+        Err(e) => {
+            warn!(error = %e, "Failed to read from user");
 
             return;
         }
@@ -238,12 +405,105 @@ pub async fn handle_request1(routes: Arc<Routes>, mut stream: TcpStream, addr: A
 
 }
 
-/// This function is designed to make it more clear, that AfterConnect is used.
-pub async fn handle_wrapper(routes: Arc<Routes>, stream: TcpStream, state: Arc<Mutex<State>>, addr: Arc<SocketAddr>, br_tx_sub: BrReceiver<[u8; 512]>, mp_tx_sub: MpscSender<[u8; 512]>) {
-    handle_request1(routes, stream, addr, state.clone(), br_tx_sub, mp_tx_sub).await;
+// This function writes the last messages from the `MessageStore` (or the ones since `<Since@...>`) right after the Handshake.
+// Live messages that were queued before it are dropped, they are in the store already, so nothing is seen twice
+// (almost, message that is saved right now could be both replayed and broadcast, `<Id@...>` tells that).
+// Returns false, if connection is closed.
+pub(crate) async fn replay<C: Connection>(reader: &mut FrameReader<C>, codec: &FrameCodec, state: &Arc<Mutex<State>>, br_tx_sub: &mut BrReceiver<Bytes>) -> bool {
+    let locked = state.lock().await;
+    let version = locked.version;
+    let since = locked.varmap.get::<Since>().map(|val| val.0);
+    let (store, count) = {
+        let app = locked.app.lock().await;
+        (app.message_store.clone(), app.replay)
+    };
+    drop(locked);
+
+    let Some(store) = store.filter(|_| count > 0) else {
+        return true;
+    };
+
+    *br_tx_sub = br_tx_sub.resubscribe();
+
+    let query = HistoryQuery {
+        since,
+        ..HistoryQuery::new(count)
+    };
 
+    let messages = match store.query(&query).await {
+        Ok(val) => val,
+        Err(e) => {
+            warn!(error = %e, "Failed to read messages for the replay");
+
+            return true;
+        }
+    };
+
+    for message in messages {
+        // Sender could speak other version, same as with the live messages
+        let Some(bytes) = message.response().and_then(|mut res| {
+            res.version = version;
+            res.as_bytes().ok()
+        }) else {
+            continue;
+        };
+
+        if let Ok(0) | Err(_) = write_frame(reader.get_mut(), codec, &bytes).await {
+            return false;
+        }
+    }
+
+    true
+}
+
+// This function writes the messages `History` middleware left in the state, they go right before its answer.
+// Returns false, if connection is closed.
+pub(crate) async fn write_page<C: Connection>(reader: &mut FrameReader<C>, codec: &FrameCodec, state: &Arc<Mutex<State>>) -> bool {
+    let mut locked = state.lock().await;
+    let page = locked.varmap.get::<HistoryPage>().cloned();
+    locked.varmap.remove::<HistoryPage>();
+    drop(locked);
+
+    for bytes in page.unwrap_or_default().0 {
+        if let Ok(0) | Err(_) = write_frame(reader.get_mut(), codec, &bytes).await {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// This function is designed to make it more clear, that AfterConnect is used.
+pub(crate) async fn handle_wrapper<C: Connection>(stream: C, state: Arc<Mutex<State>>, context: ConnectionContext) {
+    // Everything about the connection is inside of its span, `user` is there after the Bind or the Handshake
     let locked = state.lock().await;
-    if let Some(after) = locked.after.as_deref() {
+    let id = locked.id.map(|val| val.0);
+
+    // Bytes in and out are counted for the metrics
+    let stream = Metered::new(stream, locked.metrics.clone());
+    drop(locked);
+
+    let span = info_span!("connection", id, addr = %context.addr, user = field::Empty);
+
+    async move {
+        handle_request1(stream, state.clone(), context).await;
+
+        finish_connection(state).await;
+    }.instrument(span).await
+}
+
+/// Cleans up after the connection and runs AfterConnect. Router calls it on shutdown too, for connections it had to abort.
+pub async fn finish_connection(state: Arc<Mutex<State>>) {
+    // Rooms are dropped only when everyone left them, so leaving on disconnect.
+    // Also user is not reachable for the direct messages anymore.
+    let mut locked = state.lock().await;
+    locked.disconnect().await;
+
+    // Lock is dropped, so AfterConnect could lock the state itself
+    let after = locked.after.clone();
+    drop(locked);
+
+    if let Some(after) = after.as_deref() {
         after.execute(state.clone()).await;
     }
 }
\ No newline at end of file
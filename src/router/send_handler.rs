@@ -1,25 +1,37 @@
+use crate::protocol::parser::peek_version;
 use crate::protocol::request::{RawRequest, Version};
-use crate::protocol::response::ResponseCode;
+use crate::protocol::response::{Response, ResponseBuilder, ResponseCode};
+use crate::protocol::frame::{write_frame, FrameError, FrameReader};
+use bytes::Bytes;
 use tokio::select;
 use tokio::sync::Mutex;
-use tokio::task::Id;
-use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
-use tokio::sync::{mpsc::UnboundedSender as MpscSender, broadcast::Receiver as BrReceiver};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{mpsc::UnboundedSender as MpscSender, mpsc::UnboundedReceiver, broadcast::Receiver as BrReceiver};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use crate::protocol::request::Method;
-use crate::protocol::response::ResponseBuilder;
+use tracing::{info, trace, warn, Instrument};
 
-use super::{Routes, State};
+use super::{Connection, Outbound, Routes, State, request_handler::{check_version, handle_bad_frame, method_error, request_span, run_middleware, write_page}};
 
 /// This function is designed to be used in the context of Handshake
 /// I dont really know what to write here, but TODO: finish commenting this struct
-pub async fn handle_send(mut stream: TcpStream, routes: Arc<Routes>, addr: Arc<SocketAddr>, state: Arc<Mutex<State>>, mut br_tx_sub: BrReceiver<[u8; 512]>, mp_tx_sub: MpscSender<[u8; 512]>, _thread_id: Id) {
-    let mut buf = [0u8; 512];
+pub async fn handle_send<C: Connection>(mut stream: FrameReader<C>, routes: Arc<Routes>, addr: Arc<SocketAddr>, state: Arc<Mutex<State>>, mut br_tx_sub: BrReceiver<Bytes>, mp_tx_sub: MpscSender<Bytes>, mut inbox: UnboundedReceiver<Outbound>) {
+    let codec = *stream.codec();
+
+    // Inbox is the way to this user only (rooms, direct messages and pushes are delivered through it)
+    let mut locked = state.lock().await;
+    locked.connect().await;
+
+    // Version is set by the Handshake, and stays the same for the connection
+    let version = locked.version;
+    let metrics = locked.metrics.clone();
+    drop(locked);
 
     // Making infinit loop, because right now we are waiting for the live connected device
     loop {
-        let reader = stream.read(&mut buf);
+        // read_frame() is cancel safe, so if listener wins, half-read frame is still there
+        let reader = stream.read_frame();
         let listener = br_tx_sub.recv();
 
         select! {
@@ -27,133 +39,206 @@ pub async fn handle_send(mut stream: TcpStream, routes: Arc<Routes>, addr: Arc<S
             // (if is very clear, but still want to point it out)
             val = reader => {
                 match val {
-                    Ok(0) => {
-                        #[cfg(feature = "debug_light")]
-                        println!(">>> [SUB:{_thread_id}] Connection closed");
+                    Ok(None) => {
+                        info!("Connection closed");
 
                         return;
                     },
-                    Ok(_val) => {
-                        #[cfg(feature = "debug_full")]
-                        println!("--> [SUB:{_thread_id}] Read {_val} bytes from the user");
+                    Ok(Some(req_bytes)) => {
+                        trace!(bytes = req_bytes.len(), "Read bytes from the user");
 
                         let raw_req = RawRequest {
-                            bytes: buf,
+                            bytes: req_bytes,
                             addr: addr.clone(),
                         };
                         
                         // Initiall working with the request
                         let req_res = routes.starting_bytesware.bytesware(state.clone(), raw_req).await;    
 
+                        let req_res = match req_res {
+                            Ok(req) => check_version(&routes, state.clone(), req).await,
+                            Err(res) => Err(res),
+                        };
+
                         let req = match req_res {
                             Ok(val) => Ok(val),
                             Err(res) => {
-                                #[cfg(feature = "debug_light")]
-                                println!("<<< [SUB:{_thread_id}] Failed to get parse request via bytesware");
+                                warn!("Failed to get parse request via bytesware");
                                 
-                                let res = routes.send.1.bytesware(state.clone(), Err(res)).await;
+                                let res = routes.fallback().bytesware(state.clone(), Err(res)).await;
 
                                 Err(res.err().unwrap())
                             }
                         };
 
-                        
-                        match req {
-                            Ok(req) if req.method == Method::Send => {
-                                let second = routes.send.0.middleware(req, state.clone()).await;
-                                let res = routes.send.1.bytesware(state.clone(), second).await;
-                                match res {
-                                    Ok(val) => {
-                                        match mp_tx_sub.send(val) {
-                                            Ok(_) => {
-                                                #[cfg(feature = "debug_full")]
-                                                println!("<0> [SUB:{_thread_id}] Sent bytes via the channel MPSC");
-                                            },
-                                            Err(_e) => {
-                                                #[cfg(feature = "debug_full")]
-                                                println!(">0< [SUB:{_thread_id}] Failed to write bytes to the channel with error {_e}");
+                        // Send, rooms, Unbind, History and custom methods are the only ones during handshake.
+                        let route = match &req {
+                            Ok(req) if matches!(req.method, Method::Send | Method::Join | Method::Leave | Method::Unbind | Method::History | Method::Custom(_)) => routes.get(&req.method),
+                            _ => None,
+                        };
+
+                        match (req, route) {
+                            (Ok(req), Some(route)) => {
+                                // Join / Leave / Unbind / History are answered only to the user.
+                                // Everything else goes to everyone, or only to the user from `<To@...>`,
+                                // or only to the room from `<Room@...>` (`To` wins, if there are both). Err only back to the user.
+                                let to_everyone = !matches!(req.method, Method::Join | Method::Leave | Method::Unbind | Method::History);
+                                let to = req.custom.get("To").cloned().filter(|_| to_everyone);
+                                let room = req.custom.get("Room").cloned().filter(|_| to_everyone && to.is_none());
+
+                                // Only messages are saved, not the custom methods (and not direct ones)
+                                let save = req.method == Method::Send && to.is_none();
+
+                                // User can write only to the rooms they joined
+                                let joined = match &room {
+                                    Some(room) => state.lock().await.rooms.contains_key(room),
+                                    None => true,
+                                };
+
+                                let res = if joined {
+                                    let span = request_span(&req);
+                                    async {
+                                        let second = run_middleware(route, req, state.clone()).await;
+                                        route.1.bytesware(state.clone(), second).await
+                                    }.instrument(span).await
+                                } else {
+                                    let response = ResponseBuilder::new()
+                                        .version(req.version)
+                                        .code(ResponseCode::Error)
+                                        .reason(format!("not in the room `{0}`", room.as_deref().unwrap_or_default()))
+                                        .build()
+                                        .unwrap();
+                                    metrics.request(&req.method, &response.code);
+
+                                    route.1.bytesware(state.clone(), Err(response)).await
+                                };
+
+                                // Whatever should be written back to the user
+                                let back = match res {
+                                    Ok(val) if to_everyone => {
+                                        if let Some(to) = to {
+                                            let locked = state.lock().await;
+                                            let to_self = locked.varmap.get::<String>() == Some(&to);
+                                            let delivered = locked.app.lock().await.send_to(&to, val.clone());
+                                            drop(locked);
+
+                                            trace!(to = %to, delivered, "Sent bytes directly to the user");
+
+                                            // Sender gets the copy of the message, or why it wasnt delivered
+                                            if !delivered {
+                                                let response = ResponseBuilder::new()
+                                                    .version(version)
+                                                    .code(ResponseCode::Undeliverable)
+                                                    .reason(format!("user `{to}` is not connected"))
+                                                    .build()
+                                                    .unwrap();
+
+                                                match route.1.bytesware(state.clone(), Err(response)).await {
+                                                    Ok(val) => Some(val),
+                                                    Err(val) => Some(val),
+                                                }
+                                            } else if to_self {
+                                                None
+                                            } else {
+                                                Some(val)
                                             }
-                                        };
+                                        } else if let Some(room) = room {
+                                            let val = if save { store_message(&state, Some(&room), val).await } else { val };
+                                            let published = state.lock().await.app.lock().await.publish(&room, val);
+
+                                            trace!(room = %room, published, "Sent bytes to the room");
+
+                                            None
+                                        } else {
+                                            let val = if save { store_message(&state, None, val).await } else { val };
+                                            match mp_tx_sub.send(val) {
+                                                Ok(_) => {
+                                                    trace!("Sent bytes via the channel MPSC");
+                                                },
+                                                Err(e) => {
+                                                    trace!(error = %e, "Failed to write bytes to the channel");
+                                                }
+                                            };
+
+                                            None
+                                        }
                                     },
-                                    Err(val) => {
-                                        match stream.write(&val).await {
-                                            Ok(0) => {
-                                                #[cfg(feature = "debug_light")]
-                                                println!(">>> [SUB:{_thread_id}] Connection closed");
-
-                                                return;
-                                            },
-                                            Ok(_val) => {
-                                                #[cfg(feature = "debug_full")]
-                                                println!("--> [SUB:{_thread_id}] Wrote {_val} bytes to user");
-
-                                                continue;
-                                            },
-                                            Err(_e) => {
-                                                #[cfg(feature = "debug_light")]
-                                                println!("<<< [SUB:{_thread_id}] Failed to send error response to user with error {_e}");
-
-                                                continue;
-                                            }
+                                    Ok(val) | Err(val) => Some(val),
+                                };
+
+                                // History page goes before its answer
+                                if !write_page(&mut stream, &codec, &state).await {
+                                    return;
+                                }
+
+                                if let Some(val) = back {
+                                    match write_frame(stream.get_mut(), &codec, &val).await {
+                                        Ok(0) => {
+                                            info!("Connection closed");
+
+                                            return;
+                                        },
+                                        Ok(val) => {
+                                            trace!(bytes = val, "Wrote bytes to user");
+
+                                            continue;
+                                        },
+                                        Err(e) => {
+                                            warn!(error = %e, "Failed to send error response to user");
+
+                                            continue;
                                         }
                                     }
-                                } 
+                                }
                             },
-                            Ok(_) => {
-                                #[cfg(feature = "debug_light")]
-                                println!("--> [SUB:{_thread_id}] User cant send this type of requests during handshake");
+                            (Ok(req), None) => {
+                                warn!("User cant send this type of requests during handshake");
 
-                                let response = ResponseBuilder::new()
-                                    .version(Version::CHAT10)
-                                    .code(ResponseCode::Error)
-                                    .build()
-                                    .unwrap();
+                                // Methods that are only not allowed here are still counted by the name
+                                let res = method_error(&req);
+                                match routes.get(&req.method) {
+                                    Some(_) => metrics.request(&req.method, &res.code),
+                                    None => metrics.unrouted(&res.code),
+                                }
 
-                                let res = routes.send.1.bytesware(state.clone(), Err(response)).await;
+                                let res = routes.fallback().bytesware(state.clone(), Err(res)).await;
 
-                                // Unwrap is fine, because we defined response as Err 2 lines above
-                                match stream.write(&res.err().unwrap()).await {
+                                // Unwrap is fine, because we defined response as Err
+                                match write_frame(stream.get_mut(), &codec, &res.err().unwrap()).await {
                                     Ok(0) => {
-                                        #[cfg(feature = "debug_light")]
-                                        println!(">>> [SUB:{_thread_id}] Connection is closed");
+                                        info!("Connection is closed");
 
                                         return;
                                     },
-                                    Ok(_val) => {
-                                        #[cfg(feature = "debug_full")]
-                                        println!("--> [SUB:{_thread_id}] Wrote {_val} bytes to user with error");
+                                    Ok(val) => {
+                                        trace!(bytes = val, "Wrote bytes to user with error");
 
                                         continue;
                                     },
-                                    Err(_e) => {
-                                        #[cfg(feature = "debug_light")]
-                                        println!("<<< [SUB:{_thread_id}] Failed to write to user with error {_e}");
+                                    Err(e) => {
+                                        warn!(error = %e, "Failed to write to user");
                                         
                                         continue;
                                     }
                                 }
                                     
                             },
-                            Err(res) => {
-                                #[cfg(feature = "debug_light")]
-                                println!("<<< [SUB:{_thread_id}] Failed to get request from the starting_bytesware");
+                            (Err(res), _) => {
+                                warn!("Failed to get request from the starting_bytesware");
 
-                                match stream.write(&res).await {
+                                match write_frame(stream.get_mut(), &codec, &res).await {
                                     Ok(0) => {
-                                        #[cfg(feature = "debug_light")]
-                                        println!("<<< [SUB:{_thread_id}] Connection is closed before it should be");
+                                        warn!("Connection is closed before it should be");
 
                                         return;
                                     },
-                                    Ok(_val) => {
-                                        #[cfg(feature = "debug_full")]
-                                        println!("--> [SUB:{_thread_id}] Wrote {_val} bytes to the user with error");
+                                    Ok(val) => {
+                                        trace!(bytes = val, "Wrote bytes to the user with error");
 
                                         continue;
                                     },
-                                    Err(_e) => {
-                                        #[cfg(feature = "debug_light")]
-                                        println!("<<< [SUB:{_thread_id}] Failed to write to user with error {_e}");
+                                    Err(e) => {
+                                        warn!(error = %e, "Failed to write to user");
                                         
                                         continue;
                                     }
@@ -161,39 +246,93 @@ pub async fn handle_send(mut stream: TcpStream, routes: Arc<Routes>, addr: Arc<S
                             }
                         }
                     },
-                    Err(_e) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [SUB:{_thread_id}] Failed to write to user with error: {_e}");
+                    Err(FrameError::TooLarge(len)) => {
+                        warn!(bytes = len, "User sent frame, which is too large");
+
+                        // We cant find where the next frame starts, so connection is closed after the response
+                        let resp = handle_bad_frame(&routes, state.clone(), FrameError::TooLarge(len).to_string()).await;
+                        if let Err(e) = write_frame(stream.get_mut(), &codec, &resp).await {
+                            warn!(error = %e, "Failed to write to user");
+                        }
+
+                        return;
+                    },
+                    Err(e) => {
+                        warn!(error = %e, "Failed to read from user");
+
+                        return;
+                    }
+                }
+            },
+            Some(val) = inbox.recv() => {
+                // Same as the listener, but only for this user
+                let val = match val {
+                    Outbound::Frame(val) => val,
+                    Outbound::Close => {
+                        info!("Connection closed by the server");
+
+                        return;
+                    }
+                };
+
+                let val = match peek_version(&val) {
+                    Some(sent) if sent != version => reencode(val, version),
+                    _ => val,
+                };
+
+                match write_frame(stream.get_mut(), &codec, &val).await {
+                    Ok(0) => {
+                        info!("Connection is closed");
+
+                        return;
+                    },
+                    Ok(val) => {
+                        trace!(bytes = val, "Wrote bytes from inbox to user");
+
+                        continue;
+                    },
+                    Err(e)  => {
+                        warn!(error = %e, "Failed to write bytes to user");
+
+                        return;
                     }
                 }
             },
             val = listener => {
                 match val {
                     Ok(val) => {
-                        match stream.write(&val).await {
+                        // Sender could speak other version, so message is rewritten in the one this client uses
+                        let val = match peek_version(&val) {
+                            Some(sent) if sent != version => reencode(val, version),
+                            _ => val,
+                        };
+
+                        match write_frame(stream.get_mut(), &codec, &val).await {
                             Ok(0) => {
-                                #[cfg(feature = "debug_light")]
-                                println!(">>> [SUB:{_thread_id}] Connection is closed");
+                                info!("Connection is closed");
 
                                 return;
                             },
-                            Ok(_val) => {
-                                #[cfg(feature = "debug_full")]
-                                println!("--> [SUB:{_thread_id}] Wrote {_val} bytes to user");
+                            Ok(val) => {
+                                trace!(bytes = val, "Wrote bytes to user");
                                 
                                 continue;
                             },
-                            Err(_e)  => {
-                                #[cfg(feature = "debug_light")]
-                                println!("<<< [SUB:{_thread_id}] Failed to write bytes to user with error: {_e}");
+                            Err(e)  => {
+                                warn!(error = %e, "Failed to write bytes to user");
                                 
                                 return;
                             }
                         }
                     },
-                    Err(_e) => {
-                        #[cfg(feature = "debug_light")]
-                        println!("<<< [SUB:{_thread_id}] Failed to read from channel with error {_e}");
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "User is too slow, missed messages from the channel");
+                        metrics.lagged(skipped);
+
+                        continue;
+                    },
+                    Err(e) => {
+                        warn!(error = %e, "Failed to read from channel");
 
                         continue;
                     }
@@ -201,4 +340,33 @@ pub async fn handle_send(mut stream: TcpStream, routes: Arc<Routes>, addr: Arc<S
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// Saves the message in the `MessageStore`, if there is one. Returned bytes have `<Id@...>` from the store,
+// so the message has the same id live and in the history.
+async fn store_message(state: &Arc<Mutex<State>>, room: Option<&str>, val: Bytes) -> Bytes {
+    let store = state.lock().await.app.lock().await.message_store.clone();
+    let Some(store) = store else {
+        return val;
+    };
+
+    match store.append(room, val.clone()).await {
+        Ok(message) => message.response().and_then(|res| res.as_bytes().ok()).unwrap_or(val),
+        Err(e) => {
+            warn!(error = %e, "Failed to save the message");
+
+            val
+        }
+    }
+}
+
+// Rewrites broadcasted response in the `version`. If it cant be parsed, it is sent as it is.
+fn reencode(bytes: Bytes, version: Version) -> Bytes {
+    let mut res = match Response::from_bytes(&bytes) {
+        Ok(val) => val,
+        Err(_) => return bytes,
+    };
+
+    res.version = version;
+    res.as_bytes().unwrap_or(bytes)
+}
@@ -0,0 +1,104 @@
+//! ## Shutdown
+//!
+//! Everything that is needed to stop the [`Router`] without killing the process.
+//!
+//! [`Router`]: crate::router::Router
+use crate::protocol::request::Version;
+use crate::protocol::response::{Response, ResponseBuilder, ResponseCode};
+use tokio::sync::{watch, Mutex};
+use tokio::task::{Id, JoinSet};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use super::{App, State, request_handler::finish_connection};
+
+/// How long [`Router`] waits for the connections to finish on shutdown, if not set.
+///
+/// [`Router`]: crate::router::Router
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// ## `RouterHandle`
+///
+/// Handle to the running [`Router`], it could be cloned and moved anywhere. Get it via [`Router::handle`].
+///
+/// ## Example
+/// ```
+/// let router = RouterBuilder::new().build();
+/// let handle = router.handle();
+///
+/// tokio::spawn(async move {
+///     tokio::signal::ctrl_c().await.unwrap();
+///     handle.shutdown();
+/// });
+///
+/// router.run().await.unwrap(); // Returns after everyone got the goodbye
+/// ```
+///
+/// [`Router`]: crate::router::Router
+/// [`Router::handle`]: crate::router::Router::handle
+#[derive(Debug, Clone)]
+pub struct RouterHandle {
+    pub(crate) shutdown: Arc<watch::Sender<bool>>,
+}
+
+impl RouterHandle {
+    /// Asks the [`Router`] to stop. It stops accepting connections right away, but `run()` returns only
+    /// after every connection is closed. Calling it before `run()` makes `run()` return right away.
+    ///
+    /// [`Router`]: crate::router::Router
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Returns true, if shutdown was asked for.
+    pub fn is_shutdown(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+}
+
+/// Response every connection gets on shutdown, if not set: `<Code@30>` with `<Reason@'server is shutting down'>`.
+/// It is rewritten in the version every client speaks.
+pub fn default_goodbye() -> Response {
+    ResponseBuilder::new()
+        .version(Version::LATEST)
+        .code(ResponseCode::Error)
+        .reason("server is shutting down".to_string())
+        .build()
+        .expect("Default goodbye is always valid")
+}
+
+// Sends goodbye to everyone and asks every connection to close. Connections finish the request they are
+// in the middle of first. Ones that didnt make it in `timeout` are aborted, and AfterConnect is ran for them here.
+pub(crate) async fn close_connections(app: Arc<Mutex<App>>, goodbye: &Response, timeout: Duration, mut connections: JoinSet<()>, mut states: HashMap<Id, Arc<Mutex<State>>>) {
+    let goodbye = goodbye.as_bytes().ok();
+
+    let locked = app.lock().await;
+    for id in locked.connections.keys() {
+        if let Some(bytes) = &goodbye {
+            locked.push_bytes_to(*id, bytes.clone());
+        }
+        locked.close(*id);
+    }
+    drop(locked);
+
+    let drained = tokio::time::timeout(timeout, async {
+        while let Some(done) = connections.join_next_with_id().await {
+            let id = match done {
+                Ok((id, _)) => id,
+                Err(e) => e.id(),
+            };
+            states.remove(&id);
+        }
+    }).await;
+
+    if drained.is_err() {
+        warn!(connections = states.len(), "Connections didnt close in time, aborting them");
+
+        connections.shutdown().await;
+        for state in states.into_values() {
+            finish_connection(state).await;
+        }
+    }
+}
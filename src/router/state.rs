@@ -1,7 +1,10 @@
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast::error::RecvError, mpsc::{self, UnboundedReceiver, UnboundedSender}, Mutex};
+use tokio::task::AbortHandle;
+use std::collections::HashMap;
 use std::sync::Arc;
-use crate::protocol::{Varmap, wares::AfterConnect};
-use super::App;
+use crate::protocol::{Varmap, request::Version, wares::AfterConnect};
+use crate::protocol::response::Response;
+use super::{App, ConnectionId, Outbound, RoomError, RouterMetrics};
 
 /// `State` is the struct that repesenets application state. Dont confuse your self with App.
 /// `App` is the state of the client. `State` is the state of the whole application
@@ -24,7 +27,7 @@ use super::App;
 /// }
 /// 
 /// // Somewhere inside of the EndingBytesware
-/// async fn bytesware(&self, state: Arc<Mutex<State>>, res: Result<Response, Response>) -> Result<[u8; 512], [u8; 512]> {
+/// async fn bytesware(&self, state: Arc<Mutex<State>>, res: Result<Response, Response>) -> Result<Bytes, Bytes> {
 ///     let locked_state = state.lock().await;
 /// 
 ///     // Print final timer!
@@ -38,11 +41,20 @@ use super::App;
 /// 
 /// **Attention** `String` field is reserved for the `username`.
 /// 
+/// `id` and `inbox` are set by the router as soon as the connection is accepted (see [`State::attach`]), everything sent
+/// to the `inbox` is written to the user. Rooms user joined via [`State::join`], direct messages and everything
+/// pushed via [`State::push`] or [`App::push_to`] are delivered through it.
+/// 
 #[derive(Debug, Clone)]
 pub struct State {
     pub app: Arc<Mutex<App>>,                       // Linking app state
     pub after: Arc<Option<Box<dyn AfterConnect>>>,  // Saving After function to not forget it
     pub varmap: Varmap,                             // Connection-long Varmap
+    pub version: Version,                           // Version client has spoken last time, router answers in it
+    pub id: Option<ConnectionId>,                   // Id of the connection in the App, None before it is attached
+    pub inbox: Option<UnboundedSender<Outbound>>,   // Direct way to the user, None before it is attached
+    pub rooms: HashMap<String, AbortHandle>,        // Joined rooms and their forwarding tasks
+    pub metrics: Arc<RouterMetrics>,                // Metrics of the router, set with the `id`
 }
 
 impl State {
@@ -58,6 +70,149 @@ impl State {
             app,
             after,
             varmap: Varmap::new(),
+            version: Version::CHAT10,
+            id: None,
+            inbox: None,
+            rooms: HashMap::new(),
+            metrics: Arc::new(RouterMetrics::new()),
+        }
+    }
+
+    /// Registers the connection in the [`App`], sets `id`, `inbox` and `metrics`. Returned reciever gets everything that should
+    /// be written to the user. Router calls it right after the connection is accepted, before `BeforeConnect`.
+    pub async fn attach(&mut self) -> UnboundedReceiver<Outbound> {
+        let (inbox, outbound) = mpsc::unbounded_channel();
+
+        let mut app = self.app.lock().await;
+        self.id = Some(app.add_connection(inbox.clone()));
+        self.metrics = app.metrics.clone();
+        drop(app);
+
+        self.metrics.connected();
+        self.inbox = Some(inbox);
+        outbound
+    }
+
+    /// Marks the user as online in the [`App`] by the name, so direct messages could reach the user.
+    /// Router calls it right after the `Handshake`.
+    pub async fn connect(&mut self) {
+        if let (Some(name), Some(id)) = (self.varmap.get::<String>(), self.id) {
+            self.app.lock().await.connect(name, id);
+        }
+    }
+
+    /// Leaves every room and removes the connection from the [`App`]. Router calls it when connection is closed.
+    pub async fn disconnect(&mut self) {
+        self.leave_all().await;
+
+        if let Some(id) = self.id.take() {
+            self.app.lock().await.remove_connection(id);
+            self.metrics.disconnected();
+        }
+        self.inbox = None;
+    }
+
+    /// Sends `response` to this connection, it is written to the user as soon as possible, between the requests.
+    /// Returns false, if connection is not attached or response couldnt be turned into bytes.
+    /// 
+    /// ## Example
+    /// ```
+    /// // Somewhere inside of the BeforeConnect
+    /// let notice = ResponseBuilder::new()
+    ///     .version(Version::CHAT11)
+    ///     .code(ResponseCode::OK)
+    ///     .custom_insert("Notice".to_string(), "Welcome!".to_string())
+    ///     .build()
+    ///     .unwrap();
+    /// 
+    /// state.lock().await.push(notice);
+    /// ```
+    pub fn push(&self, response: Response) -> bool {
+        match (response.as_bytes(), &self.inbox) {
+            (Ok(bytes), Some(inbox)) => inbox.send(Outbound::Frame(bytes)).is_ok(),
+            _ => false,
+        }
+    }
+
+    /// Closes this connection, after everything that was pushed before is written.
+    pub fn close(&self) -> bool {
+        match &self.inbox {
+            Some(inbox) => inbox.send(Outbound::Close).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Joins the `room`, messages published there are sent to the user from now on.
+    /// Joining the room user is already in does nothing.
+    /// 
+    /// ## Example
+    /// ```
+    /// // Somewhere inside of the Middleware
+    /// let mut state = state.lock().await;
+    /// 
+    /// if let Err(e) = state.join("general").await {
+    ///     println!("Couldnt join: {e:?}");
+    /// }
+    /// ```
+    pub async fn join(&mut self, room: &str) -> Result<(), RoomError> {
+        if room.is_empty() {
+            return Err(RoomError::InvalidRoom);
+        }
+
+        let (name, inbox) = match (self.varmap.get::<String>(), &self.inbox) {
+            (Some(name), Some(inbox)) => (name.clone(), inbox.clone()),
+            _ => return Err(RoomError::NotHandshaked),
+        };
+
+        if self.rooms.contains_key(room) {
+            return Ok(());
+        }
+
+        let mut reciever = self.app.lock().await.join(room, &name);
+        let metrics = self.metrics.clone();
+
+        // Forwarding room messages to the user, until the room is gone or user left it
+        let handle = tokio::spawn(async move {
+            loop {
+                match reciever.recv().await {
+                    Ok(bytes) => {
+                        if inbox.send(Outbound::Frame(bytes)).is_err() {
+                            return;
+                        }
+                    },
+                    Err(RecvError::Lagged(skipped)) => {
+                        metrics.lagged(skipped);
+                        continue;
+                    },
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+
+        self.rooms.insert(room.to_string(), handle.abort_handle());
+        Ok(())
+    }
+
+    /// Leaves the `room`. Returns false, if user wasnt in it.
+    pub async fn leave(&mut self, room: &str) -> bool {
+        let Some(handle) = self.rooms.remove(room) else {
+            return false;
+        };
+        handle.abort();
+
+        if let Some(name) = self.varmap.get::<String>() {
+            self.app.lock().await.leave(room, name);
+        }
+
+        true
+    }
+
+    /// Leaves every room.
+    pub async fn leave_all(&mut self) {
+        let rooms: Vec<String> = self.rooms.keys().cloned().collect();
+
+        for room in rooms {
+            self.leave(&room).await;
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,365 @@
+//! ## `MessageStore`
+//!
+//! Messages from `Send` are broadcast once and are gone, so anyone who connects later sees nothing. With the
+//! [`MessageStore`] every message to everyone or to the room is saved, gets its `<Id@...>`, and the last ones are
+//! written to the user right after the `Handshake` (only the ones to everyone, rooms are not joined yet).
+//! Direct messages are not saved. Older messages are paged through with the `History` method.
+//!
+//! There are two of them out of the box: [`MemoryStore`] (last N messages, gone on restart) and [`FileStore`]
+//! (every message is appended to the file).
+//!
+//! ## Example
+//! ```
+//! let router = RouterBuilder::new()
+//!     .message_store(Box::new(FileStore::open("messages.jsonl").await?))
+//!     .replay(100)    // Last 100 messages on connect
+//!     .build();
+//! ```
+//!
+//! Client could ask only for what it missed with `<Since@...>` in the `Handshake`, same format as `<Time@...>`:
+//! ```txt
+//! <CHAT \ 1.1>
+//! <Method@Handshake>
+//! <Authorization@'...'>
+//! <Since@'2025-03-01 12:00:00'>
+//! ```
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::Mutex;
+use tracing::warn;
+use crate::protocol::response::Response;
+
+/// How many messages are replayed after the `Handshake`, if `RouterBuilder::replay` wasnt set.
+pub const DEFAULT_REPLAY: usize = 50;
+
+/// Most messages one `History` request gets, bigger `<Limit@...>` is cut to it.
+pub const MAX_HISTORY_LIMIT: usize = 200;
+
+/// How many messages [`MemoryStore::default`] keeps, and how many [`FileStore`] keeps in memory.
+pub const DEFAULT_STORE_CAPACITY: usize = 1024;
+
+/// ## `MessageStore`
+///
+/// Somewhere messages are kept. Store gives the ids, they only go up.
+///
+/// ## How it looks like in the human way
+/// ```
+/// #[async_trait]
+/// pub trait MessageStore: Debug + Send + Sync {
+///     async fn append(&self, room: Option<&str>, bytes: Bytes) -> io::Result<StoredMessage>;
+///     async fn query(&self, query: &HistoryQuery) -> io::Result<Vec<StoredMessage>>;
+/// }
+/// ```
+#[async_trait]
+pub trait MessageStore: Debug + Send + Sync {
+    /// Saves the message (response as it was broadcast), `room` is None for the messages to everyone.
+    async fn append(&self, room: Option<&str>, bytes: Bytes) -> io::Result<StoredMessage>;
+
+    /// Newest messages that fit the `query`, oldest first.
+    async fn query(&self, query: &HistoryQuery) -> io::Result<Vec<StoredMessage>>;
+}
+
+/// ## `StoredMessage`
+///
+/// Message in the store. `bytes` are the response without the `<Id@...>`, it is added by [`StoredMessage::response`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredMessage {
+    pub id: u64,
+    pub time: DateTime<Utc>,        // When it was saved
+    pub room: Option<String>,
+    pub bytes: Bytes,
+}
+
+impl StoredMessage {
+    /// Message as the [`Response`] with `<Id@...>` in it. None if bytes couldnt be parsed.
+    pub fn response(&self) -> Option<Response> {
+        let mut response = Response::from_bytes(&self.bytes).ok()?;
+        response.custom.get_or_insert_with(Default::default).insert("Id".to_string(), self.id.to_string());
+
+        Some(response)
+    }
+
+    fn matches(&self, query: &HistoryQuery) -> bool {
+        self.room == query.room
+            && query.before.is_none_or(|before| self.id < before)
+            && query.since.is_none_or(|since| self.time >= since)
+    }
+}
+
+/// ## `HistoryQuery`
+///
+/// Which messages are wanted: of the `room` (None is messages to everyone), older than `before` id,
+/// not older than `since`, and no more than `limit` newest of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryQuery {
+    pub room: Option<String>,
+    pub before: Option<u64>,
+    pub since: Option<DateTime<Utc>>,
+    pub limit: usize,
+}
+
+impl HistoryQuery {
+    pub fn new(limit: usize) -> Self {
+        HistoryQuery {
+            room: None,
+            before: None,
+            since: None,
+            limit,
+        }
+    }
+}
+
+/// ## `Since`
+///
+/// `<Since@...>` from the `Handshake`. Router puts it into the connection `varmap`, so messages are replayed from it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Since(pub DateTime<Utc>);
+
+/// ## `HistoryPage`
+///
+/// Messages the `History` middleware found, ready to be written. Middleware puts it into the connection `varmap`,
+/// and router writes them right before the answer.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryPage(pub Vec<Bytes>);
+
+/// Parses the time in the same format as `<Time@...>` has (`2025-03-01 12:00:00`, UTC).
+pub fn parse_time(val: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(val, "%Y-%m-%d %H:%M:%S").ok().map(|val| Utc.from_utc_datetime(&val))
+}
+
+// Newest `limit` of the matching ones, oldest first
+fn select<'a>(messages: impl DoubleEndedIterator<Item = &'a StoredMessage>, query: &HistoryQuery) -> Vec<StoredMessage> {
+    let mut found: Vec<StoredMessage> = messages.rev().filter(|val| val.matches(query)).take(query.limit).cloned().collect();
+    found.reverse();
+    found
+}
+
+/// ## `MemoryStore`
+///
+/// Last `capacity` messages in memory (all rooms together), older ones are dropped. Everything is gone on restart.
+#[derive(Debug)]
+pub struct MemoryStore {
+    pub capacity: usize,
+    inner: Mutex<Ring>,
+}
+
+#[derive(Debug)]
+struct Ring {
+    messages: VecDeque<StoredMessage>,
+    next_id: u64,
+}
+
+impl Ring {
+    fn push(&mut self, message: StoredMessage, capacity: usize) {
+        self.next_id = self.next_id.max(message.id + 1);
+        self.messages.push_back(message);
+
+        while self.messages.len() > capacity {
+            self.messages.pop_front();
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn new(capacity: usize) -> Self {
+        MemoryStore {
+            capacity,
+            inner: Mutex::new(Ring {
+                messages: VecDeque::new(),
+                next_id: 1,
+            }),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_STORE_CAPACITY)
+    }
+}
+
+#[async_trait]
+impl MessageStore for MemoryStore {
+    async fn append(&self, room: Option<&str>, bytes: Bytes) -> io::Result<StoredMessage> {
+        let mut inner = self.inner.lock().await;
+        let message = StoredMessage {
+            id: inner.next_id,
+            time: Utc::now(),
+            room: room.map(str::to_string),
+            bytes,
+        };
+
+        inner.push(message.clone(), self.capacity);
+        Ok(message)
+    }
+
+    async fn query(&self, query: &HistoryQuery) -> io::Result<Vec<StoredMessage>> {
+        Ok(select(self.inner.lock().await.messages.iter(), query))
+    }
+}
+
+/// ## `FileStore`
+///
+/// Every message is appended to the file as the JSON line, nothing is ever rewritten, so it survives restarts.
+/// Last `DEFAULT_STORE_CAPACITY` messages are also kept in memory, file is read only for the ones older than that.
+///
+/// There is no index, so every `History` query that goes past the memory reads and parses the whole file again.
+/// It is fine for thousands of messages, not for millions (use your own [`MessageStore`] with the database for that).
+///
+/// Last line that was cut in the middle (router crashed during the append) is cut off the file on `open`,
+/// other lines that couldnt be parsed are skipped with the warning, and are left in the file.
+#[derive(Debug)]
+pub struct FileStore {
+    pub path: PathBuf,
+    file: Mutex<(tokio::fs::File, Ring)>,
+    total: AtomicUsize,     // Messages in the file
+}
+
+#[derive(Serialize, Deserialize)]
+struct Line {
+    id: u64,
+    time: DateTime<Utc>,
+    room: Option<String>,
+    message: String,
+}
+
+impl From<Line> for StoredMessage {
+    fn from(line: Line) -> Self {
+        StoredMessage {
+            id: line.id,
+            time: line.time,
+            room: line.room,
+            bytes: Bytes::from(line.message),
+        }
+    }
+}
+
+impl FileStore {
+    /// Opens the file, or creates it if there is none. Messages that are already there are kept, ids go on from them.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut ring = Ring {
+            messages: VecDeque::new(),
+            next_id: 1,
+        };
+
+        let mut total = 0;
+        let mut complete = 0;   // Where the last line with the newline ends
+        let mut cut = false;
+
+        if let Ok(file) = tokio::fs::File::open(&path).await {
+            let mut reader = BufReader::new(file);
+            let mut line = String::new();
+
+            loop {
+                line.clear();
+                let n = reader.read_line(&mut line).await?;
+                if n == 0 {
+                    break;
+                }
+
+                // Only the last line could be without the newline, it was never written to the end
+                if !line.ends_with('\n') {
+                    cut = true;
+                    break;
+                }
+
+                match parse_line(line.trim_end()) {
+                    Ok(message) => {
+                        ring.push(message, DEFAULT_STORE_CAPACITY);
+                        total += 1;
+                    },
+                    Err(e) => {
+                        warn!(path = %path.display(), offset = complete, error = %e, "Skipping message that couldnt be parsed");
+                    }
+                }
+                complete += n as u64;
+            }
+        }
+
+        let file = tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await?;
+
+        // Cut line is cut off, so the next message starts on its own line
+        if cut {
+            warn!(path = %path.display(), offset = complete, "Cutting off the message that was written only partly");
+            file.set_len(complete).await?;
+        }
+
+        Ok(FileStore {
+            path,
+            file: Mutex::new((file, ring)),
+            total: total.into(),
+        })
+    }
+
+    // Everything from the file, for the queries that go further than memory. Broken lines were warned about on `open`
+    async fn read_all(&self) -> io::Result<Vec<StoredMessage>> {
+        let file = tokio::fs::File::open(&self.path).await?;
+        let mut lines = BufReader::new(file).lines();
+
+        let mut messages = Vec::new();
+        while let Some(line) = lines.next_line().await? {
+            if let Ok(message) = parse_line(&line) {
+                messages.push(message);
+            }
+        }
+
+        Ok(messages)
+    }
+}
+
+fn parse_line(line: &str) -> io::Result<StoredMessage> {
+    serde_json::from_str::<Line>(line)
+        .map(StoredMessage::from)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[async_trait]
+impl MessageStore for FileStore {
+    async fn append(&self, room: Option<&str>, bytes: Bytes) -> io::Result<StoredMessage> {
+        let mut locked = self.file.lock().await;
+        let (file, ring) = &mut *locked;
+
+        let line = Line {
+            id: ring.next_id,
+            time: Utc::now(),
+            room: room.map(str::to_string),
+            message: String::from_utf8_lossy(&bytes).into_owned(),
+        };
+
+        let mut json = serde_json::to_vec(&line)?;
+        json.push(b'\n');
+        file.write_all(&json).await?;
+        file.flush().await?;
+
+        let message = StoredMessage::from(line);
+        ring.push(message.clone(), DEFAULT_STORE_CAPACITY);
+        self.total.fetch_add(1, Ordering::Relaxed);
+
+        Ok(message)
+    }
+
+    async fn query(&self, query: &HistoryQuery) -> io::Result<Vec<StoredMessage>> {
+        let locked = self.file.lock().await;
+        let found = select(locked.1.messages.iter(), query);
+
+        // Memory has everything that is needed, or everything there is, or everything since `since`
+        let oldest = locked.1.messages.front();
+        if found.len() >= query.limit
+            || locked.1.messages.len() >= self.total.load(Ordering::Relaxed)
+            || query.since.is_some_and(|since| oldest.is_some_and(|val| val.time < since)) {
+            return Ok(found);
+        }
+        drop(locked);
+
+        Ok(select(self.read_all().await?.iter(), query))
+    }
+}
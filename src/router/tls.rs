@@ -0,0 +1,141 @@
+//! ## TLS
+//!
+//! [`Router`] and [`Client`] could talk over TLS, so nothing (the `Authorization` token too) goes in cleartext.
+//! See [`RouterBuilder::tls`] and [`ClientBuilder::tls`].
+//!
+//! [`Router`]: crate::router::Router
+//! [`Client`]: crate::client::Client
+//! [`RouterBuilder::tls`]: crate::router::RouterBuilder::tls
+//! [`ClientBuilder::tls`]: crate::client::ClientBuilder::tls
+use async_trait::async_trait;
+use tokio::select;
+use tokio::task::JoinSet;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::{self, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use std::fmt::{Debug, Formatter};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use super::{Connection, Listener};
+
+/// How long the client has to finish the TLS handshake, before it is dropped.
+pub const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// ## `TlsIdentity`
+///
+/// Certificate chain and the private key [`Router`] presents to the clients.
+///
+/// [`Router`]: crate::router::Router
+pub struct TlsIdentity {
+    pub certs: Vec<CertificateDer<'static>>,
+    pub key: PrivateKeyDer<'static>,
+}
+
+impl TlsIdentity {
+    /// Creates acceptor from the identity. Fails, if the key doesnt match the certificate, or is not supported.
+    pub fn acceptor(&self) -> Result<TlsAcceptor, rustls::Error> {
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(self.certs.clone(), self.key.clone_key())?;
+
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+impl Clone for TlsIdentity {
+    fn clone(&self) -> Self {
+        TlsIdentity {
+            certs: self.certs.clone(),
+            key: self.key.clone_key(),
+        }
+    }
+}
+
+// Key is not printed, only the fact that it is there
+impl Debug for TlsIdentity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsIdentity")
+            .field("certs", &self.certs.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: Connection> Connection for tokio_rustls::server::TlsStream<C> {
+    fn keepalive(&self) -> std::io::Result<()> {
+        self.get_ref().0.keepalive()
+    }
+}
+
+impl<C: Connection> Connection for tokio_rustls::client::TlsStream<C> {
+    fn keepalive(&self) -> std::io::Result<()> {
+        self.get_ref().0.keepalive()
+    }
+}
+
+/// ## `TlsListener`
+///
+/// Wraps any [`Listener`] into TLS. Handshakes are done in the background, so slow client doesnt stop
+/// others from connecting. Ones that failed the handshake are dropped, router never sees them.
+pub struct TlsListener<L: Listener> {
+    inner: L,
+    acceptor: TlsAcceptor,
+    handshakes: JoinSet<Option<(tokio_rustls::server::TlsStream<L::Conn>, SocketAddr)>>,
+}
+
+impl<L: Listener> TlsListener<L> {
+    pub fn new(inner: L, acceptor: TlsAcceptor) -> Self {
+        TlsListener {
+            inner,
+            acceptor,
+            handshakes: JoinSet::new(),
+        }
+    }
+}
+
+impl<L: Listener> Debug for TlsListener<L> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsListener")
+            .field("inner", &self.inner)
+            .field("handshakes", &self.handshakes.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<L: Listener> Listener for TlsListener<L> {
+    type Conn = tokio_rustls::server::TlsStream<L::Conn>;
+
+    async fn accept(&mut self) -> std::io::Result<(Self::Conn, SocketAddr)> {
+        loop {
+            select! {
+                val = self.inner.accept() => {
+                    let (stream, addr) = val?;
+                    let acceptor = self.acceptor.clone();
+
+                    self.handshakes.spawn(async move {
+                        match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                            Ok(Ok(stream)) => Some((stream, addr)),
+                            _ => {
+                                warn!(%addr, "TLS handshake failed");
+
+                                None
+                            }
+                        }
+                    });
+                },
+                Some(done) = self.handshakes.join_next() => {
+                    if let Ok(Some(val)) = done {
+                        return Ok(val);
+                    }
+                },
+            }
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
@@ -0,0 +1,268 @@
+//! ## Transport
+//!
+//! What [`Router`] talks over. By default it is TCP, but anything that implements [`Listener`] would do:
+//! `UnixListener`, [`MemoryListener`] for tests, or the socket that was bound before (systemd socket activation).
+//!
+//! [`Router`]: crate::router::Router
+use crate::protocol::set_keepalive;
+use async_trait::async_trait;
+use tokio::io::{duplex, AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::select;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use std::fmt::Debug;
+use std::io::{Error, ErrorKind};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Address that is given to the [`Request`] for transports without the IP address (unix sockets, memory).
+///
+/// [`Request`]: crate::protocol::request::Request
+pub const UNSPECIFIED_ADDR: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
+
+/// ## `Connection`
+///
+/// One connection of the [`Listener`]. It is just a stream, that could be read and written.
+///
+/// ## How it looks like in the human way
+/// ```
+/// pub trait Connection: AsyncRead + AsyncWrite + Unpin + Send + 'static {
+///     fn keepalive(&self) -> std::io::Result<()> { Ok(()) }
+/// }
+/// ```
+pub trait Connection: AsyncRead + AsyncWrite + Unpin + Send + 'static {
+    /// Called right after the `Handshake`, connection is long-lived from now on. Does nothing by default.
+    fn keepalive(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Connection for TcpStream {
+    fn keepalive(&self) -> std::io::Result<()> {
+        set_keepalive(self)
+    }
+}
+
+impl Connection for DuplexStream {}
+
+#[cfg(unix)]
+impl Connection for tokio::net::UnixStream {}
+
+/// ## `Listener`
+///
+/// Source of the [`Connection`]'s for the [`Router`]. See [`Router::with_listener`].
+///
+/// ## How it looks like in the human way
+/// ```
+/// #[async_trait]
+/// pub trait Listener: Debug + Send + 'static {
+///     type Conn: Connection;
+///
+///     async fn accept(&mut self) -> std::io::Result<(Self::Conn, SocketAddr)>;
+///     fn local_addr(&self) -> std::io::Result<SocketAddr>;
+/// }
+/// ```
+///
+/// [`Router`]: crate::router::Router
+/// [`Router::with_listener`]: crate::router::Router::with_listener
+#[async_trait]
+pub trait Listener: Debug + Send + 'static {
+    type Conn: Connection;
+
+    /// Waits for the next connection. Address is [`UNSPECIFIED_ADDR`], if transport doesnt have one.
+    async fn accept(&mut self) -> std::io::Result<(Self::Conn, SocketAddr)>;
+
+    /// Address router listens to. It is [`UNSPECIFIED_ADDR`], if transport doesnt have one.
+    fn local_addr(&self) -> std::io::Result<SocketAddr>;
+}
+
+#[async_trait]
+impl Listener for TcpListener {
+    type Conn = TcpStream;
+
+    async fn accept(&mut self) -> std::io::Result<(TcpStream, SocketAddr)> {
+        TcpListener::accept(self).await
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        TcpListener::local_addr(self)
+    }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl Listener for tokio::net::UnixListener {
+    type Conn = tokio::net::UnixStream;
+
+    async fn accept(&mut self) -> std::io::Result<(tokio::net::UnixStream, SocketAddr)> {
+        let (stream, _) = tokio::net::UnixListener::accept(self).await?;
+        Ok((stream, UNSPECIFIED_ADDR))
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        Ok(UNSPECIFIED_ADDR)
+    }
+}
+
+/// ## `MemoryListener`
+///
+/// In-memory [`Listener`], every connection is the `tokio::io::duplex` pair. Made for tests, so no ports are needed.
+/// Connections are made through the [`MemoryConnector`] it was created with.
+///
+/// ## Example
+/// ```
+/// let (listener, connector) = MemoryListener::new();
+/// let router = RouterBuilder::new().build().with_listener(listener)?;
+/// tokio::spawn(router.run());
+///
+/// let mut stream = connector.connect().await?;
+/// write_frame(&mut stream, &FrameCodec::default(), b"<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'Jeff'>").await?;
+/// ```
+#[derive(Debug)]
+pub struct MemoryListener {
+    incoming: Receiver<DuplexStream>,
+}
+
+/// ## `MemoryConnector`
+///
+/// The other end of the [`MemoryListener`], could be cloned.
+#[derive(Debug, Clone)]
+pub struct MemoryConnector {
+    outgoing: Sender<DuplexStream>,
+    buffer: usize,
+}
+
+impl MemoryListener {
+    /// Creates listener and the connector to it. Every connection buffers up to 64 KiB each way.
+    pub fn new() -> (MemoryListener, MemoryConnector) {
+        Self::with_buffer(64 * 1024)
+    }
+
+    /// Same as [`MemoryListener::new`], but with the custom buffer size of every connection.
+    pub fn with_buffer(buffer: usize) -> (MemoryListener, MemoryConnector) {
+        let (outgoing, incoming) = mpsc::channel(32);
+
+        (MemoryListener { incoming }, MemoryConnector { outgoing, buffer })
+    }
+}
+
+impl MemoryConnector {
+    /// Connects to the [`MemoryListener`]. Returns `ConnectionRefused`, if it is gone.
+    pub async fn connect(&self) -> std::io::Result<DuplexStream> {
+        let (client, server) = duplex(self.buffer);
+
+        match self.outgoing.send(server).await {
+            Ok(_) => Ok(client),
+            Err(_) => Err(Error::new(ErrorKind::ConnectionRefused, "memory listener is closed")),
+        }
+    }
+}
+
+#[async_trait]
+impl Listener for MemoryListener {
+    type Conn = DuplexStream;
+
+    async fn accept(&mut self) -> std::io::Result<(DuplexStream, SocketAddr)> {
+        match self.incoming.recv().await {
+            Some(stream) => Ok((stream, UNSPECIFIED_ADDR)),
+            // Every connector is gone, nobody would connect anymore
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        Ok(UNSPECIFIED_ADDR)
+    }
+}
+
+/// ## `Both`
+///
+/// Two [`Listener`]'s as one, connections are taken from whichever has them first. This is how router
+/// serves TCP and WebSocket clients in the same chat (see [`RouterBuilder::websocket`]).
+///
+/// ## Example
+/// ```
+/// let listener = Both::new(tcp_listener, WsListener::new(ws_listener, DEFAULT_MAX_FRAME_SIZE));
+/// RouterBuilder::new().build().with_listener(listener)?.run().await;
+/// ```
+///
+/// [`RouterBuilder::websocket`]: crate::router::RouterBuilder::websocket
+#[derive(Debug)]
+pub struct Both<A: Listener, B: Listener> {
+    pub first: A,
+    pub second: B,
+}
+
+impl<A: Listener, B: Listener> Both<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Both { first, second }
+    }
+}
+
+#[async_trait]
+impl<A: Listener, B: Listener> Listener for Both<A, B> {
+    type Conn = Either<A::Conn, B::Conn>;
+
+    async fn accept(&mut self) -> std::io::Result<(Self::Conn, SocketAddr)> {
+        select! {
+            val = self.first.accept() => val.map(|(stream, addr)| (Either::First(stream), addr)),
+            val = self.second.accept() => val.map(|(stream, addr)| (Either::Second(stream), addr)),
+        }
+    }
+
+    /// Address of the `first` one.
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.first.local_addr()
+    }
+}
+
+/// ## `Either`
+///
+/// [`Connection`] of the [`Both`] listener, it is one of the two.
+#[derive(Debug)]
+pub enum Either<A, B> {
+    First(A),
+    Second(B),
+}
+
+impl<A: Connection, B: Connection> Connection for Either<A, B> {
+    fn keepalive(&self) -> std::io::Result<()> {
+        match self {
+            Either::First(stream) => stream.keepalive(),
+            Either::Second(stream) => stream.keepalive(),
+        }
+    }
+}
+
+impl<A: Connection, B: Connection> AsyncRead for Either<A, B> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Either::First(stream) => Pin::new(stream).poll_read(cx, buf),
+            Either::Second(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<A: Connection, B: Connection> AsyncWrite for Either<A, B> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            Either::First(stream) => Pin::new(stream).poll_write(cx, buf),
+            Either::Second(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Either::First(stream) => Pin::new(stream).poll_flush(cx),
+            Either::Second(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Either::First(stream) => Pin::new(stream).poll_shutdown(cx),
+            Either::Second(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! ## WebSocket
+//!
+//! Browsers cant open raw TCP, so [`Router`] could listen to WebSocket too (see [`RouterBuilder::websocket`]).
+//! Every WS message (binary or text) is one CHAT frame payload, without the length header. Under the hood
+//! it is turned into the usual frame, so WS clients go through the same wares and the same broadcast as TCP ones.
+//!
+//! ## How it looks like in the human way
+//! ```
+//! const ws = new WebSocket("ws://127.0.0.1:8081");
+//! ws.onopen = () => ws.send("<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'Jeff'>");
+//! ws.onmessage = (msg) => console.log(msg.data); // <CHAT \ 1.1>\n<Code@10>...
+//! ```
+//!
+//! [`Router`]: crate::router::Router
+//! [`RouterBuilder::websocket`]: crate::router::RouterBuilder::websocket
+use async_trait::async_trait;
+use bytes::{Buf, Bytes, BytesMut};
+use futures_util::{Sink, Stream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::select;
+use tokio::task::JoinSet;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::{Message, Utf8Bytes};
+use tokio_tungstenite::WebSocketStream;
+use std::fmt::{Debug, Formatter};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::protocol::frame::HEADER_LEN;
+use super::{Connection, Listener};
+
+/// How long the client has to finish the WebSocket handshake (HTTP upgrade), before it is dropped.
+pub const WS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// ## `WsListener`
+///
+/// Wraps any [`Listener`] into WebSocket. Handshakes are done in the background, same as in the [`TlsListener`].
+/// Messages larger than `max_frame_size` close the connection.
+///
+/// ## Example
+/// ```
+/// let tcp = TcpListener::bind("127.0.0.1:8080").await?;
+/// let ws = TcpListener::bind("127.0.0.1:8081").await?;
+///
+/// RouterBuilder::new()
+///     .build()
+///     .with_listener(Both::new(tcp, WsListener::new(ws, DEFAULT_MAX_FRAME_SIZE)))?
+///     .run()
+///     .await;
+/// ```
+///
+/// [`TlsListener`]: crate::router::TlsListener
+pub struct WsListener<L: Listener> {
+    inner: L,
+    config: WebSocketConfig,
+    handshakes: JoinSet<Option<(WsConnection<L::Conn>, SocketAddr)>>,
+}
+
+impl<L: Listener> WsListener<L> {
+    pub fn new(inner: L, max_frame_size: usize) -> Self {
+        WsListener {
+            inner,
+            config: WebSocketConfig::default().max_message_size(Some(max_frame_size)),
+            handshakes: JoinSet::new(),
+        }
+    }
+}
+
+impl<L: Listener> Debug for WsListener<L> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WsListener")
+            .field("inner", &self.inner)
+            .field("handshakes", &self.handshakes.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl<L: Listener> Listener for WsListener<L> {
+    type Conn = WsConnection<L::Conn>;
+
+    async fn accept(&mut self) -> std::io::Result<(Self::Conn, SocketAddr)> {
+        loop {
+            select! {
+                val = self.inner.accept() => {
+                    let (stream, addr) = val?;
+                    let config = self.config;
+
+                    self.handshakes.spawn(async move {
+                        let handshake = tokio_tungstenite::accept_async_with_config(stream, Some(config));
+
+                        match tokio::time::timeout(WS_HANDSHAKE_TIMEOUT, handshake).await {
+                            Ok(Ok(ws)) => Some((WsConnection::new(ws), addr)),
+                            _ => {
+                                warn!(%addr, "WebSocket handshake failed");
+
+                                None
+                            }
+                        }
+                    });
+                },
+                Some(done) = self.handshakes.join_next() => {
+                    if let Ok(Some(val)) = done {
+                        return Ok(val);
+                    }
+                },
+            }
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+/// ## `WsConnection`
+///
+/// WebSocket as the byte stream, so router doesnt know the difference. Every incoming message is read as the
+/// frame (header + payload), and every frame written is sent as one message. Frames that are valid UTF-8
+/// are sent as text messages (CHAT is text almost always), others as binary.
+pub struct WsConnection<C> {
+    ws: WebSocketStream<C>,
+    incoming: Bytes,        // Frame of the last message, that wasnt read fully yet
+    outgoing: BytesMut,     // Written bytes, that are not the whole frame yet, or werent sent yet
+}
+
+impl<C: Connection> WsConnection<C> {
+    pub fn new(ws: WebSocketStream<C>) -> Self {
+        WsConnection {
+            ws,
+            incoming: Bytes::new(),
+            outgoing: BytesMut::new(),
+        }
+    }
+
+    /// Reference to the inner WebSocket
+    pub fn get_ref(&self) -> &WebSocketStream<C> {
+        &self.ws
+    }
+
+    // Sends every whole frame from `outgoing` as the message. Pending, if WebSocket cant take more right now.
+    fn poll_send_frames(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        while self.outgoing.len() >= HEADER_LEN {
+            let len = u32::from_be_bytes([self.outgoing[0], self.outgoing[1], self.outgoing[2], self.outgoing[3]]) as usize;
+            if self.outgoing.len() < HEADER_LEN + len {
+                break;
+            }
+
+            ready!(Pin::new(&mut self.ws).poll_ready(cx)).map_err(std::io::Error::other)?;
+
+            self.outgoing.advance(HEADER_LEN);
+            let payload = self.outgoing.split_to(len).freeze();
+            let message = match Utf8Bytes::try_from(payload.clone()) {
+                Ok(text) => Message::Text(text),
+                Err(_) => Message::Binary(payload),
+            };
+
+            Pin::new(&mut self.ws).start_send(message).map_err(std::io::Error::other)?;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<C> Debug for WsConnection<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WsConnection")
+            .field("incoming", &self.incoming.len())
+            .field("outgoing", &self.outgoing.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: Connection> Connection for WsConnection<C> {
+    fn keepalive(&self) -> std::io::Result<()> {
+        self.ws.get_ref().keepalive()
+    }
+}
+
+impl<C: Connection> AsyncRead for WsConnection<C> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        while this.incoming.is_empty() {
+            let payload = match ready!(Pin::new(&mut this.ws).poll_next(cx)) {
+                Some(Ok(Message::Binary(payload))) => payload,
+                Some(Ok(Message::Text(text))) => Bytes::from(text),
+                // Pings are answered by tungstenite itself
+                Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_))) => continue,
+                Some(Ok(Message::Close(_))) | None => return Poll::Ready(Ok(())),
+                Some(Err(e)) => return Poll::Ready(Err(std::io::Error::other(e))),
+            };
+
+            let mut frame = BytesMut::with_capacity(HEADER_LEN + payload.len());
+            frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            frame.extend_from_slice(&payload);
+            this.incoming = frame.freeze();
+        }
+
+        let len = this.incoming.len().min(buf.remaining());
+        buf.put_slice(&this.incoming.split_to(len));
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<C: Connection> AsyncWrite for WsConnection<C> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+
+        // Frames from before go first, so slow client doesnt get everything buffered here
+        ready!(this.poll_send_frames(cx))?;
+
+        this.outgoing.extend_from_slice(buf);
+        if let Poll::Ready(Err(e)) = this.poll_send_frames(cx) {
+            return Poll::Ready(Err(e));
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_send_frames(cx))?;
+        Pin::new(&mut this.ws).poll_flush(cx).map_err(std::io::Error::other)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_send_frames(cx))?;
+        Pin::new(&mut this.ws).poll_close(cx).map_err(std::io::Error::other)
+    }
+}
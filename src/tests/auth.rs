@@ -1,32 +1,27 @@
 //! # Tests for basic auth
 
 use crate::{
-    client::{C, DefaultClient},
+    client::ClientBuilder,
     router::RouterBuilder
 };
-use std::{
-    net::SocketAddr,
-    str::FromStr,
-    time::Duration,
-};
 
 #[tokio::test]
 async fn test_register() {
-    tokio::spawn(async move {
-        let router = RouterBuilder::new()
-            .build();
-
-        router.run().await;
-    });
+    // Port 0, so tests dont fight for the same port
+    let router = RouterBuilder::new()
+        .port(0)
+        .build()
+        .bind()
+        .await
+        .unwrap();
 
-    //Let server start in peace
-    tokio::time::sleep(Duration::from_millis(300)).await;
+    let target = router.local_addr();
+    tokio::spawn(router.run());
 
-    let target = SocketAddr::from_str("127.0.0.1:8080").unwrap();
-    let client = DefaultClient::new(target);
+    let client = ClientBuilder::new(target, None).build();
 
-    let _ = match client.bind("Jeff".to_string()).await {
-        Ok(val) => val,
+    match client.bind("Jeff".to_string()).await {
+        Ok(_) => {},
         Err(e) => {
             panic!("Failed to BIND the user {:?}", e)
         }
@@ -35,27 +30,26 @@ async fn test_register() {
 
 #[tokio::test]
 async fn test_handshake() {
-    tokio::spawn(async move {
-        let router = RouterBuilder::new()
-            .build();
-
-        router.run().await;
-    });
+    let router = RouterBuilder::new()
+        .port(0)
+        .build()
+        .bind()
+        .await
+        .unwrap();
 
-    //Let server start in peace
-    tokio::time::sleep(Duration::from_millis(300)).await;
+    let target = router.local_addr();
+    tokio::spawn(router.run());
 
-    let target = SocketAddr::from_str("127.0.0.1:8080").unwrap();
-    let mut client = DefaultClient::new(target);
+    let client = ClientBuilder::new(target, None).build();
 
-    let token = match client.bind("Jeff".to_string()).await {
-        Ok(val) => val,
+    match client.bind("Jeff".to_string()).await {
+        Ok(_) => {},
         Err(e) => {
             panic!("Failed to BIND the user {:?}", e)
         }
-    };
+    }
 
-    match client.handshake(token).await {
+    match client.handshake().await {
         Ok(_) => {},
         Err(e) => {
             panic!("Failed to start handshake {:?}", e)
@@ -68,4 +62,4 @@ async fn test_handshake() {
             panic!("Failed to send request {:?}", e)
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,212 @@
+//! # Tests for the pluggable authentication backends
+
+use crate::client::{ClientBuilder, ClientError};
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::protocol::response::{Response, ResponseCode};
+use crate::protocol::Varmap;
+use crate::router::{App, Authenticator, AuthError, HmacAuthenticator, JsonUserStore, OpenAuthenticator, PasswordFile, RouterBuilder};
+use crate::router::{hash_password, verify_password};
+use once_cell::sync::Lazy;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+// Hashing is slow on purpose, so it is done once
+static HUNTER2: Lazy<String> = Lazy::new(|| hash_password("hunter2"));
+
+async fn once(addr: SocketAddr, request: String) -> Response {
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+
+    let frame = tokio::time::timeout(Duration::from_secs(5), reader.read_frame()).await.unwrap();
+    Response::from_bytes(&frame.unwrap().unwrap()).unwrap()
+}
+
+async fn bind(addr: SocketAddr, name: &str, password: &str) -> Response {
+    once(addr, format!("<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'{name}'>\n<Password@'{password}'>")).await
+}
+
+async fn handshake(addr: SocketAddr, token: &str) -> Response {
+    once(addr, format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>")).await
+}
+
+async fn router(authenticator: Box<dyn Authenticator>, ttl: Option<Duration>) -> SocketAddr {
+    let mut builder = RouterBuilder::new().port(0).authenticator(authenticator);
+    if let Some(ttl) = ttl {
+        builder = builder.token_ttl(ttl);
+    }
+
+    let router = builder.build().bind().await.unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+    addr
+}
+
+fn app() -> Mutex<App> {
+    Mutex::new(App::new(Varmap::new()))
+}
+
+#[test]
+fn test_password_hash() {
+    assert!(HUNTER2.starts_with("pbkdf2-sha256$"));
+    assert!(verify_password(&HUNTER2, "hunter2"));
+    assert!(!verify_password(&HUNTER2, "hunter3"));
+    assert!(!verify_password("plain text", "plain text"));
+}
+
+#[test]
+fn test_password_file_parse() {
+    let file = PasswordFile::parse(&format!("# users\n\njeff:{0}\nmr:robot:{0}\n", *HUNTER2)).unwrap();
+    assert_eq!(file.users.len(), 2);
+    assert!(file.users.contains_key("mr:robot"));
+
+    let err = PasswordFile::parse("jeff:hash\nbob\n").unwrap_err();
+    assert!(err.to_string().contains("line 2"));
+}
+
+#[tokio::test]
+async fn test_open_authenticator() {
+    let app = app();
+
+    let token = OpenAuthenticator.bind(&app, "Jeff", None).await.unwrap();
+    assert_eq!(OpenAuthenticator.bind(&app, "Jeff", None).await, Err(AuthError::Taken));
+    assert_eq!(OpenAuthenticator.verify(&app, &token).await.as_deref(), Some("Jeff"));
+
+    assert_eq!(OpenAuthenticator.unbind(&app, &token).await.as_deref(), Some("Jeff"));
+    assert!(OpenAuthenticator.verify(&app, &token).await.is_none());
+}
+
+#[tokio::test]
+async fn test_password_file_bind() {
+    let file = PasswordFile::parse(&format!("jeff:{0}", *HUNTER2)).unwrap();
+    let addr = router(Box::new(file), None).await;
+
+    assert_eq!(bind(addr, "jeff", "wrong").await.code, ResponseCode::Unauthorized);
+    assert_eq!(bind(addr, "bob", "hunter2").await.code, ResponseCode::Unauthorized);
+
+    let res = bind(addr, "jeff", "hunter2").await;
+    assert_eq!(res.code, ResponseCode::AuthOK);
+    let token = res.token.unwrap();
+
+    // Name is his, so binding again gives the same token instead of `AlreadyTaken`
+    assert_eq!(bind(addr, "jeff", "hunter2").await.token.as_deref(), Some(token.as_str()));
+    assert_eq!(handshake(addr, &token).await.code, ResponseCode::AuthOK);
+
+    // No password at all
+    let res = once(addr, "<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'jeff'>".to_string()).await;
+    assert_eq!(res.code, ResponseCode::Unauthorized);
+    assert_eq!(res.reason.as_deref(), Some("wrong name or password"));
+}
+
+#[tokio::test]
+async fn test_unknown_user_takes_as_long_as_wrong_password() {
+    let file = PasswordFile::parse(&format!("jeff:{0}", *HUNTER2)).unwrap();
+    let app = app();
+
+    // Warming up the dummy hash, it is made once
+    assert_eq!(file.bind(&app, "bob", Some("x")).await, Err(AuthError::InvalidCredentials));
+
+    let started = std::time::Instant::now();
+    assert_eq!(file.bind(&app, "jeff", Some("wrong")).await, Err(AuthError::InvalidCredentials));
+    let wrong_password = started.elapsed();
+
+    let started = std::time::Instant::now();
+    assert_eq!(file.bind(&app, "bob", Some("wrong")).await, Err(AuthError::InvalidCredentials));
+    let unknown_user = started.elapsed();
+
+    assert!(unknown_user * 4 > wrong_password, "{unknown_user:?} vs {wrong_password:?}");
+}
+
+#[tokio::test]
+async fn test_json_user_store() {
+    let path = std::env::temp_dir().join(format!("pinguino-users-{0}.json", uuid::Uuid::new_v4()));
+
+    let store = Arc::new(JsonUserStore::open(&path).unwrap());
+    store.add_user("jeff", "hunter2").unwrap();
+
+    // Users are saved, and could be read again
+    let reopened = JsonUserStore::open(&path).unwrap();
+    assert!(reopened.bind(&app(), "jeff", Some("hunter2")).await.is_ok());
+
+    let addr = router(Box::new(store.clone()), None).await;
+    assert_eq!(bind(addr, "jeff", "hunter2").await.code, ResponseCode::AuthOK);
+
+    assert!(store.remove_user("jeff").unwrap());
+    assert!(!store.remove_user("jeff").unwrap());
+    assert_eq!(bind(addr, "jeff", "hunter2").await.code, ResponseCode::Unauthorized);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+fn hmac(secret: &[u8]) -> HmacAuthenticator {
+    HmacAuthenticator::new(secret, Box::new(PasswordFile::parse(&format!("jeff:{0}", *HUNTER2)).unwrap()))
+}
+
+#[tokio::test]
+async fn test_hmac_tokens() {
+    let hmac = hmac(b"secret");
+    let app = app();
+
+    // Users are required, nobody gets the name without the password
+    assert_eq!(hmac.bind(&app, "jeff", None).await, Err(AuthError::InvalidCredentials));
+    assert_eq!(hmac.bind(&app, "bob", Some("hunter2")).await, Err(AuthError::InvalidCredentials));
+
+    let token = hmac.bind(&app, "jeff", Some("hunter2")).await.unwrap();
+    assert_eq!(hmac.verify(&app, &token).await.as_deref(), Some("jeff"));
+
+    // Nothing is stored, and router without TTL still gets the expiring token
+    assert!(app.lock().await.auth.is_empty());
+    let (_, rest) = token.split_once('.').unwrap();
+    let expires: u64 = rest.split_once('.').unwrap().0.parse().unwrap();
+    assert!(expires > 0);
+
+    // Other secret, or changed name, breaks the signature
+    assert!(HmacAuthenticator::new(b"other", Box::new(PasswordFile::default())).check(&token).is_none());
+    assert!(hmac.check(&format!("Qm9i.{rest}")).is_none());
+
+    // Expired, or never expiring tokens are not accepted
+    assert!(hmac.check(&hmac.sign("jeff", 1)).is_none());
+    assert!(hmac.check(&hmac.sign("jeff", 0)).is_none());
+
+    // Unbind revokes it
+    assert_eq!(hmac.unbind(&app, &token).await.as_deref(), Some("jeff"));
+    assert!(hmac.verify(&app, &token).await.is_none());
+    assert!(hmac.unbind(&app, &token).await.is_none());
+
+    // New token works again
+    let token = hmac.bind(&app, "jeff", Some("hunter2")).await;
+    assert!(token.is_ok());
+}
+
+#[tokio::test]
+async fn test_hmac_router() {
+    let addr = router(Box::new(hmac(b"secret")), Some(Duration::from_secs(60))).await;
+
+    assert_eq!(bind(addr, "jeff", "nope").await.code, ResponseCode::Unauthorized);
+
+    let token = bind(addr, "jeff", "hunter2").await.token.unwrap();
+    assert_eq!(handshake(addr, &token).await.code, ResponseCode::AuthOK);
+
+    // Signed by some other router
+    let forged = hmac(b"guess").sign("jeff", u64::MAX);
+    assert_eq!(handshake(addr, &forged).await.code, ResponseCode::Unauthorized);
+
+    // Unbound token is not accepted anymore
+    let res = once(addr, format!("<CHAT \\ 1.1>\n<Method@Unbind>\n<Authorization@'{token}'>")).await;
+    assert_eq!(res.code, ResponseCode::OK);
+    assert_eq!(handshake(addr, &token).await.code, ResponseCode::Unauthorized);
+}
+
+#[tokio::test]
+async fn test_client_password() {
+    let addr = router(Box::new(PasswordFile::parse(&format!("jeff:{0}", *HUNTER2)).unwrap()), None).await;
+
+    let client = ClientBuilder::new(addr, None).password("hunter2").build();
+    client.bind("jeff".to_string()).await.unwrap();
+    client.handshake().await.unwrap();
+
+    let client = ClientBuilder::new(addr, None).password("hunter3").build();
+    assert!(matches!(client.bind("jeff".to_string()).await, Err(ClientError::WrongResponseCoce(ResponseCode::Unauthorized))));
+}
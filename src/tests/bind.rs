@@ -0,0 +1,37 @@
+//! # Tests for binding the router
+
+use crate::router::{RouterBuilder, RouterError};
+use tokio::net::TcpStream;
+
+#[tokio::test]
+async fn test_port_zero() {
+    let first = RouterBuilder::new().port(0).build().bind().await.unwrap();
+    let second = RouterBuilder::new().port(0).build().bind().await.unwrap();
+
+    assert_ne!(first.local_addr().port(), 0);
+    assert_ne!(first.local_addr(), second.local_addr());
+
+    // Listener is there before run
+    assert!(TcpStream::connect(first.local_addr()).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_taken_port_is_an_error() {
+    let first = RouterBuilder::new().port(0).build().bind().await.unwrap();
+    let port = first.local_addr().port();
+
+    let err = RouterBuilder::new().port(port).build().bind().await.unwrap_err();
+    assert!(matches!(&err, RouterError::Bind(addr, _) if *addr == format!("127.0.0.1:{port}")));
+    assert!(err.to_string().starts_with(&format!("failed to bind to 127.0.0.1:{port}")));
+
+    // Same for run
+    let router = RouterBuilder::new().port(port).build();
+    assert!(router.run().await.is_err());
+}
+
+#[tokio::test]
+async fn test_invalid_ip_is_an_error() {
+    let router = RouterBuilder::new().ip("not an ip".to_string()).port(0).build();
+
+    assert!(matches!(router.bind().await, Err(RouterError::Bind(..))));
+}
@@ -0,0 +1,285 @@
+//! # Tests for the ClickHouse sink, against the local HTTP stand-in
+
+use crate::client::ClientBuilder;
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::protocol::response::{Response, ResponseCode};
+use crate::protocol::wares::clickhouse_sink::{ClickHouseSink, DEFAULT_EVENTS_TABLE, DEFAULT_SENT_TABLE};
+use crate::protocol::wares::{AfterConnect, BeforeConnect};
+use crate::router::{RouterBuilder, State};
+use async_trait::async_trait;
+use clickhouse::{Client, Compression};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+// Query (from the url) and body of every request stand-in got
+type Inserts = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+// Pretends to be ClickHouse: reads HTTP requests and answers `200 OK` to all of them
+async fn stand_in() -> (SocketAddr, Inserts) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let inserts = Inserts::default();
+
+    let saved = inserts.clone();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(serve(stream, saved.clone()));
+        }
+    });
+
+    (addr, inserts)
+}
+
+async fn serve(stream: TcpStream, inserts: Inserts) {
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+            return;
+        }
+        let target = line.split(' ').nth(1).unwrap_or_default().to_string();
+
+        let (mut length, mut chunked) = (0, false);
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).await.unwrap();
+            let header = header.trim_end().to_lowercase();
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some(val) = header.strip_prefix("content-length:") {
+                length = val.trim().parse().unwrap();
+            }
+            chunked |= header == "transfer-encoding: chunked";
+        }
+
+        let mut body = Vec::new();
+        if chunked {
+            loop {
+                let mut size = String::new();
+                reader.read_line(&mut size).await.unwrap();
+                let size = usize::from_str_radix(size.trim(), 16).unwrap();
+
+                let mut chunk = vec![0; size + 2];
+                reader.read_exact(&mut chunk).await.unwrap();
+                body.extend_from_slice(&chunk[..size]);
+
+                if size == 0 {
+                    break;
+                }
+            }
+        } else {
+            body.resize(length, 0);
+            reader.read_exact(&mut body).await.unwrap();
+        }
+
+        let query = target.split_once("query=").map(|(_, val)| val.replace('+', " ")).unwrap_or_default();
+        inserts.lock().await.push((decode(&query), body));
+
+        reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+    }
+}
+
+// Just enough of the url decoding for the queries
+fn decode(val: &str) -> String {
+    let mut out = Vec::new();
+    let mut bytes = val.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex: String = bytes.by_ref().take(2).map(char::from).collect();
+                out.push(u8::from_str_radix(&hex, 16).unwrap());
+            },
+            b'&' => break,
+            _ => out.push(b),
+        }
+    }
+    String::from_utf8(out).unwrap()
+}
+
+fn contains(body: &[u8], val: &str) -> bool {
+    body.windows(val.len()).any(|window| window == val.as_bytes())
+}
+
+// Rows that went into the `table`, all bodies together
+async fn rows_of(inserts: &Inserts, table: &str) -> Vec<u8> {
+    inserts.lock().await.iter()
+        .filter(|(query, _)| query.starts_with(&format!("INSERT INTO {table}")))
+        .flat_map(|(_, body)| body.clone())
+        .collect()
+}
+
+// Counts how many times it was ran, as `before` and as `after`
+#[derive(Debug)]
+struct Count(Arc<AtomicUsize>);
+
+#[async_trait]
+impl BeforeConnect for Count {
+    async fn execute(&self, _: Arc<Mutex<State>>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl AfterConnect for Count {
+    async fn execute(&self, _: Arc<Mutex<State>>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn client(addr: SocketAddr) -> Client {
+    Client::default().with_url(format!("http://{addr}")).with_compression(Compression::None)
+}
+
+#[tokio::test]
+async fn test_sink_batches_until_flush() {
+    let (addr, inserts) = stand_in().await;
+    let sink = ClickHouseSink::builder(client(addr)).flush_interval(Duration::from_secs(3600)).build();
+
+    sink.event("connect", 1, None);
+    sink.event("bind", 1, Some("Jeff".to_string()));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Nothing is sent before the flush
+    assert!(inserts.lock().await.is_empty());
+
+    sink.flush().await;
+    let inserts = inserts.lock().await;
+    assert_eq!(inserts.len(), 1);
+    assert_eq!(inserts[0].0, format!("INSERT INTO {DEFAULT_EVENTS_TABLE}(`time`,`event`,`connection`,`user`) FORMAT RowBinary"));
+    assert!(contains(&inserts[0].1, "connect") && contains(&inserts[0].1, "Jeff"));
+}
+
+#[tokio::test]
+async fn test_sink_flush_interval_and_max_rows() {
+    let (addr, inserts) = stand_in().await;
+    let sink = ClickHouseSink::builder(client(addr))
+        .events_table("events")
+        .flush_interval(Duration::from_millis(100))
+        .build();
+
+    sink.event("connect", 1, None);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(contains(&rows_of(&inserts, "events").await, "connect"));
+
+    // Full batch goes right away
+    let (addr, inserts) = stand_in().await;
+    let sink = ClickHouseSink::builder(client(addr)).flush_interval(Duration::from_secs(3600)).max_rows(2).build();
+    sink.event("connect", 1, None);
+    sink.event("connect", 2, None);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(inserts.lock().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_zero_flush_interval_is_clamped() {
+    let (addr, inserts) = stand_in().await;
+    let sink = ClickHouseSink::builder(client(addr)).flush_interval(Duration::ZERO).build();
+
+    // Background task is still there, and flushes on its own
+    sink.event("connect", 1, None);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(contains(&rows_of(&inserts, DEFAULT_EVENTS_TABLE).await, "connect"));
+
+    tokio::time::timeout(Duration::from_secs(1), sink.flush()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_sink_on_router() {
+    let (ch, inserts) = stand_in().await;
+    let sink = ClickHouseSink::builder(client(ch)).flush_interval(Duration::from_secs(3600)).build();
+
+    let router = sink.attach(RouterBuilder::new().port(0)).build().bind().await.unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+
+    let client = ClientBuilder::new(addr, None).build();
+    client.bind("Jeff".to_string()).await.unwrap();
+    client.handshake().await.unwrap();
+    client.send("hello analytics".to_string()).await.unwrap();
+    client.terminate().await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    sink.flush().await;
+
+    let sent = rows_of(&inserts, DEFAULT_SENT_TABLE).await;
+    assert!(contains(&sent, "Jeff") && contains(&sent, "hello analytics"));
+
+    let events = rows_of(&inserts, DEFAULT_EVENTS_TABLE).await;
+    for event in ["connect", "bind", "handshake", "disconnect"] {
+        assert!(contains(&events, event), "no {event} event");
+    }
+}
+
+#[tokio::test]
+async fn test_sink_keeps_before_and_after() {
+    let (ch, inserts) = stand_in().await;
+    let sink = ClickHouseSink::builder(client(ch)).flush_interval(Duration::from_secs(3600)).build();
+
+    let before = Arc::new(AtomicUsize::new(0));
+    let after = Arc::new(AtomicUsize::new(0));
+    let builder = RouterBuilder::new()
+        .port(0)
+        .before(Box::new(Count(before.clone())))
+        .after(Box::new(Count(after.clone())));
+
+    let router = sink.attach(builder).build().bind().await.unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+
+    let client = ClientBuilder::new(addr, None).build();
+    client.bind("Jeff".to_string()).await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    sink.flush().await;
+
+    // Both the sink and the ones that were there before it
+    assert_eq!(before.load(Ordering::SeqCst), 1);
+    assert_eq!(after.load(Ordering::SeqCst), 1);
+    let events = rows_of(&inserts, DEFAULT_EVENTS_TABLE).await;
+    assert!(contains(&events, "connect") && contains(&events, "disconnect"));
+}
+
+async fn round_trip(reader: &mut FrameReader<TcpStream>, request: &str) -> Response {
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+
+    let frame = tokio::time::timeout(Duration::from_millis(500), reader.read_frame()).await.unwrap();
+    Response::from_bytes(&frame.unwrap().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_sink_marks_undeliverable() {
+    let (ch, inserts) = stand_in().await;
+    let sink = ClickHouseSink::builder(client(ch)).flush_interval(Duration::from_secs(3600)).build();
+
+    let router = sink.attach(RouterBuilder::new().port(0)).build().bind().await.unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+
+    let mut bind = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    let token = round_trip(&mut bind, "<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'Jeff'>").await.token.unwrap();
+
+    let mut jeff = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    round_trip(&mut jeff, &format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>")).await;
+
+    let res = round_trip(&mut jeff, "<CHAT \\ 1.1>\n<Method@Send>\n<Message@'to nobody'>\n<To@'Nobody'>").await;
+    assert_eq!(res.code, ResponseCode::Undeliverable);
+
+    // Message to yourself is delivered, but nothing is written back
+    write_frame(jeff.get_mut(), &FrameCodec::default(), b"<CHAT \\ 1.1>\n<Method@Send>\n<Message@'to myself'>\n<To@'Jeff'>").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    sink.flush().await;
+
+    // `delivered` is the byte right after the message
+    let sent = rows_of(&inserts, DEFAULT_SENT_TABLE).await;
+    assert!(contains(&sent, "to nobody\x00"));
+    assert!(contains(&sent, "to myself\x01"));
+}
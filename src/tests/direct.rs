@@ -0,0 +1,95 @@
+//! # Tests for direct messages
+
+use crate::protocol::response::ResponseCode;
+use crate::protocol::Varmap;
+use crate::router::{App, Outbound, State};
+use bytes::Bytes;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+#[test]
+fn test_undeliverable_code() {
+    assert_eq!(ResponseCode::Undeliverable.to_string(), "<Code@26>");
+    assert_eq!(ResponseCode::from_str("<Code@26>").unwrap(), ResponseCode::Undeliverable);
+}
+
+#[tokio::test]
+async fn test_send_to() {
+    let mut app = App::new(Varmap::new());
+    let (jeff_tx, mut jeff) = mpsc::unbounded_channel();
+
+    assert!(!app.send_to("Jeff", Bytes::from_static(b"hi")));
+
+    let id = app.add_connection(jeff_tx);
+    // Connection is there, but user didnt do the Handshake yet
+    assert!(!app.send_to("Jeff", Bytes::from_static(b"hi")));
+
+    app.connect("Jeff", id);
+    assert!(app.send_to("Jeff", Bytes::from_static(b"hi")));
+    assert!(!app.send_to("Bob", Bytes::from_static(b"hi")));
+    assert!(matches!(jeff.recv().await.unwrap(), Outbound::Frame(bytes) if bytes == Bytes::from_static(b"hi")));
+
+    // Closed connection is not reachable too
+    drop(jeff);
+    assert!(!app.send_to("Jeff", Bytes::from_static(b"hi")));
+}
+
+#[test]
+fn test_newer_connection_is_kept() {
+    let mut app = App::new(Varmap::new());
+    let old = app.add_connection(mpsc::unbounded_channel().0);
+    let new = app.add_connection(mpsc::unbounded_channel().0);
+
+    app.connect("Jeff", old);
+    app.connect("Jeff", new);
+
+    // Old connection is closed after the new one was made
+    app.remove_connection(old);
+    assert_eq!(app.online.get("Jeff"), Some(&new));
+
+    app.remove_connection(new);
+    assert!(app.online.is_empty());
+    assert!(app.connections.is_empty());
+}
+
+#[test]
+fn test_older_connection_is_reached_after_newer_closes() {
+    let mut app = App::new(Varmap::new());
+    let (old_tx, mut old_rx) = mpsc::unbounded_channel();
+    let old = app.add_connection(old_tx);
+    let new = app.add_connection(mpsc::unbounded_channel().0);
+
+    app.connect("Jeff", old);
+    app.connect("Jeff", new);
+
+    // Newer one is closed, but Jeff is still online from the older one
+    app.remove_connection(new);
+    assert_eq!(app.online.get("Jeff"), Some(&old));
+    assert!(app.send_to("Jeff", Bytes::from_static(b"hi")));
+    assert!(matches!(old_rx.try_recv().unwrap(), Outbound::Frame(bytes) if bytes == Bytes::from_static(b"hi")));
+
+    app.remove_connection(old);
+    assert!(!app.send_to("Jeff", Bytes::from_static(b"hi")));
+}
+
+#[tokio::test]
+async fn test_state_connect_and_disconnect() {
+    let app = Arc::new(Mutex::new(App::new(Varmap::new())));
+    let mut state = State::new(app.clone(), Arc::new(None));
+    let mut inbox = state.attach().await;
+
+    state.varmap.insert("Jeff".to_string());
+    state.connect().await;
+    state.join("general").await.unwrap();
+
+    assert!(app.lock().await.send_to("Jeff", Bytes::from_static(b"psst")));
+    assert!(matches!(inbox.recv().await.unwrap(), Outbound::Frame(bytes) if bytes == Bytes::from_static(b"psst")));
+
+    state.disconnect().await;
+    let app = app.lock().await;
+    assert!(app.online.is_empty());
+    assert!(app.connections.is_empty());
+    assert!(app.rooms.is_empty());
+    assert!(state.inbox.is_none());
+}
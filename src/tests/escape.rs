@@ -0,0 +1,130 @@
+//! # Tests for escaping of the header values
+
+use crate::protocol::escape::{escape, unescape};
+use crate::protocol::request::{parse_key_value, RawRequest, Request, RequestBuilder, Method, Version};
+use crate::protocol::response::{Response, ResponseBuilder, ResponseCode};
+use proptest::prelude::*;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+fn addr() -> Arc<SocketAddr> {
+    Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap())
+}
+
+fn send_request(message: &str) -> Request {
+    RequestBuilder::new()
+        .version(Version::CHAT10)
+        .method(Method::Send)
+        .addr(addr())
+        .value(message.to_string())
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_escape_special_symbols() {
+    assert_eq!(escape("Hello world!"), "Hello world!");
+    assert_eq!(escape("don't"), "don\\'t");
+    assert_eq!(escape("a\\b"), "a\\\\b");
+    assert_eq!(escape("<b>"), "<b\\>");
+    assert_eq!(escape("one\ntwo\r\n"), "one\\ntwo\\r\\n");
+}
+
+#[test]
+fn test_unescape_rejects_broken_sequences() {
+    assert!(unescape("trailing\\").is_err());
+    assert!(unescape("unknown \\x").is_err());
+    assert_eq!(unescape("don\\'t").unwrap(), "don't");
+}
+
+#[test]
+fn test_parse_key_value_quoted() {
+    assert_eq!(parse_key_value("<Message@'don\\'t'>").unwrap(), ("Message".to_string(), "don't".to_string()));
+    assert_eq!(parse_key_value("<Message@'ends with \\''>").unwrap().1, "ends with '");
+    assert_eq!(parse_key_value("<Message@''>").unwrap().1, "");
+    assert_eq!(parse_key_value("<Method@Send>").unwrap().1, "Send");
+
+    // Lone quote inside is still an error
+    assert!(parse_key_value("<Message@'don't'>").is_err());
+}
+
+#[test]
+fn test_multiline_message_request() {
+    let request = send_request("first line\nsecond 'line' <b>");
+    let bytes = request.as_bytes().unwrap();
+
+    // Whole message is still one line
+    assert_eq!(std::str::from_utf8(&bytes).unwrap().lines().count(), 3);
+
+    let parsed = Request::from_raw_request(RawRequest { bytes, addr: addr() }).unwrap();
+    assert_eq!(parsed.value, "first line\nsecond 'line' <b>");
+}
+
+#[test]
+fn test_request_custom_headers_round_trip() {
+    let request = RequestBuilder::new()
+        .version(Version::CHAT10)
+        .method(Method::Send)
+        .addr(addr())
+        .value("Hello".to_string())
+        .custom_insert("Mood".to_string(), "it's fine".to_string())
+        .build()
+        .unwrap();
+
+    let parsed = Request::from_raw_request(RawRequest { bytes: request.as_bytes().unwrap(), addr: addr() }).unwrap();
+    assert_eq!(parsed.custom.get("Mood").unwrap(), "it's fine");
+}
+
+proptest! {
+    #[test]
+    fn prop_escape_round_trip(value in any::<String>()) {
+        let escaped = escape(&value);
+
+        prop_assert!(!escaped.contains('\n'));
+        prop_assert!(!escaped.contains('\r'));
+        prop_assert_eq!(unescape(&escaped).unwrap(), value.as_str());
+    }
+
+    #[test]
+    fn prop_request_round_trip(message in any::<String>(), header in any::<String>()) {
+        let mut request = send_request(&message);
+        request.custom.insert("Header".to_string(), header.clone());
+
+        let bytes = request.as_bytes().unwrap();
+        let parsed = Request::from_raw_request(RawRequest { bytes, addr: addr() }).unwrap();
+
+        prop_assert_eq!(parsed.method, Method::Send);
+        prop_assert_eq!(parsed.value, message);
+        prop_assert_eq!(parsed.custom.get("Header"), Some(&header));
+    }
+
+    #[test]
+    fn prop_response_round_trip(user in any::<String>(), message in any::<String>(), header in any::<String>()) {
+        let response = ResponseBuilder::new()
+            .version(Version::CHAT10)
+            .code(ResponseCode::OK)
+            .user(user.clone())
+            .message(message.clone())
+            .custom_insert("Header".to_string(), header.clone())
+            .build()
+            .unwrap();
+
+        let parsed = Response::from_bytes(&response.as_bytes().unwrap()).unwrap();
+
+        prop_assert_eq!(parsed.code, ResponseCode::OK);
+        prop_assert_eq!(parsed.user, Some(user));
+        prop_assert_eq!(parsed.message, Some(message));
+        prop_assert_eq!(parsed.custom.and_then(|custom| custom.get("Header").cloned()), Some(header));
+    }
+
+    #[test]
+    fn prop_pretty_string_is_wire_format(message in any::<String>()) {
+        let response = ResponseBuilder::default()
+            .message(message)
+            .build()
+            .unwrap();
+
+        prop_assert_eq!(response.pretty_string().into_bytes(), response.as_bytes().unwrap().to_vec());
+    }
+}
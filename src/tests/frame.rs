@@ -0,0 +1,127 @@
+//! # Tests for framing and stream reassembly
+
+use crate::protocol::frame::{write_frame, FrameCodec, FrameError, FrameReader, HEADER_LEN};
+use bytes::{Bytes, BytesMut};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+// Reader that gives away only `chunk` bytes per read, so frames are split across many reads.
+struct Trickle {
+    data: Vec<u8>,
+    pos: usize,
+    chunk: usize,
+}
+
+impl AsyncRead for Trickle {
+    fn poll_read(mut self: Pin<&mut Self>, _: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let end = (self.pos + self.chunk).min(self.data.len()).min(self.pos + buf.remaining());
+        buf.put_slice(&self.data[self.pos..end]);
+        self.pos = end;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn frames(codec: &FrameCodec, payloads: &[&[u8]]) -> Vec<u8> {
+    payloads.iter().flat_map(|payload| codec.encode(payload).unwrap().to_vec()).collect()
+}
+
+#[test]
+fn test_decode_waits_for_full_frame() {
+    let codec = FrameCodec::default();
+    let frame = codec.encode(b"<CHAT \\ 1.0>\n<Method@Bind>\n<Name@Jeff>").unwrap();
+
+    let mut buf = BytesMut::new();
+    for byte in &frame[..frame.len() - 1] {
+        buf.extend_from_slice(&[*byte]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+    }
+
+    buf.extend_from_slice(&frame[frame.len() - 1..]);
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"<CHAT \\ 1.0>\n<Method@Bind>\n<Name@Jeff>")));
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_decode_rejects_too_large() {
+    let codec = FrameCodec::new(8);
+    let mut buf = BytesMut::from(&FrameCodec::default().encode(b"123456789").unwrap()[..]);
+
+    assert!(matches!(codec.decode(&mut buf), Err(FrameError::TooLarge(9))));
+}
+
+#[test]
+fn test_encode_rejects_too_large() {
+    let codec = FrameCodec::new(8);
+
+    assert!(matches!(codec.encode(b"123456789"), Err(FrameError::TooLarge(9))));
+    assert_eq!(codec.encode(b"12345678").unwrap().len(), HEADER_LEN + 8);
+}
+
+#[tokio::test]
+async fn test_write_frame_rejects_too_large() {
+    let codec = FrameCodec::new(8);
+    let mut out = Vec::new();
+
+    let err = write_frame(&mut out, &codec, b"123456789").await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(out.is_empty());
+}
+
+#[tokio::test]
+async fn test_reader_byte_at_a_time() {
+    let codec = FrameCodec::default();
+    let message = "Hello, how your day is going? ".repeat(40);
+    let data = frames(&codec, &[b"first", message.as_bytes(), b""]);
+
+    let mut reader = FrameReader::new(Trickle { data, pos: 0, chunk: 1 }, codec);
+
+    assert_eq!(reader.read_frame().await.unwrap().unwrap(), Bytes::from_static(b"first"));
+    assert_eq!(reader.read_frame().await.unwrap().unwrap(), Bytes::from(message));
+    assert_eq!(reader.read_frame().await.unwrap().unwrap(), Bytes::new());
+    assert!(reader.read_frame().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_reader_coalesced_frames() {
+    let codec = FrameCodec::default();
+    let payloads: Vec<String> = (0..50).map(|i| format!("<CHAT \\ 1.0>\n<Method@Send>\n<Message@'Hello world!{i}'>")).collect();
+    let refs: Vec<&[u8]> = payloads.iter().map(|payload| payload.as_bytes()).collect();
+    let data = frames(&codec, &refs);
+
+    // Whole input is returned by the first read
+    let mut reader = FrameReader::new(&data[..], codec);
+
+    for payload in &payloads {
+        assert_eq!(reader.read_frame().await.unwrap().unwrap(), payload.as_bytes());
+    }
+    assert!(reader.read_frame().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_reader_split_and_coalesced() {
+    let codec = FrameCodec::default();
+    let data = frames(&codec, &[b"one", b"two", b"three", b"four"]);
+
+    // 7 bytes per read - frame boundaries land everywhere, including inside of the header
+    let mut reader = FrameReader::new(Trickle { data, pos: 0, chunk: HEADER_LEN + 3 }, codec);
+
+    for payload in [&b"one"[..], b"two", b"three", b"four"] {
+        assert_eq!(reader.read_frame().await.unwrap().unwrap(), payload);
+    }
+    assert!(reader.read_frame().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_reader_eof_in_the_middle() {
+    let codec = FrameCodec::default();
+    let mut data = frames(&codec, &[b"whole"]);
+    data.extend_from_slice(&codec.encode(b"cut off").unwrap()[..HEADER_LEN + 2]);
+
+    let mut reader = FrameReader::new(Trickle { data, pos: 0, chunk: 1 }, codec);
+
+    assert_eq!(reader.read_frame().await.unwrap().unwrap(), Bytes::from_static(b"whole"));
+    assert!(matches!(reader.read_frame().await, Err(FrameError::UnexpectedEof)));
+}
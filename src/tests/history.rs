@@ -0,0 +1,206 @@
+//! # Tests for paging through the stored messages with the History method
+
+use crate::client::{ClientBuilder, ClientError};
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::protocol::request::{Method, Request};
+use crate::protocol::response::{Response, ResponseCode};
+use crate::router::{MemoryStore, RouterBuilder, MAX_HISTORY_LIMIT};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+async fn next(reader: &mut FrameReader<TcpStream>) -> Option<Response> {
+    let frame = tokio::time::timeout(Duration::from_millis(500), reader.read_frame()).await.ok()?;
+    match frame {
+        Ok(Some(frame)) => Some(Response::from_bytes(&frame).unwrap()),
+        _ => None,
+    }
+}
+
+async fn write(reader: &mut FrameReader<TcpStream>, request: &str) {
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+}
+
+// Binds and handshakes, returns the token and the connection
+async fn user(addr: SocketAddr, name: &str) -> (String, FrameReader<TcpStream>) {
+    let mut bind = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    write(&mut bind, &format!("<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'{name}'>")).await;
+    let token = next(&mut bind).await.unwrap().token.unwrap();
+
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    write(&mut reader, &format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>")).await;
+    next(&mut reader).await.unwrap();
+
+    (token, reader)
+}
+
+// Everything that comes, until nothing comes
+async fn drain(reader: &mut FrameReader<TcpStream>) -> Vec<Response> {
+    let mut all = Vec::new();
+    while let Some(res) = next(reader).await {
+        all.push(res);
+    }
+    all
+}
+
+fn id(res: &Response) -> u64 {
+    res.custom.as_ref().unwrap().get("Id").unwrap().parse().unwrap()
+}
+
+fn end(res: &Response) -> Option<&str> {
+    res.custom.as_ref()?.get("End").map(String::as_str)
+}
+
+async fn router(builder: RouterBuilder) -> SocketAddr {
+    let router = builder.port(0).build().bind().await.unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+    addr
+}
+
+// Router with `count` messages from Jeff already in the store
+async fn with_messages(count: usize) -> (SocketAddr, String, FrameReader<TcpStream>) {
+    let addr = router(RouterBuilder::new().message_store(Box::new(MemoryStore::default())).replay(0)).await;
+
+    let (token, mut jeff) = user(addr, "Jeff").await;
+    for i in 1..=count {
+        write(&mut jeff, &format!("<CHAT \\ 1.1>\n<Method@Send>\n<Message@'{i}'>")).await;
+    }
+    drain(&mut jeff).await;
+
+    (addr, token, jeff)
+}
+
+#[test]
+fn test_history_parse() {
+    let addr = Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap());
+    let request = Request::parse("<CHAT \\ 1.1>\n<Method@History>\n<Limit@20>\n<Before@42>", addr).unwrap();
+
+    assert_eq!(request.method, Method::History);
+    assert_eq!(request.value, "20");
+    assert_eq!(request.custom.get("Before").map(String::as_str), Some("42"));
+}
+
+#[tokio::test]
+async fn test_history_pages() {
+    let (_addr, _token, mut jeff) = with_messages(5).await;
+
+    // Newest 2, oldest first, and then the end
+    write(&mut jeff, "<CHAT \\ 1.1>\n<Method@History>\n<Limit@2>").await;
+    let page = drain(&mut jeff).await;
+    assert_eq!(page.len(), 3);
+    assert_eq!(page[..2].iter().map(id).collect::<Vec<_>>(), [4, 5]);
+    assert_eq!(end(&page[2]), Some("History"));
+    assert_eq!(page[2].custom.as_ref().unwrap().get("Count").map(String::as_str), Some("2"));
+
+    // And the ones before them
+    write(&mut jeff, "<CHAT \\ 1.1>\n<Method@History>\n<Limit@10>\n<Before@4>").await;
+    let page = drain(&mut jeff).await;
+    assert_eq!(page.iter().filter(|val| end(val).is_none()).map(id).collect::<Vec<_>>(), [1, 2, 3]);
+
+    // Nothing before the first one
+    write(&mut jeff, "<CHAT \\ 1.1>\n<Method@History>\n<Limit@10>\n<Before@1>").await;
+    let page = drain(&mut jeff).await;
+    assert_eq!(page.len(), 1);
+    assert_eq!(end(&page[0]), Some("History"));
+}
+
+#[tokio::test]
+async fn test_history_before_handshake() {
+    let (addr, token, _jeff) = with_messages(3).await;
+
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    write(&mut reader, &format!("<CHAT \\ 1.1>\n<Method@History>\n<Limit@10>\n<Authorization@'{token}'>")).await;
+    assert_eq!(drain(&mut reader).await.len(), 4);
+
+    // Not without the token
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    write(&mut reader, "<CHAT \\ 1.1>\n<Method@History>\n<Limit@10>").await;
+    assert_eq!(next(&mut reader).await.unwrap().code, ResponseCode::Unauthorized);
+}
+
+#[tokio::test]
+async fn test_history_errors() {
+    let (_addr, _token, mut jeff) = with_messages(1).await;
+
+    write(&mut jeff, "<CHAT \\ 1.1>\n<Method@History>\n<Limit@many>").await;
+    assert_eq!(next(&mut jeff).await.unwrap().code, ResponseCode::InvalidHeader);
+
+    write(&mut jeff, "<CHAT \\ 1.1>\n<Method@History>\n<Limit@10>\n<Before@yesterday>").await;
+    assert_eq!(next(&mut jeff).await.unwrap().code, ResponseCode::InvalidHeader);
+
+    // Room history is only for the ones in the room
+    write(&mut jeff, "<CHAT \\ 1.1>\n<Method@History>\n<Limit@10>\n<Room@'general'>").await;
+    assert_eq!(next(&mut jeff).await.unwrap().code, ResponseCode::Error);
+
+    // Without the store there is no history at all
+    let addr = router(RouterBuilder::new()).await;
+    let (_, mut bob) = user(addr, "Bob").await;
+    write(&mut bob, "<CHAT \\ 1.1>\n<Method@History>\n<Limit@10>").await;
+    let res = next(&mut bob).await.unwrap();
+    assert_eq!(res.code, ResponseCode::Error);
+    assert_eq!(res.reason.as_deref(), Some("history is not kept"));
+}
+
+#[tokio::test]
+async fn test_history_room_and_limit() {
+    let (addr, _token, mut jeff) = with_messages(MAX_HISTORY_LIMIT + 5).await;
+
+    write(&mut jeff, "<CHAT \\ 1.1>\n<Method@Join>\n<Room@'general'>").await;
+    write(&mut jeff, "<CHAT \\ 1.1>\n<Method@Send>\n<Message@'in the room'>\n<Room@'general'>").await;
+    drain(&mut jeff).await;
+
+    write(&mut jeff, "<CHAT \\ 1.1>\n<Method@History>\n<Limit@10>\n<Room@'general'>").await;
+    let page = drain(&mut jeff).await;
+    assert_eq!(page.len(), 2);
+    assert_eq!(page[0].message.as_deref(), Some("in the room"));
+    assert_eq!(page[1].custom.as_ref().unwrap().get("Room").map(String::as_str), Some("general"));
+
+    // Bigger limit is cut, and History is not broadcast to the others
+    let (_, mut bob) = user(addr, "Bob").await;
+    write(&mut jeff, "<CHAT \\ 1.1>\n<Method@History>\n<Limit@100000>").await;
+    assert_eq!(drain(&mut jeff).await.len(), MAX_HISTORY_LIMIT + 1);
+    assert!(drain(&mut bob).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_client_history() {
+    let (addr, _token, _jeff) = with_messages(5).await;
+
+    let client = ClientBuilder::new(addr, None).build();
+    assert!(matches!(client.history(None, 10).await, Err(ClientError::MissingToken)));
+
+    client.bind("Bob".to_string()).await.unwrap();
+    let page = client.history(None, 3).await.unwrap();
+    assert_eq!(page.iter().map(|val| val.message.as_deref().unwrap()).collect::<Vec<_>>(), ["3", "4", "5"]);
+
+    let older = client.history(Some(id(&page[0])), 3).await.unwrap();
+    assert_eq!(older.iter().map(id).collect::<Vec<_>>(), [1, 2]);
+}
+
+#[tokio::test]
+async fn test_history_doesnt_hold_the_client() {
+    // Server that never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            held.push(stream);
+        }
+    });
+
+    let client = Arc::new(ClientBuilder::new(addr, None).build());
+    client.state.lock().await.token = Some("token".to_string());
+
+    let waiting = client.clone();
+    let history = tokio::spawn(async move { waiting.history(None, 10).await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Page is still on its way, but the state is free
+    assert!(!history.is_finished());
+    assert!(tokio::time::timeout(Duration::from_millis(100), client.state.lock()).await.is_ok());
+    history.abort();
+}
@@ -0,0 +1,124 @@
+//! # Tests for the middleware layers
+
+use crate::protocol::request::{Method, Request};
+use crate::protocol::response::{Response, ResponseBuilder, ResponseCode};
+use crate::protocol::wares::middleware::default_send::DefaultMiddleware;
+use crate::protocol::wares::{Layer, Layered, Limit, Middleware, Next, RateLimiter};
+use crate::protocol::Varmap;
+use crate::router::{App, RouterBuilder, State};
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+type Trace = Arc<std::sync::Mutex<Vec<String>>>;
+
+// Writes its name down before and after the rest of the chain
+#[derive(Debug)]
+struct Tag(&'static str, Trace);
+
+#[async_trait]
+impl Layer for Tag {
+    async fn call(&self, req: Request, state: Arc<Mutex<State>>, next: Next<'_>) -> Result<Response, Response> {
+        self.1.lock().unwrap().push(format!("{0} in", self.0));
+        let res = next.run(req, state).await;
+        self.1.lock().unwrap().push(format!("{0} out", self.0));
+
+        res
+    }
+}
+
+// Lets through only messages without the word `darn`
+#[derive(Debug)]
+struct Filter;
+
+#[async_trait]
+impl Layer for Filter {
+    async fn call(&self, req: Request, state: Arc<Mutex<State>>, next: Next<'_>) -> Result<Response, Response> {
+        if req.value.contains("darn") {
+            return Err(ResponseBuilder::new()
+                .version(req.version)
+                .code(ResponseCode::Error)
+                .reason("watch your language".to_string())
+                .build()
+                .unwrap());
+        }
+
+        next.run(req, state).await
+    }
+}
+
+fn send(text: &str) -> Request {
+    let addr = Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap());
+    Request::parse(&format!("<CHAT \\ 1.1>\n<Method@Send>\n<Message@'{text}'>"), addr).unwrap()
+}
+
+async fn jeff() -> Arc<Mutex<State>> {
+    let app = Arc::new(Mutex::new(App::new(Varmap::new())));
+    let mut state = State::new(app, Arc::new(None));
+    state.attach().await;
+    state.varmap.insert("Jeff".to_string());
+
+    Arc::new(Mutex::new(state))
+}
+
+#[tokio::test]
+async fn test_layers_order() {
+    let trace = Trace::default();
+    let layered = Layered::new(
+        vec![Box::new(Tag("logger", trace.clone())), Box::new(Tag("auth", trace.clone()))],
+        Box::new(DefaultMiddleware),
+    );
+
+    let res = layered.middleware(send("hi"), jeff().await).await.unwrap();
+    assert_eq!(res.message.as_deref(), Some("hi"));
+    assert_eq!(*trace.lock().unwrap(), ["logger in", "auth in", "auth out", "logger out"]);
+}
+
+#[tokio::test]
+async fn test_layer_short_circuits() {
+    let trace = Trace::default();
+    let layered = Layered::new(
+        vec![Box::new(Tag("outer", trace.clone())), Box::new(Filter), Box::new(Tag("inner", trace.clone()))],
+        Box::new(DefaultMiddleware),
+    );
+
+    let res = layered.middleware(send("darn it"), jeff().await).await.unwrap_err();
+    assert_eq!(res.reason.as_deref(), Some("watch your language"));
+
+    // Nothing after the filter was ran
+    assert_eq!(*trace.lock().unwrap(), ["outer in", "outer out"]);
+}
+
+#[tokio::test]
+async fn test_rate_limiter_layer() {
+    let limiter = RateLimiter::new().per_user(Limit::new(1, Duration::from_secs(60)));
+    let layered = Layered::new(vec![Box::new(limiter), Box::new(Filter)], Box::new(DefaultMiddleware));
+    let state = jeff().await;
+
+    assert!(layered.middleware(send("hi"), state.clone()).await.is_ok());
+    assert_eq!(layered.middleware(send("hi"), state).await.unwrap_err().code, ResponseCode::RateLimited);
+}
+
+#[test]
+fn test_builder_layers() {
+    let trace = Trace::default();
+    let router = RouterBuilder::new()
+        .layer("Send", Box::new(Tag("logger", trace.clone())))
+        .layer("Send", Box::new(Filter))
+        .layer("Edit", Box::new(Filter))
+        // Replacing the middleware keeps the layers
+        .send_middleware(Box::new(DefaultMiddleware))
+        .build();
+
+    let routes = &router.routes;
+    let send = format!("{:?}", routes.get(&Method::Send).unwrap().0);
+    assert!(send.starts_with("Layered"));
+    assert!(send.contains("Filter") && send.contains("DefaultMiddleware"));
+
+    // Other routes are untouched, and there is no route for `Edit` to put layers on
+    assert_eq!(format!("{:?}", routes.get(&Method::Bind).unwrap().0), "DefaultMiddleware");
+    assert!(routes.get(&Method::Custom("Edit".to_string())).is_none());
+}
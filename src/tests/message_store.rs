@@ -0,0 +1,207 @@
+//! # Tests for the message store and the replay after the Handshake
+
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::protocol::response::Response;
+use crate::router::{FileStore, HistoryQuery, MemoryStore, MessageStore, RouterBuilder};
+use bytes::Bytes;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+async fn next(reader: &mut FrameReader<TcpStream>) -> Option<Response> {
+    let frame = tokio::time::timeout(Duration::from_millis(500), reader.read_frame()).await.ok()?;
+    match frame {
+        Ok(Some(frame)) => Some(Response::from_bytes(&frame).unwrap()),
+        _ => None,
+    }
+}
+
+async fn round_trip(reader: &mut FrameReader<TcpStream>, request: String) -> Option<Response> {
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+    next(reader).await
+}
+
+// Binds and handshakes, `extra` goes into the Handshake. Returns the Handshake response too.
+async fn user(addr: SocketAddr, name: &str, extra: &str) -> FrameReader<TcpStream> {
+    let mut bind = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    let token = round_trip(&mut bind, format!("<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'{name}'>")).await.unwrap().token.unwrap();
+
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    round_trip(&mut reader, format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>{extra}")).await.unwrap();
+
+    reader
+}
+
+async fn send(reader: &mut FrameReader<TcpStream>, text: &str, extra: &str) {
+    let request = format!("<CHAT \\ 1.1>\n<Method@Send>\n<Message@'{text}'>{extra}");
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+}
+
+// Everything that comes, until nothing comes
+async fn drain(reader: &mut FrameReader<TcpStream>) -> Vec<Response> {
+    let mut all = Vec::new();
+    while let Some(res) = next(reader).await {
+        all.push(res);
+    }
+    all
+}
+
+fn id(res: &Response) -> u64 {
+    res.custom.as_ref().unwrap().get("Id").unwrap().parse().unwrap()
+}
+
+async fn router(builder: RouterBuilder) -> SocketAddr {
+    let router = builder.port(0).build().bind().await.unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+    addr
+}
+
+#[tokio::test]
+async fn test_memory_store() {
+    let store = MemoryStore::new(3);
+    for text in ["one", "two", "three", "four"] {
+        store.append(None, Bytes::from(text)).await.unwrap();
+    }
+    store.append(Some("general"), Bytes::from("room")).await.unwrap();
+
+    // Only last 3 are kept, ids keep going
+    let all = store.query(&HistoryQuery::new(10)).await.unwrap();
+    assert_eq!(all.iter().map(|val| val.id).collect::<Vec<_>>(), [3, 4]);
+
+    let query = HistoryQuery {
+        room: Some("general".to_string()),
+        ..HistoryQuery::new(10)
+    };
+    assert_eq!(store.query(&query).await.unwrap()[0].bytes, "room");
+
+    let query = HistoryQuery {
+        before: Some(4),
+        ..HistoryQuery::new(10)
+    };
+    assert_eq!(store.query(&query).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_file_store_reopen() {
+    let path = std::env::temp_dir().join(format!("pinguino-messages-{0}.jsonl", uuid::Uuid::new_v4()));
+
+    let store = FileStore::open(&path).await.unwrap();
+    store.append(None, Bytes::from("<CHAT \\ 1.1>\n<Code@10>\n<Message@'one'>")).await.unwrap();
+    store.append(Some("general"), Bytes::from("<CHAT \\ 1.1>\n<Code@10>\n<Message@'two'>")).await.unwrap();
+    drop(store);
+
+    // Messages are there after the restart, and ids go on from them
+    let store = FileStore::open(&path).await.unwrap();
+    assert_eq!(store.append(None, Bytes::from("three")).await.unwrap().id, 3);
+
+    let found = store.query(&HistoryQuery::new(1)).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, 3);
+
+    let first = store.query(&HistoryQuery::new(10)).await.unwrap()[0].response().unwrap();
+    assert_eq!(first.message.as_deref(), Some("one"));
+    assert_eq!(id(&first), 1);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_file_store_broken_lines() {
+    let path = std::env::temp_dir().join(format!("pinguino-messages-{0}.jsonl", uuid::Uuid::new_v4()));
+
+    let store = FileStore::open(&path).await.unwrap();
+    store.append(None, Bytes::from("one")).await.unwrap();
+    store.append(None, Bytes::from("two")).await.unwrap();
+    drop(store);
+
+    // Garbage in the middle, and the crash in the middle of the append
+    let text = std::fs::read_to_string(&path).unwrap();
+    let (first, second) = text.split_once('\n').unwrap();
+    std::fs::write(&path, format!("{first}\nnot json\n{second}{{\"id\":3,\"ti")).unwrap();
+
+    let store = FileStore::open(&path).await.unwrap();
+    assert_eq!(store.append(None, Bytes::from("three")).await.unwrap().id, 3);
+    drop(store);
+
+    // Cut line is gone, garbage is skipped, and everything else is there after the next restart
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.ends_with("\n"));
+    assert_eq!(text.lines().count(), 4);
+
+    let store = FileStore::open(&path).await.unwrap();
+    let found = store.query(&HistoryQuery::new(10)).await.unwrap();
+    assert_eq!(found.iter().map(|val| val.id).collect::<Vec<_>>(), [1, 2, 3]);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_replay_after_handshake() {
+    let addr = router(RouterBuilder::new().message_store(Box::new(MemoryStore::default()))).await;
+
+    let mut jeff = user(addr, "Jeff", "").await;
+    for text in ["one", "two", "three"] {
+        send(&mut jeff, text, "").await;
+    }
+
+    // Live messages have their ids too
+    let live = drain(&mut jeff).await;
+    assert_eq!(live.iter().map(id).collect::<Vec<_>>(), [1, 2, 3]);
+
+    let mut bob = user(addr, "Bob", "").await;
+    let replayed = drain(&mut bob).await;
+    assert_eq!(replayed.iter().map(|val| val.message.as_deref().unwrap()).collect::<Vec<_>>(), ["one", "two", "three"]);
+    assert_eq!(replayed[0].user.as_deref(), Some("Jeff"));
+
+    // And then the live ones, as usual
+    send(&mut jeff, "four", "").await;
+    assert_eq!(next(&mut bob).await.unwrap().message.as_deref(), Some("four"));
+}
+
+#[tokio::test]
+async fn test_replay_count_and_what_is_saved() {
+    let addr = router(RouterBuilder::new().message_store(Box::new(MemoryStore::default())).replay(2)).await;
+
+    let mut jeff = user(addr, "Jeff", "").await;
+    round_trip(&mut jeff, "<CHAT \\ 1.1>\n<Method@Join>\n<Room@'general'>".to_string()).await.unwrap();
+
+    send(&mut jeff, "one", "").await;
+    send(&mut jeff, "in the room", "\n<Room@'general'>").await;
+    send(&mut jeff, "psst", "\n<To@'Jeff'>").await;
+    send(&mut jeff, "two", "").await;
+    send(&mut jeff, "three", "").await;
+    drain(&mut jeff).await;
+
+    // Room and direct messages are not replayed, and only last 2 of the rest
+    let mut bob = user(addr, "Bob", "").await;
+    let replayed = drain(&mut bob).await;
+    assert_eq!(replayed.iter().map(|val| val.message.as_deref().unwrap()).collect::<Vec<_>>(), ["two", "three"]);
+}
+
+#[tokio::test]
+async fn test_replay_since() {
+    let addr = router(RouterBuilder::new().message_store(Box::new(MemoryStore::default()))).await;
+
+    let mut jeff = user(addr, "Jeff", "").await;
+    send(&mut jeff, "old", "").await;
+    drain(&mut jeff).await;
+
+    let mut bob = user(addr, "Bob", "\n<Since@'2999-01-01 00:00:00'>").await;
+    assert!(drain(&mut bob).await.is_empty());
+
+    let mut alice = user(addr, "Alice", "\n<Since@'2000-01-01 00:00:00'>").await;
+    assert_eq!(drain(&mut alice).await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_no_store_no_replay() {
+    let addr = router(RouterBuilder::new()).await;
+
+    let mut jeff = user(addr, "Jeff", "").await;
+    send(&mut jeff, "one", "").await;
+    assert!(next(&mut jeff).await.unwrap().custom.is_none_or(|custom| !custom.contains_key("Id")));
+
+    let mut bob = user(addr, "Bob", "").await;
+    assert!(drain(&mut bob).await.is_empty());
+}
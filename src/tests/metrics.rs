@@ -0,0 +1,179 @@
+//! # Tests for the RouterMetrics and the Prometheus endpoint
+
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::protocol::request::Method;
+use crate::protocol::response::{Response, ResponseCode};
+use crate::router::{RouterBuilder, RouterMetrics, LATENCY_BUCKETS, UNROUTED_METHOD};
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+async fn round_trip(reader: &mut FrameReader<TcpStream>, request: &str) -> Response {
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+
+    let frame = tokio::time::timeout(Duration::from_millis(500), reader.read_frame()).await.unwrap();
+    Response::from_bytes(&frame.unwrap().unwrap()).unwrap()
+}
+
+async fn connect(addr: SocketAddr) -> FrameReader<TcpStream> {
+    FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default())
+}
+
+async fn get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+// Connections are counted as closed a bit after the client is gone
+async fn wait_active(metrics: &RouterMetrics, val: i64) {
+    for _ in 0..50 {
+        if metrics.connections_active.load(Ordering::Relaxed) == val {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("connections_active never got to {val}");
+}
+
+#[test]
+fn test_render() {
+    let metrics = RouterMetrics::new();
+    metrics.connected();
+    metrics.connected();
+    metrics.disconnected();
+    metrics.request(&Method::Bind, &ResponseCode::AuthOK);
+    metrics.request(&Method::Bind, &ResponseCode::AuthOK);
+    metrics.request(&Method::Custom("Ty\"ping".to_string()), &ResponseCode::Custom(42));
+    metrics.observe(&Method::Send, Duration::from_micros(300));
+    metrics.observe(&Method::Send, Duration::from_millis(3));
+    metrics.observe(&Method::Send, Duration::from_secs(10));
+    metrics.lagged(5);
+
+    let text = metrics.render();
+    let lines: Vec<&str> = text.lines().collect();
+
+    assert!(lines.contains(&"# TYPE pinguino_connections_total counter"));
+    assert!(lines.contains(&"pinguino_connections_total 2"));
+    assert!(lines.contains(&"# TYPE pinguino_connections_active gauge"));
+    assert!(lines.contains(&"pinguino_connections_active 1"));
+    assert!(lines.contains(&"pinguino_broadcast_lagged_total 1"));
+    assert!(lines.contains(&"pinguino_broadcast_lagged_messages_total 5"));
+    assert!(lines.contains(&"pinguino_requests_total{method=\"Bind\",code=\"AuthOK\"} 2"));
+    assert!(lines.contains(&"pinguino_requests_total{method=\"Ty\\\"ping\",code=\"Custom(42)\"} 1"));
+
+    // Buckets are cumulative, the one that is too long is only in +Inf
+    assert!(lines.contains(&"pinguino_middleware_duration_seconds_bucket{method=\"Send\",le=\"0.0005\"} 1"));
+    assert!(lines.contains(&"pinguino_middleware_duration_seconds_bucket{method=\"Send\",le=\"0.005\"} 2"));
+    assert!(lines.contains(&"pinguino_middleware_duration_seconds_bucket{method=\"Send\",le=\"2.5\"} 2"));
+    assert!(lines.contains(&"pinguino_middleware_duration_seconds_bucket{method=\"Send\",le=\"+Inf\"} 3"));
+    assert!(lines.contains(&"pinguino_middleware_duration_seconds_count{method=\"Send\"} 3"));
+
+    let histogram = metrics.latency(&Method::Send).unwrap();
+    assert_eq!(histogram.buckets.len(), LATENCY_BUCKETS.len());
+    assert!((histogram.sum - 10.0033).abs() < 1e-9);
+    assert_eq!(metrics.latency(&Method::Bind), None);
+}
+
+#[tokio::test]
+async fn test_counts_requests() {
+    let router = RouterBuilder::new().port(0).build().bind().await.unwrap();
+    let addr = router.local_addr();
+    let metrics = router.metrics();
+    tokio::spawn(router.run());
+
+    let mut bind = connect(addr).await;
+    let token = round_trip(&mut bind, "<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'Jeff'>").await.token.unwrap();
+    drop(bind);
+
+    // Send before the Handshake is not allowed, but counted
+    let mut early = connect(addr).await;
+    let res = round_trip(&mut early, "<CHAT \\ 1.1>\n<Method@Send>\n<Message@'hi'>").await;
+    assert_eq!(res.code, ResponseCode::Unauthorized);
+    drop(early);
+
+    let mut jeff = connect(addr).await;
+    round_trip(&mut jeff, &format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>")).await;
+    round_trip(&mut jeff, "<CHAT \\ 1.1>\n<Method@Send>\n<Message@'hello'>").await;
+
+    assert_eq!(metrics.requests(&Method::Bind, &ResponseCode::AuthOK), 1);
+    assert_eq!(metrics.requests(&Method::Handshake, &ResponseCode::AuthOK), 1);
+    assert_eq!(metrics.requests(&Method::Send, &ResponseCode::Unauthorized), 1);
+    assert_eq!(metrics.requests(&Method::Send, &ResponseCode::OK), 1);
+    assert_eq!(metrics.latency(&Method::Send).unwrap().count, 1);
+    assert_eq!(metrics.latency(&Method::Handshake).unwrap().count, 1);
+
+    assert_eq!(metrics.connections_total.load(Ordering::Relaxed), 3);
+    wait_active(&metrics, 1).await;
+    assert!(metrics.bytes_in.load(Ordering::Relaxed) > 0);
+    assert!(metrics.bytes_out.load(Ordering::Relaxed) > 0);
+
+    drop(jeff);
+    wait_active(&metrics, 0).await;
+}
+
+#[tokio::test]
+async fn test_unrouted_methods_are_one_label() {
+    let router = RouterBuilder::new().port(0).build().bind().await.unwrap();
+    let addr = router.local_addr();
+    let metrics = router.metrics();
+    tokio::spawn(router.run());
+
+    // Before and after the Handshake, names are up to the user
+    let mut bind = connect(addr).await;
+    let token = round_trip(&mut bind, "<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'Jeff'>").await.token.unwrap();
+    drop(bind);
+
+    for i in 0..10 {
+        let mut early = connect(addr).await;
+        round_trip(&mut early, &format!("<CHAT \\ 1.1>\n<Method@Early{i}>")).await;
+    }
+
+    let mut jeff = connect(addr).await;
+    round_trip(&mut jeff, &format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>")).await;
+    for i in 0..10 {
+        round_trip(&mut jeff, &format!("<CHAT \\ 1.1>\n<Method@Late{i}>")).await;
+    }
+
+    // Known method, that is only not allowed after the Handshake, keeps its name
+    let res = round_trip(&mut jeff, "<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'Jeff'>").await;
+
+    let text = metrics.render();
+    let unrouted: Vec<&str> = text.lines()
+        .filter(|line| line.starts_with("pinguino_requests_total{") && !line.contains("method=\"Bind\"") && !line.contains("method=\"Handshake\""))
+        .collect();
+    assert_eq!(unrouted, vec![format!("pinguino_requests_total{{method=\"{UNROUTED_METHOD}\",code=\"{0:?}\"}} 20", res.code)]);
+    assert_eq!(metrics.requests(&Method::Bind, &res.code), 1);
+    assert!(!text.contains("Early") && !text.contains("Late"));
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint() {
+    let router = RouterBuilder::new().port(0).metrics(0).build().bind().await.unwrap();
+    let addr = router.local_addr();
+    let metrics_addr = router.metrics_addr().unwrap();
+    let handle = router.handle();
+    tokio::spawn(router.run());
+
+    let mut jeff = connect(addr).await;
+    round_trip(&mut jeff, "<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'Jeff'>").await;
+
+    let response = get(metrics_addr, "/metrics").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+    assert!(response.contains("\npinguino_connections_total 1\n"));
+    assert!(response.contains("pinguino_requests_total{method=\"Bind\",code=\"AuthOK\"} 1"));
+
+    let response = get(metrics_addr, "/other").await;
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    // Endpoint is gone together with the router
+    handle.shutdown();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(TcpStream::connect(metrics_addr).await.is_err());
+}
@@ -2,4 +2,53 @@
 //! Now its empty, but preferably it shouldnt be like this, and will be change in the future.
 //! Need help with what should be covered, and what shouldnt be.
 
-//pub mod auth;
\ No newline at end of file
+#[cfg(test)]
+pub mod auth;
+#[cfg(test)]
+pub mod frame;
+#[cfg(test)]
+pub mod escape;
+#[cfg(test)]
+pub mod parser;
+#[cfg(test)]
+pub mod parse_error;
+#[cfg(test)]
+pub mod version;
+#[cfg(test)]
+pub mod routes;
+#[cfg(test)]
+pub mod rooms;
+#[cfg(test)]
+pub mod direct;
+#[cfg(test)]
+pub mod push;
+#[cfg(test)]
+pub mod shutdown;
+#[cfg(test)]
+pub mod bind;
+#[cfg(test)]
+pub mod transport;
+#[cfg(test)]
+pub mod tls;
+#[cfg(test)]
+pub mod websocket;
+#[cfg(test)]
+pub mod rate_limit;
+#[cfg(test)]
+pub mod layer;
+#[cfg(test)]
+pub mod tokens;
+#[cfg(test)]
+pub mod authenticator;
+#[cfg(test)]
+pub mod name_policy;
+#[cfg(test)]
+pub mod message_store;
+#[cfg(test)]
+pub mod history;
+#[cfg(test)]
+pub mod clickhouse_sink;
+#[cfg(test)]
+pub mod spans;
+#[cfg(test)]
+pub mod metrics;
@@ -0,0 +1,115 @@
+//! # Tests for the name policy of `Bind`
+
+use crate::client::{ClientBuilder, ClientError};
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::protocol::response::{Response, ResponseCode};
+use crate::protocol::Varmap;
+use crate::router::{skeleton, App, NamePolicy, NameViolation, RouterBuilder};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+async fn bind(addr: SocketAddr, name: &str) -> Response {
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    let request = format!("<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'{name}'>");
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+
+    let frame = tokio::time::timeout(Duration::from_secs(2), reader.read_frame()).await.unwrap();
+    Response::from_bytes(&frame.unwrap().unwrap()).unwrap()
+}
+
+async fn router(policy: NamePolicy) -> SocketAddr {
+    let router = RouterBuilder::new().port(0).name_policy(policy).build().bind().await.unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+    addr
+}
+
+#[test]
+fn test_skeleton() {
+    let jeff = skeleton("jeff");
+
+    assert_eq!(skeleton("Jeff"), jeff);
+    assert_eq!(skeleton("jеff"), jeff);             // Cyrillic `е`
+    assert_eq!(skeleton("ｊｅｆｆ"), jeff);          // Fullwidth
+    assert_eq!(skeleton("je\u{0301}ff"), jeff);     // Combining accent
+    assert_eq!(skeleton("Bill"), skeleton("BiII"));
+    assert_eq!(skeleton("modern"), skeleton("modem"));
+    assert_eq!(skeleton("b0b"), skeleton("bob"));
+
+    assert_ne!(skeleton("jeff"), skeleton("jeffy"));
+}
+
+#[test]
+fn test_policy_rules() {
+    let policy = NamePolicy::new().length(3, 8).reserve("Admin");
+    let none: Vec<String> = Vec::new();
+
+    assert_eq!(policy.check("Jo", &none), Err(NameViolation::TooShort(3)));
+    assert_eq!(policy.check("Bartholomew", &none), Err(NameViolation::TooLong(8)));
+    assert_eq!(policy.check("Jeff Doe", &none), Err(NameViolation::InvalidChar(' ')));
+    assert_eq!(policy.check("аdmin", &none), Err(NameViolation::Reserved));
+
+    // Length is in characters, not bytes
+    assert!(policy.check("Ёжик", &none).is_ok());
+
+    let strict = NamePolicy::new().allowed(|c| c.is_ascii_alphanumeric());
+    assert_eq!(strict.check("Ёжик", &none), Err(NameViolation::InvalidChar('Ё')));
+}
+
+#[test]
+fn test_policy_uniqueness() {
+    let taken = vec!["Jeff".to_string()];
+
+    // Exactly the same name is up to `register`
+    assert!(NamePolicy::new().check("Jeff", &taken).is_ok());
+    assert_eq!(NamePolicy::new().check("jеff", &taken), Err(NameViolation::LooksLike("Jeff".to_string())));
+
+    let cased = NamePolicy::new().confusables(false);
+    assert!(cased.check("jеff", &taken).is_ok());
+    assert!(cased.check("JEFF", &taken).is_err());
+
+    let exact = NamePolicy::new().confusables(false).case_insensitive(false);
+    assert!(exact.check("JEFF", &taken).is_ok());
+}
+
+#[test]
+fn test_app_without_policy() {
+    let mut app = App::new(Varmap::new());
+    app.register("Jeff".to_string()).unwrap();
+
+    assert!(app.check_name("jеff").is_ok());
+    assert!(app.check_name("").is_ok());
+
+    app.name_policy = Some(NamePolicy::new());
+    assert!(app.check_name("jеff").is_err());
+}
+
+#[tokio::test]
+async fn test_invalid_name_response() {
+    let addr = router(NamePolicy::new().reserve("server")).await;
+
+    assert_eq!(bind(addr, "Jeff").await.code, ResponseCode::AuthOK);
+
+    let res = bind(addr, "JEFF").await;
+    assert_eq!(res.code, ResponseCode::InvalidName);
+    assert_eq!(res.reason.as_deref(), Some("name looks like already taken \"Jeff\""));
+
+    let res = bind(addr, "Server").await;
+    assert_eq!(res.code, ResponseCode::InvalidName);
+    assert_eq!(res.reason.as_deref(), Some("name is reserved"));
+
+    // Same name is still `AlreadyTaken`
+    assert_eq!(bind(addr, "Jeff").await.code, ResponseCode::AlreadyTaken);
+}
+
+#[tokio::test]
+async fn test_client_invalid_name() {
+    let addr = router(NamePolicy::new()).await;
+    let client = ClientBuilder::new(addr, None).build();
+
+    match client.bind("Jeff Doe".to_string()).await {
+        Err(ClientError::Rejected(reason)) => assert_eq!(reason, "character ' ' is not allowed in the name"),
+        other => panic!("expected rejection, got {other:?}"),
+    }
+}
@@ -0,0 +1,72 @@
+//! # Tests for `ParseError` context and `<Reason@...>`
+
+use crate::protocol::parser::Position;
+use crate::protocol::request::{ParseError, ParseErrorKind, RawRequest, Request, MAX_REPORTED_HEADER};
+use crate::protocol::response::{Response, ResponseCode};
+use crate::protocol::wares::starting_bytesware::{DefaultStartingBytesware, StartingBytesware};
+use crate::protocol::Varmap;
+use crate::router::{App, State};
+use bytes::Bytes;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+fn addr() -> Arc<SocketAddr> {
+    Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap())
+}
+
+#[test]
+fn test_wrong_key_context() {
+    let err = Request::parse("<CHAT \\ 1.0>\n<Method@Send>\n<Name@Jeff>", addr()).unwrap_err();
+
+    assert_eq!(err.kind, ParseErrorKind::InvalidKey);
+    assert_eq!(err.position, Some(Position { line: 3, column: 1 }));
+    assert_eq!(err.header.as_deref(), Some("<Name@Jeff>"));
+    assert_eq!(err.expected.as_deref(), Some("Message"));
+    assert_eq!(err.to_string(), "invalid key at line 3, column 1 in `<Name@Jeff>`, expected `Message`");
+}
+
+#[test]
+fn test_syntax_error_context() {
+    let err = Request::parse("<CHAT \\ 1.0>\n<Method@Send\n<Message@'Hi'>", addr()).unwrap_err();
+
+    assert_eq!(err.kind, ParseErrorKind::InvalidFormat);
+    assert_eq!(err.header.as_deref(), Some("<Method@Send"));
+    assert_eq!(err.expected.as_deref(), Some("Method"));
+    assert_eq!(err.to_string(), "invalid format at line 2, column 13 in `<Method@Send`, expected `Method`");
+}
+
+#[test]
+fn test_missing_line_has_no_header() {
+    let err = Request::parse("<CHAT \\ 1.0>\n<Method@Bind>\n", addr()).unwrap_err();
+
+    assert_eq!(err.kind, ParseErrorKind::MissingRequestValue);
+    assert_eq!(err.header, None);
+    assert_eq!(err.expected.as_deref(), Some("Name"));
+}
+
+#[test]
+fn test_reported_header_is_cut() {
+    let long = format!("<Message@'{0}", "a".repeat(1000));
+    let err = ParseError::new(ParseErrorKind::InvalidFormat).with_header(&long);
+
+    assert_eq!(err.header.unwrap().chars().count(), MAX_REPORTED_HEADER + 3);
+}
+
+#[tokio::test]
+async fn test_reason_travels_to_client() {
+    let app = Arc::new(Mutex::new(App::new(Varmap::new())));
+    let state = Arc::new(Mutex::new(State::new(app, Arc::new(None))));
+    let raw_req = RawRequest {
+        bytes: Bytes::from_static(b"<CHAT \\ 1.1>\n<Method@Send>\n<Name@Jeff>"),
+        addr: addr(),
+    };
+
+    let response = DefaultStartingBytesware.bytesware(state, raw_req).await.unwrap_err();
+    let parsed = Response::from_bytes(&response.as_bytes().unwrap()).unwrap();
+
+    // Wrong key is not `Unauthorized` anymore
+    assert_eq!(parsed.code, ResponseCode::ParseError);
+    assert_eq!(parsed.reason.unwrap(), "invalid key at line 3, column 1 in `<Name@Jeff>`, expected `Message`");
+}
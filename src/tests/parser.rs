@@ -0,0 +1,104 @@
+//! # Tests for the protocol parser
+
+use crate::protocol::parser::{parse_header, Parser, Position};
+use crate::protocol::request::{Method, ParseErrorKind, Request, Version};
+use crate::protocol::response::{Response, ResponseCode};
+use std::borrow::Cow;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+fn addr() -> Arc<SocketAddr> {
+    Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap())
+}
+
+fn error_at(input: &str) -> (ParseErrorKind, Position) {
+    let mut parser = Parser::new(input);
+
+    let err = match parser.version() {
+        Ok(_) => parser.find_map(Result::err).unwrap(),
+        Err(e) => e,
+    };
+    (err.kind, err.position.unwrap())
+}
+
+#[test]
+fn test_headers_borrow_from_input() {
+    let input = "<CHAT \\ 1.0>\n<Method@Send>\n<Message@'Hello world!'>\n<Mood@'don\\'t'>";
+    let mut parser = Parser::new(input);
+
+    assert_eq!(parser.version().unwrap(), Version::CHAT10);
+
+    let headers: Vec<_> = parser.map(Result::unwrap).collect();
+    assert_eq!(headers.len(), 3);
+    assert_eq!(headers[0].key, "Method");
+    assert!(matches!(headers[1].value, Cow::Borrowed("Hello world!")));
+    // Only escaped values are copied
+    assert!(matches!(&headers[2].value, Cow::Owned(val) if val == "don't"));
+    assert_eq!(headers[2].position, Position { line: 4, column: 1 });
+}
+
+#[test]
+fn test_crlf_and_trailing_newline() {
+    let request = Request::parse("<CHAT \\ 1.0>\r\n<Method@Send>\r\n<Message@'Hi'>\r\n", addr()).unwrap();
+
+    assert_eq!(request.method, Method::Send);
+    assert_eq!(request.value, "Hi");
+    assert!(request.custom.is_empty());
+}
+
+#[test]
+fn test_error_positions() {
+    assert_eq!(error_at(""), (ParseErrorKind::MissingVersion, Position { line: 1, column: 1 }));
+    assert_eq!(error_at("<CHAT / 1.0>").1, Position { line: 1, column: 7 });
+    assert_eq!(error_at("<CHAT \\ 1.0> ").1, Position { line: 1, column: 13 });
+    assert_eq!(error_at("<CHAT \\ 9.9>").0, ParseErrorKind::UnsupportedVersion);
+
+    // Missing `>` at the end of the key
+    assert_eq!(error_at("<CHAT \\ 1.0>\n<Method@Send").1, Position { line: 2, column: 13 });
+    // Column is counted in chars
+    assert_eq!(error_at("<CHAT \\ 1.0>\n<Message@'привет\\x'>").1, Position { line: 2, column: 17 });
+    // Not closed quote
+    assert_eq!(error_at("<CHAT \\ 1.0>\n<Method@Send>\n<Message@'Hi>").1, Position { line: 3, column: 14 });
+    // Empty line in the middle
+    assert_eq!(error_at("<CHAT \\ 1.0>\n\n<Method@Send>").1, Position { line: 2, column: 1 });
+}
+
+#[test]
+fn test_invalid_utf8_position() {
+    let err = Parser::from_bytes(b"<CHAT \\ 1.0>\n<Me\xffthod@Send>").unwrap_err();
+
+    assert_eq!(err.position, Some(Position { line: 2, column: 4 }));
+}
+
+#[test]
+fn test_parse_header_single_line() {
+    assert_eq!(parse_header("<Method@Send>").unwrap().value, "Send");
+    assert!(parse_header("<Method@Send>\n<Message@'Hi'>").is_err());
+    assert!(parse_header("<@Send>").is_err());
+    assert!(parse_header("<Method@two words>").is_err());
+}
+
+#[test]
+fn test_request_errors() {
+    let kind = |input: &str| Request::parse(input, addr()).unwrap_err().kind;
+
+    assert_eq!(kind("<CHAT \\ 1.0>"), ParseErrorKind::MissingMethod);
+    assert_eq!(kind("<CHAT \\ 1.0>\n<Mood@Send>"), ParseErrorKind::MissingMethod);
+    assert_eq!(kind("<CHAT \\ 1.0>\n<Method@Send>"), ParseErrorKind::MissingRequestValue);
+    assert_eq!(kind("<CHAT \\ 1.0>\n<Method@Send>\n<Name@Jeff>"), ParseErrorKind::InvalidKey);
+}
+
+#[test]
+fn test_response_shares_parser() {
+    let response = Response::from_bytes(b"<CHAT \\ 1.0>\n<Code@42>\n<User@Jeff>\n<Time@'2025-01-01 10:00:00'>\n<Message@'Hi'>\n<Mood@'ok'>").unwrap();
+
+    assert_eq!(response.code, ResponseCode::Custom(42));
+    assert_eq!(response.user.as_deref(), Some("Jeff"));
+    assert!(response.time.is_some());
+    assert_eq!(response.message.as_deref(), Some("Hi"));
+    assert_eq!(response.custom.unwrap().get("Mood").unwrap(), "ok");
+
+    assert_eq!(Response::from_bytes(b"<CHAT \\ 1.0>\n<User@Jeff>").unwrap_err().kind, ParseErrorKind::MissingCode);
+    assert_eq!(Response::from_bytes(b"<CHAT \\ 1.0>\n<Code@10>\n<Time@'yesterday'>").unwrap_err().kind, ParseErrorKind::InvalidFormat);
+}
@@ -0,0 +1,76 @@
+//! # Tests for pushing to the connection from the server
+
+use crate::protocol::request::Version;
+use crate::protocol::response::{Response, ResponseBuilder, ResponseCode};
+use crate::protocol::Varmap;
+use crate::router::{App, ConnectionId, Outbound, State};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+fn notice(text: &str) -> Response {
+    ResponseBuilder::new()
+        .version(Version::CHAT11)
+        .code(ResponseCode::OK)
+        .custom_insert("Notice".to_string(), text.to_string())
+        .build()
+        .unwrap()
+}
+
+fn frame(val: Option<Outbound>) -> Response {
+    match val {
+        Some(Outbound::Frame(bytes)) => Response::from_bytes(&bytes).unwrap(),
+        other => panic!("Expected frame, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_connection_ids_are_unique() {
+    let mut app = App::new(Varmap::new());
+
+    let first = app.add_connection(tokio::sync::mpsc::unbounded_channel().0);
+    let second = app.add_connection(tokio::sync::mpsc::unbounded_channel().0);
+
+    assert_ne!(first, second);
+    assert_eq!(ConnectionId(7).to_string(), "#7");
+    assert_eq!(app.connections.len(), 2);
+}
+
+#[tokio::test]
+async fn test_push_to_connection() {
+    let app = Arc::new(Mutex::new(App::new(Varmap::new())));
+    let mut jeff = State::new(app.clone(), Arc::new(None));
+    let mut bob = State::new(app.clone(), Arc::new(None));
+
+    let mut jeff_out = jeff.attach().await;
+    let mut bob_out = bob.attach().await;
+
+    // Pushing works before the Handshake too
+    assert!(app.lock().await.push_to(jeff.id.unwrap(), notice("hi Jeff")));
+    assert_eq!(frame(jeff_out.recv().await).custom.unwrap().get("Notice").unwrap(), "hi Jeff");
+    assert!(bob_out.try_recv().is_err());
+
+    assert!(bob.push(notice("hi Bob")));
+    assert_eq!(frame(bob_out.recv().await).custom.unwrap().get("Notice").unwrap(), "hi Bob");
+
+    // Gone connection is not reachable
+    let id = jeff.id.unwrap();
+    jeff.disconnect().await;
+    assert!(!app.lock().await.push_to(id, notice("hi")));
+    assert!(!jeff.push(notice("hi")));
+}
+
+#[tokio::test]
+async fn test_kick() {
+    let app = Arc::new(Mutex::new(App::new(Varmap::new())));
+    let mut state = State::new(app.clone(), Arc::new(None));
+    let mut outbound = state.attach().await;
+    let id = state.id.unwrap();
+
+    let app = app.lock().await;
+    assert!(app.push_to(id, notice("bye")));
+    assert!(app.close(id));
+
+    // Goodbye is written before the connection is closed
+    assert_eq!(frame(outbound.recv().await).code, ResponseCode::OK);
+    assert!(matches!(outbound.recv().await, Some(Outbound::Close)));
+}
@@ -0,0 +1,219 @@
+//! # Tests for the rate limiting wares
+
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::protocol::request::Version;
+use crate::protocol::response::{Response, ResponseCode};
+use crate::protocol::wares::middleware::default_send::DefaultMiddleware;
+use crate::protocol::wares::rate_limit::{rate_limited, LimitKey, MIN_PER};
+use crate::protocol::wares::{Limit, RateLimitBytesware, RateLimitMiddleware, RateLimiter};
+use crate::router::{ConnectionId, RouterBuilder};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+async fn next(reader: &mut FrameReader<TcpStream>) -> Option<Response> {
+    let frame = tokio::time::timeout(Duration::from_secs(2), reader.read_frame()).await.unwrap();
+    match frame {
+        Ok(Some(frame)) => Some(Response::from_bytes(&frame).unwrap()),
+        _ => None,
+    }
+}
+
+async fn round_trip(reader: &mut FrameReader<TcpStream>, request: String) -> Option<Response> {
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+    next(reader).await
+}
+
+async fn user(addr: SocketAddr, name: &str) -> FrameReader<TcpStream> {
+    let mut bind = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    let token = round_trip(&mut bind, format!("<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'{name}'>")).await.unwrap().token.unwrap();
+
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    let res = round_trip(&mut reader, format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>")).await.unwrap();
+    assert_eq!(res.code, ResponseCode::AuthOK);
+
+    reader
+}
+
+fn send(text: &str) -> String {
+    format!("<CHAT \\ 1.1>\n<Method@Send>\n<Message@'{text}'>")
+}
+
+fn retry_after(res: &Response) -> u64 {
+    res.custom.as_ref().unwrap().get("RetryAfter").unwrap().parse().unwrap()
+}
+
+#[test]
+fn test_rate_limited_code() {
+    assert_eq!(ResponseCode::RateLimited.to_string(), "<Code@27>");
+    assert_eq!(ResponseCode::from_str("<Code@27>").unwrap(), ResponseCode::RateLimited);
+
+    let res = rate_limited(Version::CHAT11, Duration::from_micros(1500));
+    assert_eq!(res.code, ResponseCode::RateLimited);
+    assert_eq!(retry_after(&res), 2);
+}
+
+#[tokio::test]
+async fn test_bucket_refills() {
+    let limit = Limit::new(2, Duration::from_millis(200));
+    let limiter = RateLimiter::new();
+    let key = || vec![(LimitKey::Connection(ConnectionId(1)), limit)];
+
+    assert!(limiter.check(key()).is_ok());
+    assert!(limiter.check(key()).is_ok());
+
+    let wait = limiter.check(key()).unwrap_err();
+    assert!(wait > Duration::ZERO && wait <= Duration::from_millis(100));
+
+    tokio::time::sleep(wait).await;
+    assert!(limiter.check(key()).is_ok());
+
+    // Other key has its own bucket
+    assert!(limiter.check(vec![(LimitKey::Connection(ConnectionId(2)), limit)]).is_ok());
+}
+
+#[tokio::test]
+async fn test_zero_limits_are_clamped() {
+    let limiter = RateLimiter::new();
+
+    // Zero capacity is one request, and doesnt panic on the wait
+    let zero = Limit::new(0, Duration::from_secs(1));
+    assert_eq!(zero.capacity, 1);
+    let key = || vec![(LimitKey::Connection(ConnectionId(1)), zero)];
+    assert!(limiter.check(key()).is_ok());
+    assert!(limiter.check(key()).unwrap_err() <= Duration::from_secs(1));
+
+    // Zero `per` is the shortest one, not the infinite rate
+    let instant = Limit::new(1, Duration::ZERO);
+    assert_eq!(instant.per, MIN_PER);
+    let key = || vec![(LimitKey::Connection(ConnectionId(2)), instant)];
+    assert!(limiter.check(key()).is_ok());
+    assert!(limiter.check(key()).unwrap_err() <= MIN_PER);
+
+    // Limit made by hand is clamped too
+    let by_hand = Limit { capacity: 0, per: Duration::ZERO };
+    let key = || vec![(LimitKey::Connection(ConnectionId(3)), by_hand)];
+    assert!(limiter.check(key()).unwrap_err() <= MIN_PER);
+}
+
+#[tokio::test]
+async fn test_nothing_is_taken_when_limited() {
+    let limiter = RateLimiter::new();
+    let ip = LimitKey::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    let user = LimitKey::User("Jeff".to_string());
+
+    limiter.check(vec![(user.clone(), Limit::new(1, Duration::from_secs(60)))]).unwrap();
+
+    // User bucket is empty, so the IP one stays full
+    let both = vec![(ip.clone(), Limit::new(1, Duration::from_secs(60))), (user, Limit::new(1, Duration::from_secs(60)))];
+    assert!(limiter.check(both).is_err());
+    assert!(limiter.check(vec![(ip, Limit::new(1, Duration::from_secs(60)))]).is_ok());
+}
+
+#[tokio::test]
+async fn test_method_limit() {
+    let limiter = RateLimiter::new().method("Send", Limit::new(2, Duration::from_secs(60)));
+    let router = RouterBuilder::new()
+        .port(0)
+        .starting_bytesware(Box::new(RateLimitBytesware::new(limiter)))
+        .build()
+        .bind()
+        .await
+        .unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+
+    let mut jeff = user(addr, "Jeff").await;
+    assert_eq!(round_trip(&mut jeff, send("one")).await.unwrap().message.as_deref(), Some("one"));
+    assert_eq!(round_trip(&mut jeff, send("two")).await.unwrap().message.as_deref(), Some("two"));
+
+    let res = round_trip(&mut jeff, send("three")).await.unwrap();
+    assert_eq!(res.code, ResponseCode::RateLimited);
+    assert!(retry_after(&res) > 0);
+
+    // Other methods are not limited, and other users have their own bucket
+    let res = round_trip(&mut jeff, "<CHAT \\ 1.1>\n<Method@Join>\n<Room@'general'>".to_string()).await.unwrap();
+    assert_ne!(res.code, ResponseCode::RateLimited);
+
+    let mut bob = user(addr, "Bob").await;
+    assert_eq!(round_trip(&mut bob, send("hi")).await.unwrap().message.as_deref(), Some("hi"));
+}
+
+#[tokio::test]
+async fn test_repeat_offender_is_disconnected() {
+    let limiter = RateLimiter::new()
+        .per_connection(Limit::new(2, Duration::from_secs(60)))
+        .disconnect_after(2);
+
+    let router = RouterBuilder::new()
+        .port(0)
+        .starting_bytesware(Box::new(RateLimitBytesware::new(limiter)))
+        .build()
+        .bind()
+        .await
+        .unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+
+    // Handshake took one token already
+    let mut jeff = user(addr, "Jeff").await;
+    assert!(round_trip(&mut jeff, send("one")).await.is_some());
+
+    let res = round_trip(&mut jeff, send("two")).await.unwrap();
+    assert_eq!(res.code, ResponseCode::RateLimited);
+
+    // Second strike is answered, and then connection is closed
+    let res = round_trip(&mut jeff, send("three")).await.unwrap();
+    assert_eq!(res.code, ResponseCode::RateLimited);
+    assert!(next(&mut jeff).await.is_none());
+}
+
+#[tokio::test]
+async fn test_old_strikes_are_forgotten() {
+    let limiter = RateLimiter::new()
+        .per_connection(Limit::new(1, Duration::from_millis(200)))
+        .disconnect_after(2);
+
+    let router = RouterBuilder::new()
+        .port(0)
+        .starting_bytesware(Box::new(RateLimitBytesware::new(limiter)))
+        .build()
+        .bind()
+        .await
+        .unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+
+    // Handshake took the token, so the first strike is right away
+    let mut jeff = user(addr, "Jeff").await;
+    assert_eq!(round_trip(&mut jeff, send("one")).await.unwrap().code, ResponseCode::RateLimited);
+
+    // Bucket is full again, and the strike is forgotten
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(round_trip(&mut jeff, send("two")).await.unwrap().message.as_deref(), Some("two"));
+    assert_eq!(round_trip(&mut jeff, send("three")).await.unwrap().code, ResponseCode::RateLimited);
+
+    // Still connected, next strike is the second one in a row
+    assert_eq!(round_trip(&mut jeff, send("four")).await.unwrap().code, ResponseCode::RateLimited);
+    assert!(next(&mut jeff).await.is_none());
+}
+
+#[tokio::test]
+async fn test_rate_limit_middleware() {
+    let limiter = RateLimiter::new().per_user(Limit::new(1, Duration::from_secs(60)));
+    let router = RouterBuilder::new()
+        .port(0)
+        .send_middleware(Box::new(RateLimitMiddleware::new(limiter, Box::new(DefaultMiddleware))))
+        .build()
+        .bind()
+        .await
+        .unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+
+    // Only `Send` goes through the limiter, so Bind and Handshake are free
+    let mut jeff = user(addr, "Jeff").await;
+    assert_eq!(round_trip(&mut jeff, send("one")).await.unwrap().message.as_deref(), Some("one"));
+    assert_eq!(round_trip(&mut jeff, send("two")).await.unwrap().code, ResponseCode::RateLimited);
+}
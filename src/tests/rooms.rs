@@ -0,0 +1,143 @@
+//! # Tests for rooms
+
+use crate::protocol::request::{Method, Request};
+use crate::protocol::Varmap;
+use crate::router::{App, Outbound, RoomError, State};
+use bytes::Bytes;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+fn state(app: Arc<Mutex<App>>) -> State {
+    State::new(app, Arc::new(None))
+}
+
+#[test]
+fn test_join_leave_parse() {
+    let addr = Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap());
+
+    let join = Request::parse("<CHAT \\ 1.1>\n<Method@Join>\n<Room@'general'>", addr.clone()).unwrap();
+    assert_eq!(join.method, Method::Join);
+    assert_eq!(join.value, "general");
+
+    let leave = Request::parse("<CHAT \\ 1.1>\n<Method@Leave>\n<Message@'general'>", addr).unwrap_err();
+    assert_eq!(leave.expected.as_deref(), Some("Room"));
+}
+
+#[test]
+fn test_rooms_are_created_and_dropped() {
+    let mut app = App::new(Varmap::new());
+
+    let _jeff = app.join("general", "Jeff");
+    let _bob = app.join("general", "Bob");
+    let _lonely = app.join("random", "Bob");
+
+    assert_eq!(app.rooms(), vec!["general", "random"]);
+    assert_eq!(app.members("general").unwrap(), vec!["Bob", "Jeff"]);
+    assert_eq!(app.members("nowhere"), None);
+
+    assert!(app.leave("random", "Bob"));
+    assert!(!app.leave("random", "Bob"));
+    assert_eq!(app.rooms(), vec!["general"]);
+
+    app.leave("general", "Jeff");
+    app.leave("general", "Bob");
+    assert!(app.rooms.is_empty());
+}
+
+#[tokio::test]
+async fn test_publish_goes_only_to_the_room() {
+    let mut app = App::new(Varmap::new());
+
+    let mut general = app.join("general", "Jeff");
+    let mut random = app.join("random", "Bob");
+
+    assert!(app.publish("general", Bytes::from_static(b"hi")));
+    assert!(!app.publish("nowhere", Bytes::from_static(b"hi")));
+
+    assert_eq!(general.recv().await.unwrap(), Bytes::from_static(b"hi"));
+    assert!(random.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_state_join_needs_handshake() {
+    let app = Arc::new(Mutex::new(App::new(Varmap::new())));
+    let mut state = state(app.clone());
+
+    assert_eq!(state.join("general").await, Err(RoomError::NotHandshaked));
+
+    state.varmap.insert("Jeff".to_string());
+    let _inbox = state.attach().await;
+    assert_eq!(state.join("").await, Err(RoomError::InvalidRoom));
+    assert!(app.lock().await.rooms.is_empty());
+}
+
+#[tokio::test]
+async fn test_state_join_delivers_to_inbox() {
+    let app = Arc::new(Mutex::new(App::new(Varmap::new())));
+    let mut state = state(app.clone());
+    let mut inbox = state.attach().await;
+    state.varmap.insert("Jeff".to_string());
+
+    state.join("general").await.unwrap();
+    state.join("random").await.unwrap();
+    assert_eq!(app.lock().await.members("general").unwrap(), vec!["Jeff"]);
+
+    app.lock().await.publish("general", Bytes::from_static(b"hi"));
+    let received = tokio::time::timeout(Duration::from_secs(1), inbox.recv()).await.unwrap();
+    assert!(matches!(received.unwrap(), Outbound::Frame(bytes) if bytes == Bytes::from_static(b"hi")));
+
+    assert!(state.leave("general").await);
+    assert!(!state.leave("general").await);
+    assert_eq!(app.lock().await.rooms(), vec!["random"]);
+
+    // Disconnect
+    state.leave_all().await;
+    assert!(state.rooms.is_empty());
+    assert!(app.lock().await.rooms.is_empty());
+}
+
+#[test]
+fn test_same_name_from_two_connections() {
+    let mut app = App::new(Varmap::new());
+
+    let _phone = app.join("general", "Jeff");
+    let _laptop = app.join("general", "Jeff");
+    assert_eq!(app.members("general").unwrap(), vec!["Jeff"]);
+
+    // Room stays, until every connection left it
+    assert!(app.leave("general", "Jeff"));
+    assert_eq!(app.members("general").unwrap(), vec!["Jeff"]);
+
+    assert!(app.leave("general", "Jeff"));
+    assert!(!app.leave("general", "Jeff"));
+    assert!(app.rooms.is_empty());
+}
+
+#[tokio::test]
+async fn test_state_leave_keeps_other_connection() {
+    let app = Arc::new(Mutex::new(App::new(Varmap::new())));
+
+    let mut phone = state(app.clone());
+    let _phone_inbox = phone.attach().await;
+    phone.varmap.insert("Jeff".to_string());
+    phone.join("general").await.unwrap();
+
+    let mut laptop = state(app.clone());
+    let mut laptop_inbox = laptop.attach().await;
+    laptop.varmap.insert("Jeff".to_string());
+    laptop.join("general").await.unwrap();
+
+    // Phone disconnects, laptop is still in the room
+    phone.disconnect().await;
+    assert_eq!(app.lock().await.members("general").unwrap(), vec!["Jeff"]);
+
+    assert!(app.lock().await.publish("general", Bytes::from_static(b"hi")));
+    let received = tokio::time::timeout(Duration::from_secs(1), laptop_inbox.recv()).await.unwrap();
+    assert!(matches!(received.unwrap(), Outbound::Frame(bytes) if bytes == Bytes::from_static(b"hi")));
+
+    laptop.disconnect().await;
+    assert!(app.lock().await.rooms.is_empty());
+}
@@ -0,0 +1,90 @@
+//! # Tests for custom methods and the routes registry
+
+use crate::protocol::request::{Method, ParseErrorKind, Request, RequestBuilder, Version};
+use crate::protocol::response::{Response, ResponseBuilder, ResponseCode};
+use crate::protocol::wares::ending_bytesware::default_send::DefaultEndingBytesware;
+use crate::protocol::wares::Middleware;
+use crate::router::{RouterBuilder, State};
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+fn addr() -> Arc<SocketAddr> {
+    Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap())
+}
+
+#[derive(Debug)]
+struct TypingMiddleware;
+
+#[async_trait]
+impl Middleware for TypingMiddleware {
+    async fn middleware(&self, req: Request, _: Arc<Mutex<State>>) -> Result<Response, Response> {
+        Ok(ResponseBuilder::new()
+            .version(req.version)
+            .code(ResponseCode::Custom(40))
+            .build()
+            .unwrap())
+    }
+}
+
+#[test]
+fn test_custom_method_parse() {
+    let request = Request::parse("<CHAT \\ 1.1>\n<Method@Typing>\n<Room@'general'>", addr()).unwrap();
+
+    assert_eq!(request.method, Method::Custom("Typing".to_string()));
+    assert_eq!(request.method.to_str(), "Typing");
+    assert_eq!(request.value, "");
+    assert_eq!(request.custom.get("Room").unwrap(), "general");
+
+    // Only headers at all
+    let request = Request::parse("<CHAT \\ 1.1>\n<Method@Ping>", addr()).unwrap();
+    assert!(request.custom.is_empty());
+
+    let err = Request::parse("<CHAT \\ 1.1>\n<Method@'Two words'>", addr()).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::MissingMethod);
+}
+
+#[test]
+fn test_custom_method_as_bytes() {
+    let request = RequestBuilder::new()
+        .version(Version::CHAT11)
+        .method(Method::Custom("React".to_string()))
+        .value(String::new())
+        .addr(addr())
+        .build()
+        .unwrap();
+
+    let bytes = request.as_bytes().unwrap();
+    assert_eq!(&bytes[..], b"<CHAT \\ 1.1>\n<Method@React>");
+    assert_eq!(Request::parse(std::str::from_utf8(&bytes).unwrap(), addr()).unwrap().method, request.method);
+}
+
+#[test]
+fn test_routes_registry() {
+    let router = RouterBuilder::new()
+        .route("Typing", Box::new(TypingMiddleware), Box::new(DefaultEndingBytesware))
+        .send_middleware(Box::new(TypingMiddleware))
+        .build();
+
+    let routes = &router.routes;
+    assert_eq!(routes.registry.len(), 8);
+    assert!(routes.get(&Method::Custom("Typing".to_string())).is_some());
+    assert!(routes.get(&Method::Custom("Edit".to_string())).is_none());
+
+    // Setters for the default routes still work on the registry
+    let send = routes.get(&Method::Send).unwrap();
+    assert_eq!(format!("{:?}", send.0), "TypingMiddleware");
+}
+
+#[test]
+fn test_default_route_can_be_replaced() {
+    let router = RouterBuilder::new()
+        .route("Bind", Box::new(TypingMiddleware), Box::new(DefaultEndingBytesware))
+        .build();
+
+    let bind = router.routes.get(&Method::Bind).unwrap();
+    assert_eq!(format!("{:?}", bind.0), "TypingMiddleware");
+    assert_eq!(router.routes.registry.len(), 7);
+}
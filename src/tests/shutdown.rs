@@ -0,0 +1,135 @@
+//! # Tests for graceful shutdown
+
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::protocol::request::Request;
+use crate::protocol::response::{Response, ResponseBuilder, ResponseCode};
+use crate::protocol::wares::ending_bytesware::default_send::DefaultEndingBytesware;
+use crate::protocol::wares::{AfterConnect, Middleware};
+use crate::router::{default_goodbye, RouterBuilder, State};
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+#[derive(Debug)]
+struct CountAfter(Arc<AtomicUsize>);
+
+#[async_trait]
+impl AfterConnect for CountAfter {
+    async fn execute(&self, state: Arc<Mutex<State>>) {
+        // State is not locked by the router anymore
+        let _locked = state.lock().await;
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug)]
+struct SlowMiddleware;
+
+#[async_trait]
+impl Middleware for SlowMiddleware {
+    async fn middleware(&self, req: Request, _: Arc<Mutex<State>>) -> Result<Response, Response> {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        Ok(ResponseBuilder::new().version(req.version).code(ResponseCode::OK).build().unwrap())
+    }
+}
+
+async fn read(reader: &mut FrameReader<TcpStream>) -> Option<Response> {
+    let frame = tokio::time::timeout(Duration::from_secs(2), reader.read_frame()).await.unwrap().unwrap();
+    frame.map(|val| Response::from_bytes(&val).unwrap())
+}
+
+async fn connect(addr: SocketAddr, name: &str) -> FrameReader<TcpStream> {
+    let codec = FrameCodec::default();
+
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), codec);
+    write_frame(reader.get_mut(), &codec, format!("<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'{name}'>").as_bytes()).await.unwrap();
+    let token = read(&mut reader).await.unwrap().token.unwrap();
+
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), codec);
+    write_frame(reader.get_mut(), &codec, format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>").as_bytes()).await.unwrap();
+    assert_eq!(read(&mut reader).await.unwrap().code, ResponseCode::AuthOK);
+
+    reader
+}
+
+#[tokio::test]
+async fn test_shutdown_before_run() {
+    let router = RouterBuilder::new().port(0).build();
+    let handle = router.handle();
+
+    handle.shutdown();
+    assert!(handle.is_shutdown());
+    tokio::time::timeout(Duration::from_secs(2), router.run()).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_run_until() {
+    let router = RouterBuilder::new().port(0).build();
+
+    tokio::time::timeout(Duration::from_secs(2), router.run_until(async {})).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_goodbye_and_after_connect() {
+    let after = Arc::new(AtomicUsize::new(0));
+    let router = RouterBuilder::new()
+        .port(0)
+        .after(Box::new(CountAfter(after.clone())))
+        .build()
+        .bind()
+        .await
+        .unwrap();
+    let (addr, handle) = (router.local_addr(), router.handle());
+    let running = tokio::spawn(router.run());
+
+    let mut jeff = connect(addr, "Jeff").await;
+    let mut idle = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    handle.shutdown();
+
+    let goodbye = read(&mut jeff).await.unwrap();
+    assert_eq!(goodbye.code, ResponseCode::Error);
+    assert_eq!(goodbye.reason, default_goodbye().reason);
+    assert!(read(&mut jeff).await.is_none());
+
+    // Connection that didnt send anything yet is closed too
+    assert_eq!(read(&mut idle).await.unwrap().code, ResponseCode::Error);
+    assert!(read(&mut idle).await.is_none());
+
+    tokio::time::timeout(Duration::from_secs(2), running).await.unwrap().unwrap();
+    // Bind connection, Jeff and the idle one
+    assert_eq!(after.load(Ordering::SeqCst), 3);
+
+    // Not accepting anymore
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn test_stuck_connection_is_aborted() {
+    let after = Arc::new(AtomicUsize::new(0));
+    let router = RouterBuilder::new()
+        .port(0)
+        .after(Box::new(CountAfter(after.clone())))
+        .route("Slow", Box::new(SlowMiddleware), Box::new(DefaultEndingBytesware))
+        .shutdown_timeout(Duration::from_millis(100))
+        .build()
+        .bind()
+        .await
+        .unwrap();
+    let (addr, handle) = (router.local_addr(), router.handle());
+    let running = tokio::spawn(router.run());
+
+    let mut jeff = connect(addr, "Jeff").await;
+    write_frame(jeff.get_mut(), &FrameCodec::default(), b"<CHAT \\ 1.1>\n<Method@Slow>").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    handle.shutdown();
+    tokio::time::timeout(Duration::from_secs(2), running).await.unwrap().unwrap();
+
+    assert_eq!(after.load(Ordering::SeqCst), 2);
+}
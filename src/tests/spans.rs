@@ -0,0 +1,71 @@
+//! # Tests for the connection and request spans
+
+use crate::client::ClientBuilder;
+use crate::router::RouterBuilder;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+// Span name, field and its value, for every field that was recorded
+#[derive(Debug, Clone, Default)]
+struct Fields(Arc<Mutex<Vec<(String, String, String)>>>);
+
+impl Fields {
+    fn has(&self, span: &str, field: &str, value: &str) -> bool {
+        self.0.lock().unwrap().iter().any(|(s, f, v)| s == span && f == field && v == value)
+    }
+}
+
+struct Visitor<'a>(&'a str, &'a Fields);
+
+impl Visit for Visitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.1.0.lock().unwrap().push((self.0.to_string(), field.name().to_string(), format!("{value:?}")));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.1.0.lock().unwrap().push((self.0.to_string(), field.name().to_string(), value.to_string()));
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Fields {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+        attrs.record(&mut Visitor(attrs.metadata().name(), self));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            values.record(&mut Visitor(span.name(), self));
+        }
+    }
+}
+
+// Runtime is single threaded, so the router tasks are on this thread too and see the subscriber
+#[tokio::test]
+async fn test_connection_and_request_spans() {
+    let fields = Fields::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(fields.clone()));
+
+    let router = RouterBuilder::new().port(0).build().bind().await.unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+
+    let client = ClientBuilder::new(addr, None).build();
+    client.bind("Jeff".to_string()).await.unwrap();
+    client.handshake().await.unwrap();
+    client.send("hello".to_string()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Bound user is recorded in the connection span, both for the Bind and the Handshake connection
+    assert_eq!(fields.0.lock().unwrap().iter().filter(|(s, f, v)| s == "connection" && f == "user" && v == "Jeff").count(), 2);
+    assert!(fields.0.lock().unwrap().iter().any(|(s, f, v)| s == "connection" && f == "addr" && v.starts_with("127.0.0.1:")));
+
+    for method in ["Bind", "Handshake", "Send"] {
+        assert!(fields.has("request", "method", method), "no request span for {method}");
+    }
+}
@@ -0,0 +1,95 @@
+//! # Tests for TLS
+
+use crate::client::{ClientBuilder, ClientError};
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::router::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use crate::router::rustls::RootCertStore;
+use crate::router::{RouterBuilder, RouterError};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+// Self-signed certificate for the `127.0.0.1`
+fn self_signed() -> (CertificateDer<'static>, PrivateKeyDer<'static>) {
+    let certified = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+    let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+
+    (certified.cert.der().clone(), key.into())
+}
+
+fn roots(cert: CertificateDer<'static>) -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots.add(cert).unwrap();
+    roots
+}
+
+async fn tls_router() -> (SocketAddr, CertificateDer<'static>) {
+    let (cert, key) = self_signed();
+    let router = RouterBuilder::new()
+        .port(0)
+        .tls(vec![cert.clone()], key)
+        .build()
+        .bind()
+        .await
+        .unwrap();
+
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+    (addr, cert)
+}
+
+#[tokio::test]
+async fn test_client_over_tls() {
+    let (addr, cert) = tls_router().await;
+
+    let client = ClientBuilder::new(addr, None)
+        .tls(roots(cert))
+        .build();
+
+    client.bind("Jeff".to_string()).await.unwrap();
+    client.handshake().await.unwrap();
+
+    let sub = client.subscribe().await;
+    client.send("Hi over TLS".to_string()).await.unwrap();
+
+    let mut reciever = sub.lock().await;
+    let message = tokio::time::timeout(Duration::from_secs(2), reciever.recv()).await.unwrap().unwrap();
+    assert_eq!(message.message.as_deref(), Some("Hi over TLS"));
+}
+
+#[tokio::test]
+async fn test_untrusted_certificate() {
+    let (addr, _) = tls_router().await;
+    let (other, _) = self_signed();
+
+    let client = ClientBuilder::new(addr, None)
+        .tls(roots(other))
+        .build();
+
+    assert!(matches!(client.bind("Jeff".to_string()).await, Err(ClientError::TlsFailed(_))));
+}
+
+#[tokio::test]
+async fn test_plain_client_gets_nothing() {
+    let (addr, _) = tls_router().await;
+
+    // Plain text is not a TLS handshake, so router drops the connection, without ever reading the request
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    write_frame(reader.get_mut(), &FrameCodec::default(), b"<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'Jeff'>").await.unwrap();
+
+    let read = tokio::time::timeout(Duration::from_secs(2), reader.read_frame()).await.unwrap();
+    assert!(!matches!(read, Ok(Some(_))));
+}
+
+#[tokio::test]
+async fn test_bad_key_is_an_error() {
+    let (cert, _) = self_signed();
+    let (_, other_key) = self_signed();
+
+    let router = RouterBuilder::new()
+        .port(0)
+        .tls(vec![cert], other_key)
+        .build();
+
+    assert!(matches!(router.bind().await, Err(RouterError::Tls(_))));
+}
@@ -0,0 +1,180 @@
+//! # Tests for the token lifecycle: expiry, unbind and revoke
+
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::protocol::request::{Method, Request};
+use crate::protocol::response::{Response, ResponseCode};
+use crate::protocol::Varmap;
+use crate::router::{App, Outbound, RouterBuilder, State};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+async fn next(reader: &mut FrameReader<TcpStream>) -> Option<Response> {
+    let frame = tokio::time::timeout(Duration::from_secs(2), reader.read_frame()).await.unwrap();
+    match frame {
+        Ok(Some(frame)) => Some(Response::from_bytes(&frame).unwrap()),
+        _ => None,
+    }
+}
+
+// One request on the new connection, same as Bind is done
+async fn once(addr: SocketAddr, request: String) -> Response {
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+    next(&mut reader).await.unwrap()
+}
+
+async fn bind(addr: SocketAddr, name: &str) -> Response {
+    once(addr, format!("<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'{name}'>")).await
+}
+
+async fn handshake(addr: SocketAddr, token: &str) -> (FrameReader<TcpStream>, Response) {
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    let request = format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>");
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+
+    let res = next(&mut reader).await.unwrap();
+    (reader, res)
+}
+
+async fn router(ttl: Option<Duration>) -> SocketAddr {
+    let mut builder = RouterBuilder::new().port(0);
+    if let Some(ttl) = ttl {
+        builder = builder.token_ttl(ttl);
+    }
+
+    let router = builder.build().bind().await.unwrap();
+    let addr = router.local_addr();
+    tokio::spawn(router.run());
+    addr
+}
+
+#[test]
+fn test_unbind_method_parse() {
+    let addr = Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap());
+    let request = Request::parse("<CHAT \\ 1.1>\n<Method@Unbind>\n<Authorization@'abc'>", addr).unwrap();
+
+    assert_eq!(request.method, Method::Unbind);
+    assert_eq!(request.value, "abc");
+}
+
+#[tokio::test]
+async fn test_token_expires() {
+    let mut app = App::new(Varmap::new());
+    app.token_ttl = Some(Duration::from_millis(50));
+
+    let token = app.register("Jeff".to_string()).unwrap();
+    assert_eq!(app.authorize(&token).map(String::as_str), Some("Jeff"));
+    assert!(app.register("Jeff".to_string()).is_err());
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(app.authorize(&token).is_none());
+
+    // Name of the expired token could be taken again, and old token is gone
+    let new = app.register("Jeff".to_string()).unwrap();
+    assert_ne!(new, token);
+    assert!(!app.auth.contains_key(&token));
+}
+
+#[tokio::test]
+async fn test_sweep_keeps_online_names() {
+    let mut app = App::new(Varmap::new());
+    app.token_ttl = Some(Duration::from_millis(10));
+
+    app.register("Jeff".to_string()).unwrap();
+    app.register("Bob".to_string()).unwrap();
+
+    let id = app.add_connection(tokio::sync::mpsc::unbounded_channel().0);
+    app.connect("Bob", id);
+
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(app.sweep(), ["Jeff"]);
+
+    // Bob is online, so his name is kept, until the connection is gone
+    assert!(app.register("Bob".to_string()).is_err());
+    app.remove_connection(id);
+    assert_eq!(app.sweep(), ["Bob"]);
+    assert!(app.names.is_empty());
+}
+
+#[tokio::test]
+async fn test_revoke_closes_connections() {
+    let app = Arc::new(Mutex::new(App::new(Varmap::new())));
+    let token = app.lock().await.register("Jeff".to_string()).unwrap();
+
+    let mut jeff = State::new(app.clone(), Arc::new(None));
+    let mut outbound = jeff.attach().await;
+    jeff.varmap.insert("Jeff".to_string());
+    jeff.connect().await;
+
+    let mut locked = app.lock().await;
+    assert!(locked.revoke(&token));
+    assert!(!locked.revoke(&token));
+    assert!(locked.authorize(&token).is_none());
+    assert!(locked.names.is_empty());
+    drop(locked);
+
+    assert!(matches!(outbound.recv().await, Some(Outbound::Close)));
+}
+
+#[tokio::test]
+async fn test_unbind_frees_name() {
+    let addr = router(None).await;
+
+    let token = bind(addr, "Jeff").await.token.unwrap();
+    assert_eq!(bind(addr, "Jeff").await.code, ResponseCode::AlreadyTaken);
+
+    let res = once(addr, format!("<CHAT \\ 1.1>\n<Method@Unbind>\n<Authorization@'{token}'>")).await;
+    assert_eq!(res.code, ResponseCode::OK);
+    assert_eq!(res.user.as_deref(), Some("Jeff"));
+
+    // Old token is no good anymore, but the name is free
+    assert_eq!(handshake(addr, &token).await.1.code, ResponseCode::Unauthorized);
+    assert_eq!(bind(addr, "Jeff").await.code, ResponseCode::AuthOK);
+
+    let res = once(addr, format!("<CHAT \\ 1.1>\n<Method@Unbind>\n<Authorization@'{token}'>")).await;
+    assert_eq!(res.code, ResponseCode::Unauthorized);
+}
+
+#[tokio::test]
+async fn test_unbind_disconnects_live_connection() {
+    let addr = router(None).await;
+    let token = bind(addr, "Jeff").await.token.unwrap();
+
+    let (mut jeff, res) = handshake(addr, &token).await;
+    assert_eq!(res.code, ResponseCode::AuthOK);
+
+    // Live connection is closed, when the token is unbound from anywhere
+    let res = once(addr, format!("<CHAT \\ 1.1>\n<Method@Unbind>\n<Authorization@'{token}'>")).await;
+    assert_eq!(res.code, ResponseCode::OK);
+    assert!(next(&mut jeff).await.is_none());
+}
+
+#[tokio::test]
+async fn test_unbind_from_live_connection() {
+    let addr = router(None).await;
+    let token = bind(addr, "Jeff").await.token.unwrap();
+    let (mut jeff, _) = handshake(addr, &token).await;
+
+    let request = format!("<CHAT \\ 1.1>\n<Method@Unbind>\n<Authorization@'{token}'>");
+    write_frame(jeff.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+
+    // Answer comes first, and only then connection is closed
+    assert_eq!(next(&mut jeff).await.unwrap().code, ResponseCode::OK);
+    assert!(next(&mut jeff).await.is_none());
+}
+
+#[tokio::test]
+async fn test_expired_token_handshake() {
+    let addr = router(Some(Duration::from_millis(50))).await;
+    let token = bind(addr, "Jeff").await.token.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(handshake(addr, &token).await.1.code, ResponseCode::Unauthorized);
+
+    // Sweeper already freed it
+    assert_eq!(bind(addr, "Jeff").await.code, ResponseCode::AuthOK);
+}
@@ -0,0 +1,81 @@
+//! # Tests for running the router over other transports
+
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::protocol::response::{Response, ResponseCode};
+use crate::router::{Connection, MemoryConnector, MemoryListener, RouterBuilder, UNSPECIFIED_ADDR};
+use std::time::Duration;
+
+async fn round_trip<C: Connection>(reader: &mut FrameReader<C>, request: String) -> Response {
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+
+    let frame = tokio::time::timeout(Duration::from_secs(2), reader.read_frame()).await.unwrap().unwrap().unwrap();
+    Response::from_bytes(&frame).unwrap()
+}
+
+async fn chat(connector: &MemoryConnector) {
+    let codec = FrameCodec::default();
+
+    let mut bind = FrameReader::new(connector.connect().await.unwrap(), codec);
+    let token = round_trip(&mut bind, "<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'Jeff'>".to_string()).await.token.unwrap();
+
+    let mut jeff = FrameReader::new(connector.connect().await.unwrap(), codec);
+    let res = round_trip(&mut jeff, format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>")).await;
+    assert_eq!(res.code, ResponseCode::AuthOK);
+
+    let res = round_trip(&mut jeff, "<CHAT \\ 1.1>\n<Method@Send>\n<Message@'Hi'>".to_string()).await;
+    assert_eq!(res.message.as_deref(), Some("Hi"));
+}
+
+#[tokio::test]
+async fn test_memory_listener() {
+    let (listener, connector) = MemoryListener::new();
+    let router = RouterBuilder::new().build().with_listener(listener).unwrap();
+    assert_eq!(router.local_addr(), UNSPECIFIED_ADDR);
+
+    tokio::spawn(router.run());
+    chat(&connector).await;
+}
+
+#[tokio::test]
+async fn test_memory_listener_is_gone() {
+    let (listener, connector) = MemoryListener::new();
+    drop(listener);
+
+    let err = connector.connect().await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::ConnectionRefused);
+}
+
+#[tokio::test]
+async fn test_pre_bound_listener() {
+    // Same as the socket, that was handed by systemd
+    let std_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    std_listener.set_nonblocking(true).unwrap();
+    let addr = std_listener.local_addr().unwrap();
+
+    let listener = tokio::net::TcpListener::from_std(std_listener).unwrap();
+    let router = RouterBuilder::new().build().with_listener(listener).unwrap();
+    assert_eq!(router.local_addr(), addr);
+
+    tokio::spawn(router.run());
+
+    let mut reader = FrameReader::new(tokio::net::TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    let res = round_trip(&mut reader, "<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'Jeff'>".to_string()).await;
+    assert_eq!(res.code, ResponseCode::AuthOK);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_listener() {
+    let path = std::env::temp_dir().join(format!("pinguino-{0}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+    let router = RouterBuilder::new().build().with_listener(listener).unwrap();
+    tokio::spawn(router.run());
+
+    let mut reader = FrameReader::new(tokio::net::UnixStream::connect(&path).await.unwrap(), FrameCodec::default());
+    let res = round_trip(&mut reader, "<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'Jeff'>".to_string()).await;
+    assert_eq!(res.code, ResponseCode::AuthOK);
+
+    let _ = std::fs::remove_file(&path);
+}
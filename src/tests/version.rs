@@ -0,0 +1,115 @@
+//! # Tests for `CHAT 1.1` and version negotiation
+
+use crate::protocol::parser::peek_version;
+use crate::protocol::request::{ParseErrorKind, RawRequest, Request, Version};
+use crate::protocol::response::{Response, ResponseBuilder, ResponseCode};
+use crate::protocol::wares::starting_bytesware::{DefaultStartingBytesware, StartingBytesware};
+use crate::protocol::Varmap;
+use crate::router::{App, State};
+use bytes::Bytes;
+use regex::Regex;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+fn addr() -> Arc<SocketAddr> {
+    Arc::new(SocketAddr::from_str("127.0.0.1:3000").unwrap())
+}
+
+// Does the `1.0` parser (the regex one, that old clients have) accept the whole response
+fn old_client_accepts(bytes: &[u8]) -> bool {
+    let header = Regex::new(r"^<CHAT \\ (?P<version>[1-9]\.[0-9])>$").unwrap();
+    let line = Regex::new(r"^<(?P<key>[a-zA-Z]+)@(?P<value>'[^']+'|\b\w+\b)>$").unwrap();
+
+    let text = std::str::from_utf8(bytes).unwrap();
+    let mut lines = text.split('\n');
+    lines.next().is_some_and(|val| header.is_match(val)) && lines.all(|val| line.is_match(val))
+}
+
+async fn starting(bytes: &'static [u8]) -> Result<Request, Response> {
+    let app = Arc::new(Mutex::new(App::new(Varmap::new())));
+    let state = Arc::new(Mutex::new(State::new(app, Arc::new(None))));
+    let raw_req = RawRequest {
+        bytes: Bytes::from_static(bytes),
+        addr: addr(),
+    };
+
+    DefaultStartingBytesware.bytesware(state, raw_req).await
+}
+
+#[test]
+fn test_both_versions_parse() {
+    let old = Request::parse("<CHAT \\ 1.0>\n<Method@Send>\n<Message@'Hi'>", addr()).unwrap();
+    let new = Request::parse("<CHAT \\ 1.1>\n<Method@Send>\n<Message@'Hi'>", addr()).unwrap();
+
+    assert_eq!(old.version, Version::CHAT10);
+    assert_eq!(new.version, Version::CHAT11);
+    assert!(Version::CHAT10 < Version::LATEST);
+
+    let err = Request::parse("<CHAT \\ 9.9>\n<Method@Send>\n<Message@'Hi'>", addr()).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::UnsupportedVersion);
+}
+
+#[test]
+fn test_new_headers_are_not_sent_to_old_clients() {
+    // Reason quotes the header, so it has the escaped quotes
+    let response = |version| ResponseBuilder::new()
+        .version(version)
+        .code(ResponseCode::ParseError)
+        .reason("bad header `<Name@'Jeff'>`".to_string())
+        .custom_insert("Supported".to_string(), "1.1".to_string())
+        .custom_insert("RetryAfter".to_string(), "250".to_string())
+        .custom_insert("Id".to_string(), "7".to_string())
+        .custom_insert("Mood".to_string(), "ok".to_string())
+        .build()
+        .unwrap();
+
+    let old = response(Version::CHAT10).as_bytes().unwrap();
+    assert!(old_client_accepts(&old));
+
+    let old = Response::from_bytes(&old).unwrap();
+    assert_eq!(old.version, Version::CHAT10);
+    assert_eq!(old.reason, None);
+    assert_eq!(old.custom.unwrap().len(), 1);
+
+    // `1.1` gets everything, and `1.0` parser would choke on it
+    let new = response(Version::CHAT11).as_bytes().unwrap();
+    assert!(!old_client_accepts(&new));
+
+    let new = Response::from_bytes(&new).unwrap();
+    assert_eq!(new.reason.as_deref(), Some("bad header `<Name@'Jeff'>`"));
+    assert_eq!(new.custom.unwrap().len(), 4);
+
+    // Pretty string follows the same rules
+    assert!(!response(Version::CHAT10).pretty_string().contains("Reason"));
+}
+
+#[test]
+fn test_peek_version() {
+    assert_eq!(peek_version(b"<CHAT \\ 1.0>\n<Method@Bind>"), Some(Version::CHAT10));
+    assert_eq!(peek_version(b"<CHAT \\ 1.1>\n<Me\xffthod@Bind>"), Some(Version::CHAT11));
+    assert_eq!(peek_version(b"<CHAT \\ 9.9>"), None);
+    assert_eq!(peek_version(b"garbage"), None);
+}
+
+#[tokio::test]
+async fn test_errors_are_answered_in_client_version() {
+    let old = starting(b"<CHAT \\ 1.0>\n<Method@Send>\n<Name@Jeff>").await.unwrap_err();
+    assert_eq!(old.version, Version::CHAT10);
+    assert_eq!(old.code, ResponseCode::ParseError);
+    // `1.0` doesnt know about `<Reason@...>`
+    assert_eq!(Response::from_bytes(&old.as_bytes().unwrap()).unwrap().reason, None);
+    assert!(old_client_accepts(&old.as_bytes().unwrap()));
+
+    let new = starting(b"<CHAT \\ 1.1>\n<Method@Send>\n<Name@Jeff>").await.unwrap_err();
+    assert_eq!(new.version, Version::CHAT11);
+}
+
+#[tokio::test]
+async fn test_unknown_version_is_rejected() {
+    let response = starting(b"<CHAT \\ 2.0>\n<Method@Bind>\n<Name@'Jeff'>").await.unwrap_err();
+
+    assert_eq!(response.code, ResponseCode::UnsupportedVersion);
+    assert_eq!(response.version, Version::LATEST);
+}
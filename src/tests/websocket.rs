@@ -0,0 +1,120 @@
+//! # Tests for the WebSocket gateway
+
+use crate::protocol::frame::{write_frame, FrameCodec, FrameReader};
+use crate::protocol::response::{Response, ResponseCode};
+use crate::router::RouterBuilder;
+use futures_util::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::{Bytes, Message};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+async fn router() -> (SocketAddr, SocketAddr) {
+    let router = RouterBuilder::new()
+        .port(0)
+        .websocket(0)
+        .build()
+        .bind()
+        .await
+        .unwrap();
+
+    let addrs = (router.local_addr(), router.websocket_addr().unwrap());
+    tokio::spawn(router.run());
+    addrs
+}
+
+async fn ws_next(ws: &mut Ws) -> Response {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(2), ws.next()).await.unwrap().unwrap().unwrap();
+        match message {
+            Message::Text(text) => return Response::from_bytes(text.as_bytes()).unwrap(),
+            Message::Binary(payload) => return Response::from_bytes(&payload).unwrap(),
+            _ => continue,
+        }
+    }
+}
+
+async fn ws_round_trip(ws: &mut Ws, request: String) -> Response {
+    ws.send(Message::text(request)).await.unwrap();
+    ws_next(ws).await
+}
+
+async fn tcp_next(reader: &mut FrameReader<TcpStream>) -> Response {
+    let frame = tokio::time::timeout(Duration::from_secs(2), reader.read_frame()).await.unwrap().unwrap().unwrap();
+    Response::from_bytes(&frame).unwrap()
+}
+
+async fn tcp_round_trip(reader: &mut FrameReader<TcpStream>, request: String) -> Response {
+    write_frame(reader.get_mut(), &FrameCodec::default(), request.as_bytes()).await.unwrap();
+    tcp_next(reader).await
+}
+
+// Binds the name over the WebSocket and makes the long-lived connection with it
+async fn ws_user(ws_addr: SocketAddr, name: &str) -> Ws {
+    let (mut bind, _) = connect_async(format!("ws://{ws_addr}")).await.unwrap();
+    let token = ws_round_trip(&mut bind, format!("<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'{name}'>")).await.token.unwrap();
+
+    let (mut ws, _) = connect_async(format!("ws://{ws_addr}")).await.unwrap();
+    let res = ws_round_trip(&mut ws, format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>")).await;
+    assert_eq!(res.code, ResponseCode::AuthOK);
+
+    ws
+}
+
+async fn tcp_user(addr: SocketAddr, name: &str) -> FrameReader<TcpStream> {
+    let mut bind = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    let token = tcp_round_trip(&mut bind, format!("<CHAT \\ 1.1>\n<Method@Bind>\n<Name@'{name}'>")).await.token.unwrap();
+
+    let mut reader = FrameReader::new(TcpStream::connect(addr).await.unwrap(), FrameCodec::default());
+    let res = tcp_round_trip(&mut reader, format!("<CHAT \\ 1.1>\n<Method@Handshake>\n<Authorization@'{token}'>")).await;
+    assert_eq!(res.code, ResponseCode::AuthOK);
+
+    reader
+}
+
+#[tokio::test]
+async fn test_websocket_chat() {
+    let (_, ws_addr) = router().await;
+    let mut ws = ws_user(ws_addr, "Jeff").await;
+
+    let res = ws_round_trip(&mut ws, "<CHAT \\ 1.1>\n<Method@Send>\n<Message@'Hi from the browser'>".to_string()).await;
+    assert_eq!(res.message.as_deref(), Some("Hi from the browser"));
+}
+
+#[tokio::test]
+async fn test_binary_message() {
+    let (_, ws_addr) = router().await;
+    let mut ws = ws_user(ws_addr, "Jeff").await;
+
+    let request = Bytes::from_static(b"<CHAT \\ 1.1>\n<Method@Send>\n<Message@'Binary hi'>");
+    ws.send(Message::Binary(request)).await.unwrap();
+
+    let res = ws_next(&mut ws).await;
+    assert_eq!(res.message.as_deref(), Some("Binary hi"));
+}
+
+#[tokio::test]
+async fn test_websocket_and_tcp_share_chat() {
+    let (addr, ws_addr) = router().await;
+    let mut ws = ws_user(ws_addr, "Ann").await;
+    let mut tcp = tcp_user(addr, "Jeff").await;
+
+    // WebSocket -> TCP
+    ws.send(Message::text("<CHAT \\ 1.1>\n<Method@Send>\n<Message@'Hi Jeff'>")).await.unwrap();
+    assert_eq!(ws_next(&mut ws).await.message.as_deref(), Some("Hi Jeff"));
+    assert_eq!(tcp_next(&mut tcp).await.message.as_deref(), Some("Hi Jeff"));
+
+    // TCP -> WebSocket
+    write_frame(tcp.get_mut(), &FrameCodec::default(), b"<CHAT \\ 1.1>\n<Method@Send>\n<Message@'Hi Ann'>").await.unwrap();
+    assert_eq!(tcp_next(&mut tcp).await.message.as_deref(), Some("Hi Ann"));
+    assert_eq!(ws_next(&mut ws).await.message.as_deref(), Some("Hi Ann"));
+}
+
+#[tokio::test]
+async fn test_no_websocket_by_default() {
+    let router = RouterBuilder::new().port(0).build().bind().await.unwrap();
+    assert!(router.websocket_addr().is_none());
+}